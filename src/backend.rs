@@ -0,0 +1,62 @@
+use crate::{InputSynth, Result};
+
+/// The basic operations every way of injecting input supports, so code can drive X11 and
+/// Wayland sessions alike. Create one with [`crate::InputSynthBuilder::build_backend`].
+pub trait InputBackend: Send + Sync {
+    /// Move the pointer to `(x, y)`.
+    fn move_cursor(&self, x: i16, y: i16) -> Result<()>;
+
+    /// Press, or release if `press` is false, pointer `button` at `(x, y)`. Buttons are
+    /// numbered like in X11: 1 to 3 are left, middle and right, 4 to 7 scroll.
+    fn click(&self, x: i16, y: i16, button: u8, press: bool) -> Result<()>;
+
+    /// Press, or release if `press` is false, the key producing `keysym`. Pressing also holds the
+    /// modifiers needed to produce it, until the key is released again.
+    fn key(&self, keysym: u32, press: bool) -> Result<()>;
+}
+
+/// Which [`InputBackend`] [`crate::InputSynthBuilder::build_backend`] creates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BackendKind {
+    /// Wayland if `$WAYLAND_DISPLAY` is set and Wayland support is compiled in, X11 otherwise.
+    #[default]
+    Auto,
+    X11,
+    /// Needs the `wayland` feature.
+    Wayland,
+    /// A virtual kernel input device, using the keymap and screen size of the X server. Needs the
+    /// `uinput` feature.
+    Uinput,
+}
+
+/// The X11 backend, injecting events through XTEST.
+pub type X11Backend = InputSynth;
+
+impl InputBackend for InputSynth {
+    fn move_cursor(&self, x: i16, y: i16) -> Result<()> {
+        InputSynth::move_cursor(self, x, y)
+    }
+
+    fn click(&self, x: i16, y: i16, button: u8, press: bool) -> Result<()> {
+        InputSynth::click(self, x, y, button, press)
+    }
+
+    fn key(&self, keysym: u32, press: bool) -> Result<()> {
+        InputSynth::key(self, keysym, press)
+    }
+}
+
+#[cfg(feature = "mock")]
+impl InputBackend for crate::MockInputSynth {
+    fn move_cursor(&self, x: i16, y: i16) -> Result<()> {
+        crate::MockInputSynth::move_cursor(self, x, y)
+    }
+
+    fn click(&self, x: i16, y: i16, button: u8, press: bool) -> Result<()> {
+        crate::MockInputSynth::click(self, x, y, button, press)
+    }
+
+    fn key(&self, keysym: u32, press: bool) -> Result<()> {
+        crate::MockInputSynth::key(self, keysym, press)
+    }
+}
@@ -0,0 +1,257 @@
+//! Command line access to the library, in the style of xdotool.
+
+use std::process::ExitCode;
+use std::time::Duration;
+
+use inputsynth::{Error, InputSynth, KeyCombo, TypingDelay};
+
+const USAGE: &str = "\
+usage: inputsynth [--display DISPLAY] [--delay MS] COMMAND
+
+commands:
+    type TEXT                   type TEXT
+    key COMBO                   press a key combination, e.g. ctrl+shift+t
+    click X Y [--button N]      click button N (default 1) at (X, Y)
+    move X Y                    move the pointer to (X, Y)
+    scroll AMOUNT               scroll down by AMOUNT steps, up if negative";
+
+/// Exit codes, so scripts can tell failures apart.
+const EXIT_USAGE: u8 = 1;
+const EXIT_CONNECTION: u8 = 2;
+const EXIT_UNMAPPABLE: u8 = 3;
+const EXIT_OTHER: u8 = 4;
+
+enum Command {
+    Type(String),
+    Key(KeyCombo),
+    Click { x: i16, y: i16, button: u8 },
+    Move { x: i16, y: i16 },
+    Scroll(i32),
+}
+
+struct Args {
+    display: Option<String>,
+    delay: Option<Duration>,
+    command: Command,
+}
+
+/// Why the command line wasn't turned into [`Args`].
+#[derive(Debug)]
+enum ArgsError {
+    /// `--help` was given.
+    Help,
+    /// The command line is malformed, with a message saying how.
+    Usage(String),
+    /// An argument is well-formed, but names something that doesn't exist, like an unknown key.
+    Invalid(Error),
+}
+
+impl From<&str> for ArgsError {
+    fn from(message: &str) -> Self {
+        ArgsError::Usage(message.to_owned())
+    }
+}
+
+impl From<String> for ArgsError {
+    fn from(message: String) -> Self {
+        ArgsError::Usage(message)
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, ArgsError> {
+    fn number<T: std::str::FromStr>(arg: Option<String>, what: &str) -> Result<T, String> {
+        let arg = arg.ok_or_else(|| format!("missing {what}"))?;
+        arg.parse().map_err(|_| format!("invalid {what}: {arg}"))
+    }
+
+    let mut display = None;
+    let mut delay = None;
+    let mut positional = Vec::new();
+    let mut button = 1;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--display" => display = Some(args.next().ok_or("missing display")?),
+            "--delay" => delay = Some(Duration::from_millis(number(args.next(), "delay")?)),
+            "--button" => button = number(args.next(), "button")?,
+            "-h" | "--help" => return Err(ArgsError::Help),
+            _ => positional.push(arg),
+        }
+    }
+    let mut positional = positional.into_iter();
+    let command = match positional.next().as_deref() {
+        Some("type") => Command::Type(positional.next().ok_or("missing text")?),
+        Some("key") => {
+            let combo = positional.next().ok_or("missing key combination")?;
+            Command::Key(combo.parse().map_err(ArgsError::Invalid)?)
+        }
+        Some("click") => Command::Click {
+            x: number(positional.next(), "x")?,
+            y: number(positional.next(), "y")?,
+            button,
+        },
+        Some("move") => Command::Move {
+            x: number(positional.next(), "x")?,
+            y: number(positional.next(), "y")?,
+        },
+        Some("scroll") => Command::Scroll(number(positional.next(), "amount")?),
+        Some(command) => return Err(format!("unknown command: {command}").into()),
+        None => return Err("missing command".into()),
+    };
+    if let Some(extra) = positional.next() {
+        return Err(format!("unexpected argument: {extra}").into());
+    }
+    Ok(Args {
+        display,
+        delay,
+        command,
+    })
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    let mut builder = InputSynth::builder();
+    if let Some(display) = args.display {
+        builder = builder.display(display);
+    }
+    if let Some(delay) = args.delay {
+        builder = builder.typing_delay(TypingDelay::Fixed(delay));
+    }
+    let synth = builder.build()?;
+    match args.command {
+        Command::Type(text) => synth.type_string(&text),
+        Command::Key(combo) => synth.key_combo(&combo),
+        Command::Click { x, y, button } => synth.click_at(x, y, button),
+        Command::Move { x, y } => synth.move_cursor(x, y),
+        Command::Scroll(amount) => {
+            // Buttons 4 and 5 scroll up and down. The position doesn't matter for buttons.
+            let button = if amount < 0 { 4 } else { 5 };
+            for _ in 0..amount.unsigned_abs() {
+                synth.click(0, 0, button, true)?;
+                synth.click(0, 0, button, false)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// The exit code for a failure with `error`.
+fn exit_code(error: &Error) -> u8 {
+    match error {
+        Error::Connect { .. }
+        | Error::Connection(_)
+        | Error::ExtensionMissing { .. }
+        | Error::NoKeyboardDevice
+        | Error::InvalidScreen(_) => EXIT_CONNECTION,
+        Error::NoKeySequence { .. }
+        | Error::Untypeable(_)
+        | Error::TypingFailed { .. }
+        | Error::UnknownKeyName(_) => EXIT_UNMAPPABLE,
+        _ => EXIT_OTHER,
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(ArgsError::Help) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(ArgsError::Usage(message)) => {
+            eprintln!("inputsynth: {message}");
+            eprintln!("{USAGE}");
+            return ExitCode::from(EXIT_USAGE);
+        }
+        Err(ArgsError::Invalid(e)) => {
+            eprintln!("inputsynth: {e}");
+            return ExitCode::from(exit_code(&e));
+        }
+    };
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("inputsynth: {e}");
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use inputsynth::Error;
+
+    use super::{
+        exit_code, parse_args, Args, ArgsError, Command, EXIT_CONNECTION, EXIT_OTHER,
+        EXIT_UNMAPPABLE,
+    };
+
+    fn parse(args: &str) -> Result<Args, ArgsError> {
+        parse_args(args.split_whitespace().map(str::to_owned))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = parse("--display :1 --delay 20 click 10 20 --button 3").unwrap();
+        assert_eq!(args.display.as_deref(), Some(":1"));
+        assert_eq!(args.delay, Some(Duration::from_millis(20)));
+        assert!(matches!(
+            args.command,
+            Command::Click {
+                x: 10,
+                y: 20,
+                button: 3
+            }
+        ));
+
+        let args = parse("type hello").unwrap();
+        assert_eq!(args.display, None);
+        assert!(matches!(args.command, Command::Type(text) if text == "hello"));
+        assert!(matches!(
+            parse("key ctrl+t").unwrap().command,
+            Command::Key(_)
+        ));
+        assert!(matches!(
+            parse("move 1 2").unwrap().command,
+            Command::Move { x: 1, y: 2 }
+        ));
+        assert!(matches!(
+            parse("scroll -3").unwrap().command,
+            Command::Scroll(-3)
+        ));
+
+        assert!(matches!(parse("--help"), Err(ArgsError::Help)));
+        assert!(matches!(parse("type a -h"), Err(ArgsError::Help)));
+        for malformed in [
+            "", "jump", "type", "click 1", "move x 2", "type a b", "--delay",
+        ] {
+            assert!(
+                matches!(parse(malformed), Err(ArgsError::Usage(_))),
+                "{malformed:?}"
+            );
+        }
+        assert!(matches!(
+            parse("key ctrl+nosuchkey"),
+            Err(ArgsError::Invalid(Error::UnknownKeyName(name))) if name == "nosuchkey"
+        ));
+    }
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(
+            exit_code(&Error::UnknownKeyName("nosuchkey".into())),
+            EXIT_UNMAPPABLE
+        );
+        assert_eq!(exit_code(&Error::Untypeable('\u{1f600}')), EXIT_UNMAPPABLE);
+        assert_eq!(
+            exit_code(&Error::NoKeySequence { keysym: 0x20ac }),
+            EXIT_UNMAPPABLE
+        );
+        assert_eq!(exit_code(&Error::NoKeyboardDevice), EXIT_CONNECTION);
+        assert_eq!(exit_code(&Error::InvalidScreen(1)), EXIT_CONNECTION);
+        assert_eq!(
+            exit_code(&Error::Timeout(Duration::from_secs(1))),
+            EXIT_OTHER
+        );
+    }
+}
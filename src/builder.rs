@@ -0,0 +1,201 @@
+use std::time::Duration;
+
+#[cfg(feature = "rand")]
+use crate::Humanize;
+use crate::{
+    BackendKind, InputBackend, InputSynth, NewlinePolicy, RateLimit, RequestChecking, Result,
+    TypingDelay,
+};
+
+/// What to do when the connection to the X server is lost, e.g. because the server restarted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReconnectPolicy {
+    /// Fail with the connection error.
+    #[default]
+    Never,
+    /// Make up to this many attempts to connect to the same display again, with increasing
+    /// delays between them. When a connection is made, the keymap is loaded again, the
+    /// interrupted call is repeated once, and the callback set with
+    /// [`InputSynth::set_reconnect_callback`] is called. `Retries(0)` is the same as `Never`.
+    ///
+    /// Only applies to instances that opened their own connection, not those created with
+    /// [`InputSynth::from_connection`].
+    Retries(u32),
+}
+
+impl ReconnectPolicy {
+    /// How many attempts to connect again are made, 0 for [`Self::Never`].
+    pub(crate) fn attempts(self) -> u32 {
+        match self {
+            Self::Never => 0,
+            Self::Retries(retries) => retries,
+        }
+    }
+}
+
+/// Configures and creates an [`InputSynth`]. Obtained from [`InputSynth::builder`].
+///
+/// The builder can be cloned to create several instances with the same configuration.
+#[derive(Debug, Clone)]
+pub struct InputSynthBuilder {
+    pub(crate) display: Option<String>,
+    pub(crate) screen: Option<usize>,
+    pub(crate) keyboard_device: Option<u8>,
+    pub(crate) typing_delay: TypingDelay,
+    pub(crate) newline_policy: NewlinePolicy,
+    pub(crate) rate_limit: RateLimit,
+    #[cfg(feature = "rand")]
+    pub(crate) humanize: Option<Humanize>,
+    pub(crate) selection_timeout: Duration,
+    pub(crate) reconnect_policy: ReconnectPolicy,
+    pub(crate) impervious_to_grabs: bool,
+    pub(crate) fail_on_grab: bool,
+    pub(crate) request_checking: RequestChecking,
+    pub(crate) coordinate_scale: f64,
+    pub(crate) backend: BackendKind,
+}
+
+impl Default for InputSynthBuilder {
+    fn default() -> Self {
+        Self {
+            display: None,
+            screen: None,
+            keyboard_device: None,
+            typing_delay: TypingDelay::default(),
+            newline_policy: NewlinePolicy::default(),
+            rate_limit: RateLimit::default(),
+            #[cfg(feature = "rand")]
+            humanize: None,
+            selection_timeout: Duration::from_secs(1),
+            reconnect_policy: ReconnectPolicy::default(),
+            impervious_to_grabs: false,
+            fail_on_grab: false,
+            request_checking: RequestChecking::default(),
+            coordinate_scale: 1.0,
+            backend: BackendKind::default(),
+        }
+    }
+}
+
+impl InputSynthBuilder {
+    /// Connect to `display` instead of the one named by `$DISPLAY`. Unless [`Self::screen`] is
+    /// also used, the screen is the one given in the display string, e.g. 1 for `":0.1"`.
+    pub fn display(mut self, display: impl Into<String>) -> Self {
+        self.display = Some(display.into());
+        self
+    }
+
+    /// Inject events on `screen` instead of the display's default screen.
+    pub fn screen(mut self, screen: usize) -> Self {
+        self.screen = Some(screen);
+        self
+    }
+
+    /// See [`InputSynth::set_keyboard_device`].
+    pub fn keyboard_device(mut self, id: u8) -> Self {
+        self.keyboard_device = Some(id);
+        self
+    }
+
+    /// See [`InputSynth::set_typing_delay`].
+    pub fn typing_delay(mut self, delay: TypingDelay) -> Self {
+        self.typing_delay = delay;
+        self
+    }
+
+    /// See [`InputSynth::set_newline_policy`].
+    pub fn newline_policy(mut self, policy: NewlinePolicy) -> Self {
+        self.newline_policy = policy;
+        self
+    }
+
+    /// See [`InputSynth::set_rate_limit`].
+    pub fn rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limit = limit;
+        self
+    }
+
+    /// See [`InputSynth::set_humanize`].
+    #[cfg(feature = "rand")]
+    pub fn humanize(mut self, config: Humanize) -> Self {
+        self.humanize = Some(config);
+        self
+    }
+
+    /// See [`InputSynth::set_selection_timeout`].
+    pub fn selection_timeout(mut self, timeout: Duration) -> Self {
+        self.selection_timeout = timeout;
+        self
+    }
+
+    /// Reconnect when the connection to the X server is lost, see [`ReconnectPolicy`].
+    pub fn reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = policy;
+        self
+    }
+
+    /// See [`InputSynth::set_grab_control`].
+    pub fn impervious_to_grabs(mut self, impervious: bool) -> Self {
+        self.impervious_to_grabs = impervious;
+        self
+    }
+
+    /// See [`InputSynth::set_fail_on_grab`].
+    pub fn fail_on_grab(mut self, fail: bool) -> Self {
+        self.fail_on_grab = fail;
+        self
+    }
+
+    /// See [`InputSynth::set_coordinate_scale`]. Building fails with
+    /// [`Error::InvalidScale`] if `scale` isn't positive.
+    pub fn coordinate_scale(mut self, scale: f64) -> Self {
+        self.coordinate_scale = scale;
+        self
+    }
+
+    /// See [`InputSynth::set_request_checking`].
+    pub fn request_checking(mut self, checking: RequestChecking) -> Self {
+        self.request_checking = checking;
+        self
+    }
+
+    /// Choose the backend created by [`Self::build_backend`].
+    pub fn backend(mut self, backend: BackendKind) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Connect to the X server and create the [`InputSynth`].
+    pub fn build(&self) -> Result<InputSynth> {
+        InputSynth::from_builder(self)
+    }
+
+    /// Create the backend selected with [`Self::backend`]. X11 backends are configured by this
+    /// builder, Wayland backends connect to `$WAYLAND_DISPLAY`.
+    pub fn build_backend(&self) -> Result<Box<dyn InputBackend>> {
+        let backend = match self.backend {
+            BackendKind::Auto
+                if cfg!(feature = "wayland") && std::env::var_os("WAYLAND_DISPLAY").is_some() =>
+            {
+                BackendKind::Wayland
+            }
+            BackendKind::Auto => BackendKind::X11,
+            backend => backend,
+        };
+        match backend {
+            #[cfg(feature = "wayland")]
+            BackendKind::Wayland => Ok(Box::new(crate::WaylandBackend::new()?)),
+            #[cfg(not(feature = "wayland"))]
+            BackendKind::Wayland => Err(crate::Error::Unsupported(
+                "Wayland without the `wayland` feature",
+            )),
+            #[cfg(feature = "uinput")]
+            BackendKind::Uinput => Ok(Box::new(crate::UinputBackend::new(&self.build()?)?)),
+            #[cfg(not(feature = "uinput"))]
+            BackendKind::Uinput => Err(crate::Error::Unsupported(
+                "uinput without the `uinput` feature",
+            )),
+            _ => Ok(Box::new(self.build()?)),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::{Condvar, Mutex, PoisonError};
+use std::thread::{self, ThreadId};
+
+use crate::lock;
+
+/// Serializes public calls on an `InputSynth`, so events from concurrent calls are never
+/// interleaved. The thread holding the lock can take it again, which lets public methods call
+/// each other.
+#[derive(Debug, Default)]
+pub(crate) struct CallLock {
+    /// The thread holding the lock, and how many times it has taken it.
+    owner: Mutex<Option<(ThreadId, usize)>>,
+    released: Condvar,
+}
+
+pub(crate) struct CallGuard<'a> {
+    lock: &'a CallLock,
+    outermost: bool,
+}
+
+impl CallLock {
+    pub(crate) fn enter(&self) -> CallGuard<'_> {
+        let me = thread::current().id();
+        let mut owner = lock(&self.owner);
+        let outermost = loop {
+            match &mut *owner {
+                Some((thread, depth)) if *thread == me => {
+                    *depth += 1;
+                    break false;
+                }
+                Some(_) => {
+                    owner = self
+                        .released
+                        .wait(owner)
+                        .unwrap_or_else(PoisonError::into_inner)
+                }
+                None => {
+                    *owner = Some((me, 1));
+                    break true;
+                }
+            }
+        };
+        CallGuard {
+            lock: self,
+            outermost,
+        }
+    }
+}
+
+impl CallGuard<'_> {
+    /// Whether this guard isn't nested inside another one on the same thread.
+    pub(crate) fn is_outermost(&self) -> bool {
+        self.outermost
+    }
+}
+
+impl Drop for CallGuard<'_> {
+    fn drop(&mut self) {
+        let mut owner = lock(&self.lock.owner);
+        if let Some((_, depth)) = &mut *owner {
+            *depth -= 1;
+            if *depth == 0 {
+                *owner = None;
+                self.lock.released.notify_one();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CallLock;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_call_lock() {
+        let lock = CallLock::default();
+        let inside = AtomicUsize::new(0);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..100 {
+                        let outer = lock.enter();
+                        let inner = lock.enter();
+                        assert!(outer.is_outermost() && !inner.is_outermost());
+                        assert_eq!(inside.fetch_add(1, Ordering::SeqCst), 0);
+                        std::thread::yield_now();
+                        inside.fetch_sub(1, Ordering::SeqCst);
+                    }
+                });
+            }
+        });
+    }
+}
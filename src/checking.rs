@@ -0,0 +1,117 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use x11rb::errors::ReplyError;
+use x11rb::protocol::xproto;
+use x11rb::x11_utils::X11Error;
+
+use crate::Error;
+
+/// Whether to wait for the server to accept each synthesized event, see
+/// [`crate::InputSynth::set_request_checking`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RequestChecking {
+    /// Make a round trip to the server for every event, and fail right away if it is rejected.
+    /// Much slower, especially with a remote server.
+    Checked,
+    /// Send events in batches. Rejected events are reported by the next call that reads
+    /// events from the server, or by [`crate::InputSynth::sync`].
+    #[default]
+    Unchecked,
+}
+
+/// An event we asked XTEST to synthesize, to say which one failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FakeInput {
+    /// The public method that sent it, like `"click"`.
+    pub method: &'static str,
+    /// The core event type, like `KeyPress`.
+    pub event_type: u8,
+    /// The keycode or button.
+    pub detail: u8,
+    pub x: i16,
+    pub y: i16,
+}
+
+impl FakeInput {
+    /// The error to report for `error` in response to this event.
+    pub(crate) fn error(self, error: ReplyError) -> Error {
+        match error {
+            ReplyError::X11Error(error) => Error::FakeInputFailed { input: self, error },
+            error => error.into(),
+        }
+    }
+}
+
+/// Formats as e.g. `key press of keycode 38` or `motion to (10, 20)`.
+impl fmt::Display for FakeInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.event_type {
+            xproto::KEY_PRESS_EVENT => write!(f, "key press of keycode {}", self.detail),
+            xproto::KEY_RELEASE_EVENT => write!(f, "key release of keycode {}", self.detail),
+            xproto::BUTTON_PRESS_EVENT => write!(f, "press of button {}", self.detail),
+            xproto::BUTTON_RELEASE_EVENT => write!(f, "release of button {}", self.detail),
+            xproto::MOTION_NOTIFY_EVENT => write!(f, "motion to ({}, {})", self.x, self.y),
+            event_type => write!(f, "event {event_type} with detail {}", self.detail),
+        }
+    }
+}
+
+/// Events sent without checking, by sequence number, so errors for them can be reported.
+#[derive(Debug, Default)]
+pub(crate) struct PendingInputs {
+    inputs: VecDeque<(u64, FakeInput)>,
+}
+
+impl PendingInputs {
+    /// Errors for older events are no longer attributed to them.
+    const CAPACITY: usize = 1024;
+
+    pub(crate) fn push(&mut self, sequence: u64, input: FakeInput) {
+        if self.inputs.len() == Self::CAPACITY {
+            self.inputs.pop_front();
+        }
+        self.inputs.push_back((sequence, input));
+    }
+
+    /// The event `error` is about, if it's one of ours.
+    pub(crate) fn take(&mut self, error: &X11Error) -> Option<FakeInput> {
+        // Errors only carry the lower 16 bits of the sequence number.
+        let index = self
+            .inputs
+            .iter()
+            .position(|&(sequence, _)| sequence as u16 == error.sequence)?;
+        self.inputs.remove(index).map(|(_, input)| input)
+    }
+
+    /// Forget about the events sent so far, once the server is known to have processed them.
+    pub(crate) fn clear(&mut self) {
+        self.inputs.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use x11rb::protocol::xproto;
+
+    use super::FakeInput;
+
+    #[test]
+    fn test_display() {
+        let input = |event_type, detail, x, y| FakeInput {
+            method: "test",
+            event_type,
+            detail,
+            x,
+            y,
+        };
+        assert_eq!(
+            input(xproto::KEY_PRESS_EVENT, 38, 0, 0).to_string(),
+            "key press of keycode 38"
+        );
+        assert_eq!(
+            input(xproto::MOTION_NOTIFY_EVENT, 0, 10, 20).to_string(),
+            "motion to (10, 20)"
+        );
+    }
+}
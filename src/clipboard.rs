@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    self, Atom, AtomEnum, ChangeWindowAttributesAux, ConnectionExt as _, CreateWindowAux,
+    EventMask, PropMode, Property, PropertyNotifyEvent, SelectionClearEvent, SelectionNotifyEvent,
+    SelectionRequestEvent, WindowClass,
+};
+use x11rb::wrapper::ConnectionExt as _;
+
+use crate::Result;
+
+/// Selections owned by us, and the window used to own them.
+pub(crate) struct Selections {
+    window: xproto::Window,
+    pub(crate) clipboard: Atom,
+    utf8_string: Atom,
+    targets: Atom,
+    incr: Atom,
+    /// Property on `window` that selection contents we asked for are delivered to.
+    transfer: Atom,
+    contents: HashMap<Atom, String>,
+    /// Texts too large for one request, being handed out in chunks with the INCR protocol.
+    transfers: Vec<IncrTransfer>,
+}
+
+/// An INCR transfer of a selection to `requestor`. The requestor deletes `property` each time
+/// it has read a chunk, and we answer with the next one, ending with an empty one.
+struct IncrTransfer {
+    requestor: xproto::Window,
+    property: Atom,
+    target: Atom,
+    data: Vec<u8>,
+    /// How much of `data` was handed out.
+    sent: usize,
+}
+
+/// The largest property we write at once, within the request size the server accepts.
+fn chunk_size(conn: &impl Connection) -> usize {
+    /// Room for the rest of the ChangeProperty request.
+    const HEADER: usize = 64;
+    /// More than this at once only makes us hold on to the connection for long.
+    const MAX: usize = 1 << 20;
+    conn.maximum_request_bytes().saturating_sub(HEADER).min(MAX)
+}
+
+/// The contents of a selection, as delivered by its owner.
+pub(crate) enum Transfer {
+    /// All of the text at once.
+    Complete(String),
+    /// The owner sends the text in chunks with the INCR protocol, see
+    /// [`Selections::read_chunk`].
+    Incremental,
+}
+
+pub(crate) fn intern(conn: &impl Connection, name: &[u8]) -> Result<Atom> {
+    Ok(conn.intern_atom(false, name)?.reply()?.atom)
+}
+
+impl Selections {
+    pub(crate) fn new(conn: &impl Connection, root: xproto::Window) -> Result<Self> {
+        let window = conn.generate_id()?;
+        conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            window,
+            root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            x11rb::COPY_FROM_PARENT,
+            // Chunks of INCR transfers to us are announced by PropertyNotify.
+            &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        )?;
+        Ok(Self {
+            window,
+            clipboard: intern(conn, b"CLIPBOARD")?,
+            utf8_string: intern(conn, b"UTF8_STRING")?,
+            targets: intern(conn, b"TARGETS")?,
+            incr: intern(conn, b"INCR")?,
+            transfer: intern(conn, b"INPUTSYNTH_SELECTION")?,
+            contents: HashMap::new(),
+            transfers: Vec::new(),
+        })
+    }
+
+    /// Ask the owner of `selection` to send us its contents as UTF-8 text. The answer arrives as
+    /// a `SelectionNotify` event, after which [`Self::read_transfer`] fetches the text.
+    pub(crate) fn request(&self, conn: &impl Connection, selection: Atom) -> Result<()> {
+        conn.convert_selection(
+            self.window,
+            selection,
+            self.utf8_string,
+            self.transfer,
+            x11rb::CURRENT_TIME,
+        )?;
+        conn.flush()?;
+        Ok(())
+    }
+
+    /// Whether `event` is the answer to our last [`Self::request`] for `selection`.
+    pub(crate) fn is_answer(&self, event: &SelectionNotifyEvent, selection: Atom) -> bool {
+        event.requestor == self.window && event.selection == selection
+    }
+
+    /// Read the text delivered with a `SelectionNotify` event. Empty if the owner refused.
+    ///
+    /// For an INCR transfer, reading the property tells the owner to start sending chunks.
+    pub(crate) fn read_transfer(
+        &self,
+        conn: &impl Connection,
+        event: &SelectionNotifyEvent,
+    ) -> Result<Transfer> {
+        if event.property == x11rb::NONE {
+            return Ok(Transfer::Complete(String::new()));
+        }
+        let reply = conn
+            .get_property(
+                true,
+                self.window,
+                event.property,
+                AtomEnum::ANY,
+                0,
+                u32::MAX,
+            )?
+            .reply()?;
+        conn.flush()?;
+        if reply.type_ == self.incr {
+            return Ok(Transfer::Incremental);
+        }
+        Ok(Transfer::Complete(
+            String::from_utf8_lossy(&reply.value).into_owned(),
+        ))
+    }
+
+    /// The window and property that selection contents we ask for are delivered to. A
+    /// `PropertyNotify` with `NEW_VALUE` for them announces the next chunk of an INCR transfer.
+    pub(crate) fn transfer_property(&self) -> (xproto::Window, Atom) {
+        (self.window, self.transfer)
+    }
+
+    /// Read and delete the next chunk of an INCR transfer to us, which tells the owner to send
+    /// the one after it. Empty at the end of the transfer.
+    pub(crate) fn read_chunk(&self, conn: &impl Connection) -> Result<Vec<u8>> {
+        let reply = conn
+            .get_property(true, self.window, self.transfer, AtomEnum::ANY, 0, u32::MAX)?
+            .reply()?;
+        conn.flush()?;
+        Ok(reply.value)
+    }
+
+    /// Take ownership of `selection` and serve `text` from it.
+    pub(crate) fn set(
+        &mut self,
+        conn: &impl Connection,
+        selection: Atom,
+        text: &str,
+    ) -> Result<()> {
+        conn.set_selection_owner(self.window, selection, x11rb::CURRENT_TIME)?;
+        self.contents.insert(selection, text.to_owned());
+        conn.flush()?;
+        Ok(())
+    }
+
+    /// Answer a request for the contents of one of our selections. Returns whether the actual
+    /// text was handed out, as opposed to the list of supported targets or a refusal.
+    pub(crate) fn handle_request(
+        &mut self,
+        conn: &impl Connection,
+        event: &SelectionRequestEvent,
+    ) -> Result<bool> {
+        // Obsolete clients may not set the property, ICCCM says to use the target in that case.
+        let property = if event.property == x11rb::NONE {
+            event.target
+        } else {
+            event.property
+        };
+        let mut served = false;
+        let property = match self.contents.get(&event.selection) {
+            Some(_) if event.target == self.targets => {
+                conn.change_property32(
+                    PropMode::REPLACE,
+                    event.requestor,
+                    property,
+                    AtomEnum::ATOM,
+                    &[self.targets, self.utf8_string, AtomEnum::STRING.into()],
+                )?;
+                property
+            }
+            Some(text)
+                if text.len() > chunk_size(conn)
+                    && (event.target == self.utf8_string
+                        || event.target == Atom::from(AtomEnum::STRING)) =>
+            {
+                // We learn that a chunk was read by the requestor deleting the property.
+                conn.change_window_attributes(
+                    event.requestor,
+                    &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+                )?;
+                conn.change_property32(
+                    PropMode::REPLACE,
+                    event.requestor,
+                    property,
+                    self.incr,
+                    &[text.len().try_into().unwrap_or(u32::MAX)],
+                )?;
+                self.transfers.push(IncrTransfer {
+                    requestor: event.requestor,
+                    property,
+                    target: event.target,
+                    data: text.as_bytes().to_owned(),
+                    sent: 0,
+                });
+                property
+            }
+            Some(text)
+                if event.target == self.utf8_string
+                    || event.target == Atom::from(AtomEnum::STRING) =>
+            {
+                conn.change_property8(
+                    PropMode::REPLACE,
+                    event.requestor,
+                    property,
+                    event.target,
+                    text.as_bytes(),
+                )?;
+                served = true;
+                property
+            }
+            _ => x11rb::NONE,
+        };
+        let notify = SelectionNotifyEvent {
+            response_type: xproto::SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: event.time,
+            requestor: event.requestor,
+            selection: event.selection,
+            target: event.target,
+            property,
+        };
+        conn.send_event(false, event.requestor, EventMask::NO_EVENT, notify)?;
+        conn.flush()?;
+        Ok(served)
+    }
+
+    /// Continue an INCR transfer once the requestor has read the last chunk. Returns whether
+    /// that was the end of the text.
+    pub(crate) fn handle_property_notify(
+        &mut self,
+        conn: &impl Connection,
+        event: &PropertyNotifyEvent,
+    ) -> Result<bool> {
+        if event.state != Property::DELETE {
+            return Ok(false);
+        }
+        let Some(index) = self
+            .transfers
+            .iter()
+            .position(|t| t.requestor == event.window && t.property == event.atom)
+        else {
+            return Ok(false);
+        };
+        let transfer = &mut self.transfers[index];
+        let end = (transfer.sent + chunk_size(conn)).min(transfer.data.len());
+        // The last chunk is empty, once everything was read.
+        conn.change_property8(
+            PropMode::REPLACE,
+            transfer.requestor,
+            transfer.property,
+            transfer.target,
+            &transfer.data[transfer.sent..end],
+        )?;
+        let done = transfer.sent == end;
+        transfer.sent = end;
+        if done {
+            conn.change_window_attributes(
+                transfer.requestor,
+                &ChangeWindowAttributesAux::new().event_mask(EventMask::NO_EVENT),
+            )?;
+            self.transfers.swap_remove(index);
+        }
+        conn.flush()?;
+        Ok(done)
+    }
+
+    /// Someone else took over a selection from us.
+    pub(crate) fn handle_clear(&mut self, event: &SelectionClearEvent) {
+        if event.owner == self.window {
+            self.contents.remove(&event.selection);
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! Typing characters through Compose sequences, like a dead key followed by a letter.
+
+use std::ffi::OsString;
+
+use xkbcommon::xkb;
+use xkbcommon::xkb::compose::{FeedResult, State, Status, Table};
+
+/// The longest sequence looked for, `Multi_key` and two more keys covers most of them.
+const MAX_LENGTH: usize = 3;
+
+/// The user's locale, which picks the Compose table.
+fn locale() -> OsString {
+    // The locale categories in the order they take effect.
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .into_iter()
+        .filter_map(std::env::var_os)
+        .find(|locale| !locale.is_empty())
+        .unwrap_or_else(|| OsString::from("C"))
+}
+
+/// The Compose table of a locale. Compiling it takes a while, so it is kept around until the
+/// locale changes.
+pub(crate) struct LocaleTable {
+    locale: OsString,
+    /// `None` if the locale has no Compose table.
+    table: Option<Table>,
+}
+
+// SAFETY: the table is compiled in a context of its own, which nothing else holds on to, so all
+// reference count changes happen through whoever has the `LocaleTable`, see the comment on
+// `KeymapState`.
+unsafe impl Send for LocaleTable {}
+
+impl LocaleTable {
+    /// The Compose table of the user's locale, reusing `cached` if the locale hasn't changed.
+    pub(crate) fn current(cached: &mut Option<LocaleTable>) -> Option<&Table> {
+        let locale = locale();
+        if cached.as_ref().is_none_or(|cached| cached.locale != locale) {
+            let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+            let table =
+                Table::new_from_locale(&context, &locale, xkb::compose::COMPILE_NO_FLAGS).ok();
+            *cached = Some(LocaleTable { locale, table });
+        }
+        cached.as_ref()?.table.as_ref()
+    }
+}
+
+/// The shortest sequence of `keysyms` that composes `ch` according to `table`. Sequences of the
+/// same length are tried in the order of `keysyms`.
+pub(crate) fn find_sequence(table: &Table, keysyms: &[u32], ch: char) -> Option<Vec<u32>> {
+    let target = ch.to_string();
+    let mut state = State::new(table, xkb::compose::STATE_NO_FLAGS);
+    // Sequences that have started, but not finished composing.
+    let mut prefixes = vec![Vec::new()];
+    for _ in 0..MAX_LENGTH {
+        let mut composing = Vec::new();
+        for prefix in &prefixes {
+            for &keysym in keysyms {
+                // The table has no way of being searched, so replay each candidate.
+                state.reset();
+                for &k in prefix {
+                    state.feed(k);
+                }
+                if state.feed(keysym) == FeedResult::Ignored {
+                    continue;
+                }
+                let mut sequence = prefix.clone();
+                sequence.push(keysym);
+                match state.status() {
+                    Status::Composed if state.utf8().as_deref() == Some(target.as_str()) => {
+                        return Some(sequence)
+                    }
+                    Status::Composing => composing.push(sequence),
+                    _ => (),
+                }
+            }
+        }
+        prefixes = composing;
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use xkbcommon::xkb;
+    use xkbcommon::xkb::compose::Table;
+
+    const DEAD_ACUTE: u32 = 0xfe51;
+    const MULTI_KEY: u32 = 0xff20;
+
+    #[test]
+    fn test_find_sequence() {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let table = Table::new_from_buffer(
+            &context,
+            "<dead_acute> <e> : \"é\" eacute\n\
+             <Multi_key> <n> <asciitilde> : \"ñ\" ntilde\n\
+             <Multi_key> <asciitilde> <n> : \"ñ\" ntilde\n",
+            "C",
+            xkb::compose::FORMAT_TEXT_V1,
+            xkb::compose::COMPILE_NO_FLAGS,
+        )
+        .unwrap();
+        let keysyms = [
+            u32::from(b'e'),
+            u32::from(b'n'),
+            0x7e,
+            DEAD_ACUTE,
+            MULTI_KEY,
+        ];
+        assert_eq!(
+            super::find_sequence(&table, &keysyms, 'é'),
+            Some(vec![DEAD_ACUTE, u32::from(b'e')])
+        );
+        assert_eq!(
+            super::find_sequence(&table, &keysyms, 'ñ'),
+            Some(vec![MULTI_KEY, u32::from(b'n'), 0x7e])
+        );
+        assert_eq!(super::find_sequence(&table, &keysyms[..3], 'é'), None);
+        assert_eq!(super::find_sequence(&table, &keysyms, 'x'), None);
+    }
+}
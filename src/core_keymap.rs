@@ -0,0 +1,195 @@
+//! Building an XKB keymap from the core protocol keyboard mapping. This works on any
+//! connection, unlike the `xkb_x11_*` functions of libxkbcommon, which need libxcb.
+
+use std::fmt::Write as _;
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::ConnectionExt as _;
+use xkbcommon::xkb;
+
+use crate::{keysym_to_name, Result};
+
+/// Core modifiers, in the order of `GetModifierMapping`.
+const MODIFIER_NAMES: [&str; 8] = [
+    "Shift", "Lock", "Control", "Mod1", "Mod2", "Mod3", "Mod4", "Mod5",
+];
+
+/// Fetch the core keyboard mapping of the server and compile it into a keymap. Key types are
+/// guessed from the keysyms by libxkbcommon.
+pub(crate) fn keymap_from_core_mapping(
+    conn: &impl Connection,
+    ctx: &xkb::Context,
+) -> Result<Option<xkb::Keymap>> {
+    let setup = conn.setup();
+    let (min_keycode, max_keycode) = (setup.min_keycode, setup.max_keycode);
+    let mapping = conn
+        .get_keyboard_mapping(min_keycode, max_keycode - min_keycode + 1)?
+        .reply()?;
+    let modifiers = conn.get_modifier_mapping()?.reply()?;
+    let text = keymap_text(
+        min_keycode,
+        mapping.keysyms_per_keycode,
+        &mapping.keysyms,
+        &modifiers.keycodes,
+    );
+    Ok(xkb::Keymap::new_from_string(
+        ctx,
+        text,
+        xkb::KEYMAP_FORMAT_TEXT_V1,
+        xkb::COMPILE_NO_FLAGS,
+    ))
+}
+
+fn keysym_text(keysym: u32) -> String {
+    keysym_to_name(keysym).unwrap_or_else(|| format!("{keysym:#x}"))
+}
+
+/// Split the core keysyms of a key into its groups, each a list of levels.
+///
+/// The core mapping holds the first two levels of group 1 and group 2, followed by the rest of
+/// the levels of group 1, then those of group 2. Groups are assumed to have the same number of
+/// levels, so that every keysym lands in one of the two groups. Groups 3 and 4 can't be told
+/// apart from extra levels this way, and show up as such. Servers copy group 1 into group 2 of
+/// keys with only one group, such copies are dropped.
+fn key_groups(syms: &[u32]) -> Vec<Vec<u32>> {
+    let sym = |i: usize| syms.get(i).copied().unwrap_or(0);
+    let extra = syms.len().saturating_sub(4).div_ceil(2);
+    let group = |first: usize, rest: usize| -> Vec<u32> {
+        let mut levels: Vec<_> = [sym(first), sym(first + 1)]
+            .into_iter()
+            .chain((rest..rest + extra).map(sym))
+            .collect();
+        while levels.len() > 1 && levels.last() == Some(&0) {
+            levels.pop();
+        }
+        levels
+    };
+    let mut groups = vec![group(0, 4), group(2, 4 + extra)];
+    if groups[1] == groups[0] || groups[1].iter().all(|&sym| sym == 0) {
+        groups.pop();
+    }
+    if groups[0].iter().all(|&sym| sym == 0) && groups.len() == 1 {
+        groups.pop();
+    }
+    groups
+}
+
+/// Write the keymap in the XKB text format. `modifier_keycodes` is the modifier mapping, as
+/// returned by `GetModifierMapping`.
+fn keymap_text(
+    min_keycode: u8,
+    keysyms_per_keycode: u8,
+    keysyms: &[u32],
+    modifier_keycodes: &[u8],
+) -> String {
+    let mut keycodes = String::new();
+    let mut symbols = String::new();
+    for (i, syms) in keysyms
+        .chunks(keysyms_per_keycode.max(1).into())
+        .enumerate()
+    {
+        let keycode = usize::from(min_keycode) + i;
+        let _ = writeln!(keycodes, "    <K{keycode}> = {keycode};");
+        let groups: Vec<_> = key_groups(syms)
+            .iter()
+            .map(|levels| {
+                let levels: Vec<_> = levels.iter().map(|&sym| keysym_text(sym)).collect();
+                levels.join(", ")
+            })
+            .collect();
+        match &groups[..] {
+            [] => (),
+            [levels] => {
+                let _ = writeln!(symbols, "    key <K{keycode}> {{ [ {levels} ] }};");
+            }
+            groups => {
+                let groups: Vec<_> = (groups.iter().enumerate())
+                    .map(|(i, levels)| format!("symbols[Group{}] = [ {levels} ]", i + 1))
+                    .collect();
+                let _ = writeln!(symbols, "    key <K{keycode}> {{ {} }};", groups.join(", "));
+            }
+        }
+    }
+    let per_modifier = (modifier_keycodes.len() / MODIFIER_NAMES.len()).max(1);
+    for (name, keycodes) in MODIFIER_NAMES
+        .iter()
+        .zip(modifier_keycodes.chunks(per_modifier))
+    {
+        let keys: Vec<_> = keycodes
+            .iter()
+            .filter(|&&k| k != 0)
+            .map(|k| format!("<K{k}>"))
+            .collect();
+        if !keys.is_empty() {
+            let _ = writeln!(
+                symbols,
+                "    modifier_map {name} {{ {} }};",
+                keys.join(", ")
+            );
+        }
+    }
+    format!(
+        "xkb_keymap {{\n\
+         xkb_keycodes {{\n    minimum = {min_keycode};\n    maximum = 255;\n{keycodes}}};\n\
+         xkb_types {{ include \"complete\" }};\n\
+         xkb_compat {{ include \"complete\" }};\n\
+         xkb_symbols {{\n{symbols}}};\n\
+         }};\n"
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use xkbcommon::xkb;
+
+    #[test]
+    fn test_keymap_text() {
+        // Keycode 10 is a/A, 11 is Shift_L.
+        let keysyms = [0x61, 0x41, 0, 0, 0xffe1, 0, 0, 0];
+        let mut modifiers = [0; 16];
+        modifiers[0] = 11;
+        let text = super::keymap_text(10, 4, &keysyms, &modifiers);
+        assert!(text.contains("key <K10> { [ a, A ] };"));
+        assert!(text.contains("modifier_map Shift { <K11> };"));
+
+        let ctx = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_string(
+            &ctx,
+            text,
+            xkb::KEYMAP_FORMAT_TEXT_V1,
+            xkb::COMPILE_NO_FLAGS,
+        )
+        .unwrap();
+        assert_eq!(keymap.key_get_syms_by_level(10, 0, 1), [0x41]);
+    }
+
+    #[test]
+    fn test_key_groups() {
+        use super::key_groups;
+        // a, A in both groups: one group, copied by the server.
+        assert_eq!(key_groups(&[0x61, 0x41, 0x61, 0x41]), [vec![0x61, 0x41]]);
+        // q, Q, then Cyrillic_shorti, Cyrillic_SHORTI, then at and Greek_OMEGA on level 3 and 4
+        // of group 1.
+        let syms = [0x71, 0x51, 0x6ca, 0x6ea, 0x40, 0x7d9, 0, 0];
+        assert_eq!(
+            key_groups(&syms),
+            [vec![0x71, 0x51, 0x40, 0x7d9], vec![0x6ca, 0x6ea]]
+        );
+        assert!(key_groups(&[0, 0, 0, 0]).is_empty());
+
+        let mut modifiers = [0; 16];
+        modifiers[0] = 11;
+        let text = super::keymap_text(10, 8, &syms, &modifiers);
+        let ctx = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_string(
+            &ctx,
+            text,
+            xkb::KEYMAP_FORMAT_TEXT_V1,
+            xkb::COMPILE_NO_FLAGS,
+        )
+        .unwrap();
+        assert_eq!(keymap.num_layouts_for_key(10), 2);
+        assert_eq!(keymap.key_get_syms_by_level(10, 0, 2), [0x40]);
+        assert_eq!(keymap.key_get_syms_by_level(10, 1, 0), [0x6ca]);
+    }
+}
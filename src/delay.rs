@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+/// How long to wait between consecutive characters when typing a string.
+///
+/// The random variants need the `rand` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypingDelay {
+    /// Always wait exactly this long.
+    Fixed(Duration),
+    /// Wait a duration drawn uniformly from `min..=max`.
+    #[cfg(feature = "rand")]
+    RandomRange { min: Duration, max: Duration },
+    /// Wait a duration drawn from a normal distribution, negative samples are clamped to zero.
+    #[cfg(feature = "rand")]
+    Gaussian { mean: Duration, stddev: Duration },
+}
+
+impl Default for TypingDelay {
+    fn default() -> Self {
+        TypingDelay::Fixed(Duration::ZERO)
+    }
+}
+
+#[cfg(feature = "rand")]
+pub(crate) fn gaussian<R: rand::Rng + ?Sized>(rng: &mut R, mean: f64, stddev: f64) -> f64 {
+    // Box-Muller transform, 1 - u1 keeps the argument of ln() away from 0.
+    let u1: f64 = rng.gen();
+    let u2: f64 = rng.gen();
+    let z = (-2.0 * (1.0 - u1).ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+    mean + z * stddev
+}
+
+impl TypingDelay {
+    /// Pick the duration of the next pause.
+    pub fn sample(&self) -> Duration {
+        match *self {
+            TypingDelay::Fixed(d) => d,
+            #[cfg(feature = "rand")]
+            TypingDelay::RandomRange { min, max } => {
+                use rand::Rng;
+                if max <= min {
+                    min
+                } else {
+                    rand::thread_rng().gen_range(min..=max)
+                }
+            }
+            #[cfg(feature = "rand")]
+            TypingDelay::Gaussian { mean, stddev } => {
+                let secs = gaussian(
+                    &mut rand::thread_rng(),
+                    mean.as_secs_f64(),
+                    stddev.as_secs_f64(),
+                );
+                Duration::from_secs_f64(secs.max(0.0))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TypingDelay;
+    use std::time::Duration;
+
+    #[test]
+    fn test_fixed_delay() {
+        let d = TypingDelay::Fixed(Duration::from_millis(20));
+        assert_eq!(d.sample(), Duration::from_millis(20));
+        assert_eq!(TypingDelay::default().sample(), Duration::ZERO);
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_random_delay_bounds() {
+        let min = Duration::from_millis(10);
+        let max = Duration::from_millis(30);
+        let d = TypingDelay::RandomRange { min, max };
+        for _ in 0..100 {
+            let s = d.sample();
+            assert!(s >= min && s <= max);
+        }
+        let d = TypingDelay::Gaussian {
+            mean: Duration::from_millis(1),
+            stddev: Duration::from_millis(50),
+        };
+        for _ in 0..100 {
+            // Must never panic on negative samples.
+            let _ = d.sample();
+        }
+    }
+}
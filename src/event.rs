@@ -0,0 +1,293 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::keysym::keysym_to_char;
+use crate::{keysym_to_name, Error, InputBackend, Result};
+
+/// A single synthesized input event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InputEvent {
+    KeyPress {
+        keysym: u32,
+    },
+    KeyRelease {
+        keysym: u32,
+    },
+    ButtonPress {
+        x: i16,
+        y: i16,
+        button: u8,
+    },
+    ButtonRelease {
+        x: i16,
+        y: i16,
+        button: u8,
+    },
+    Motion {
+        x: i16,
+        y: i16,
+    },
+    /// A pause between two events.
+    Delay(Duration),
+}
+
+impl InputEvent {
+    /// Send this event through `backend`, or sleep if it's a delay.
+    pub(crate) fn send(self, backend: &(impl InputBackend + ?Sized)) -> Result<()> {
+        match self {
+            InputEvent::KeyPress { keysym } => backend.key(keysym, true),
+            InputEvent::KeyRelease { keysym } => backend.key(keysym, false),
+            InputEvent::ButtonPress { x, y, button } => backend.click(x, y, button, true),
+            InputEvent::ButtonRelease { x, y, button } => backend.click(x, y, button, false),
+            InputEvent::Motion { x, y } => backend.move_cursor(x, y),
+            InputEvent::Delay(delay) => {
+                std::thread::sleep(delay);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Formats as e.g. `KeyPress(sym=0x61 'a')`, `ButtonPress(x=100, y=200, btn=Left)` or
+/// `Delay(50ms)`.
+impl fmt::Display for InputEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            InputEvent::KeyPress { keysym } => write!(f, "KeyPress(sym={})", Keysym(keysym)),
+            InputEvent::KeyRelease { keysym } => write!(f, "KeyRelease(sym={})", Keysym(keysym)),
+            InputEvent::ButtonPress { x, y, button } => {
+                write!(f, "ButtonPress(x={x}, y={y}, btn={})", Button(button))
+            }
+            InputEvent::ButtonRelease { x, y, button } => {
+                write!(f, "ButtonRelease(x={x}, y={y}, btn={})", Button(button))
+            }
+            InputEvent::Motion { x, y } => write!(f, "Motion(x={x}, y={y})"),
+            InputEvent::Delay(delay) => write!(f, "Delay({delay:?})"),
+        }
+    }
+}
+
+/// A keysym in hex, followed by the character it produces, or its name.
+struct Keysym(u32);
+
+impl fmt::Display for Keysym {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}", self.0)?;
+        match keysym_to_char(self.0) {
+            Some(ch) if !ch.is_control() => write!(f, " {ch:?}"),
+            _ => match keysym_to_name(self.0) {
+                Some(name) => write!(f, " {name}"),
+                None => Ok(()),
+            },
+        }
+    }
+}
+
+/// A pointer button, by name if it has a conventional meaning.
+struct Button(u8);
+
+impl fmt::Display for Button {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.0 {
+            1 => "Left",
+            2 => "Middle",
+            3 => "Right",
+            4 => "ScrollUp",
+            5 => "ScrollDown",
+            6 => "ScrollLeft",
+            7 => "ScrollRight",
+            button => return write!(f, "{button}"),
+        };
+        f.write_str(name)
+    }
+}
+
+/// A list of events that can be replayed, see [`EventSequence::replay`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct EventSequence {
+    events: Vec<InputEvent>,
+}
+
+impl From<Vec<InputEvent>> for EventSequence {
+    fn from(events: Vec<InputEvent>) -> Self {
+        Self { events }
+    }
+}
+
+impl EventSequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, event: InputEvent) {
+        self.events.push(event);
+    }
+
+    pub fn events(&self) -> &[InputEvent] {
+        &self.events
+    }
+
+    /// Send the events through `backend`, sleeping for the delays in between.
+    pub fn replay(&self, backend: &(impl InputBackend + ?Sized)) -> Result<()> {
+        self.events.iter().try_for_each(|event| event.send(backend))
+    }
+
+    /// This sequence `n` times in a row.
+    pub fn repeat(&self, n: usize) -> EventSequence {
+        self.repeat_with_delay(n, Duration::ZERO)
+    }
+
+    /// Like [`Self::repeat`], with a pause of `delay` between repetitions.
+    pub fn repeat_with_delay(&self, n: usize, delay: Duration) -> EventSequence {
+        let mut events = Vec::with_capacity(n * (self.events.len() + 1));
+        for i in 0..n {
+            if i > 0 && !delay.is_zero() {
+                events.push(InputEvent::Delay(delay));
+            }
+            events.extend_from_slice(&self.events);
+        }
+        EventSequence { events }
+    }
+
+    /// This sequence backwards, with presses turned into releases and the other way around, so
+    /// a sequence that presses keys becomes one that releases them in the opposite order.
+    pub fn reverse(&self) -> EventSequence {
+        let events = self
+            .events
+            .iter()
+            .rev()
+            .map(|&event| match event {
+                InputEvent::KeyPress { keysym } => InputEvent::KeyRelease { keysym },
+                InputEvent::KeyRelease { keysym } => InputEvent::KeyPress { keysym },
+                InputEvent::ButtonPress { x, y, button } => {
+                    InputEvent::ButtonRelease { x, y, button }
+                }
+                InputEvent::ButtonRelease { x, y, button } => {
+                    InputEvent::ButtonPress { x, y, button }
+                }
+                event => event,
+            })
+            .collect();
+        EventSequence { events }
+    }
+
+    /// A copy of this sequence with all delays multiplied by `factor`, e.g. 0.5 to replay at
+    /// twice the speed. Scaled delays are at least 1ms. `factor` has to be positive, otherwise
+    /// this fails with [`Error::InvalidFactor`].
+    pub fn scale_timing(&self, factor: f64) -> Result<EventSequence> {
+        /// Delays are never scaled below this.
+        const MIN_DELAY: Duration = Duration::from_millis(1);
+        if factor.is_nan() || factor <= 0.0 {
+            return Err(Error::InvalidFactor(factor));
+        }
+        let events = self
+            .events
+            .iter()
+            .map(|&event| match event {
+                InputEvent::Delay(delay) => {
+                    let scaled = Duration::try_from_secs_f64(delay.as_secs_f64() * factor)
+                        .unwrap_or(Duration::MAX);
+                    InputEvent::Delay(scaled.max(MIN_DELAY))
+                }
+                event => event,
+            })
+            .collect();
+        Ok(EventSequence { events })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{EventSequence, InputEvent};
+    use crate::Error;
+
+    #[test]
+    fn test_scale_timing() {
+        let sequence = EventSequence::from(vec![
+            InputEvent::KeyPress { keysym: 0x61 },
+            InputEvent::Delay(Duration::from_millis(100)),
+            InputEvent::Delay(Duration::from_micros(100)),
+        ]);
+        let scaled = sequence.scale_timing(0.5).unwrap();
+        assert_eq!(
+            scaled.events(),
+            [
+                InputEvent::KeyPress { keysym: 0x61 },
+                InputEvent::Delay(Duration::from_millis(50)),
+                InputEvent::Delay(Duration::from_millis(1)),
+            ]
+        );
+        assert!(matches!(
+            sequence.scale_timing(0.0),
+            Err(Error::InvalidFactor(_))
+        ));
+        assert!(sequence.scale_timing(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_repeat_and_reverse() {
+        let sequence = EventSequence::from(vec![
+            InputEvent::KeyPress { keysym: 0x61 },
+            InputEvent::Motion { x: 1, y: 2 },
+        ]);
+        assert_eq!(sequence.repeat(2).events().len(), 4);
+        assert!(sequence.repeat(0).events().is_empty());
+        let delay = Duration::from_millis(10);
+        assert_eq!(
+            sequence.repeat_with_delay(2, delay).events(),
+            [
+                InputEvent::KeyPress { keysym: 0x61 },
+                InputEvent::Motion { x: 1, y: 2 },
+                InputEvent::Delay(delay),
+                InputEvent::KeyPress { keysym: 0x61 },
+                InputEvent::Motion { x: 1, y: 2 },
+            ]
+        );
+        assert_eq!(
+            sequence.reverse().events(),
+            [
+                InputEvent::Motion { x: 1, y: 2 },
+                InputEvent::KeyRelease { keysym: 0x61 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            InputEvent::KeyPress { keysym: 0x61 }.to_string(),
+            "KeyPress(sym=0x61 'a')"
+        );
+        assert_eq!(
+            InputEvent::KeyRelease { keysym: 0xff0d }.to_string(),
+            "KeyRelease(sym=0xff0d Return)"
+        );
+        assert_eq!(
+            InputEvent::ButtonPress {
+                x: 100,
+                y: 200,
+                button: 1
+            }
+            .to_string(),
+            "ButtonPress(x=100, y=200, btn=Left)"
+        );
+        assert_eq!(
+            InputEvent::ButtonRelease {
+                x: 0,
+                y: 0,
+                button: 9
+            }
+            .to_string(),
+            "ButtonRelease(x=0, y=0, btn=9)"
+        );
+        assert_eq!(
+            InputEvent::Delay(Duration::from_millis(50)).to_string(),
+            "Delay(50ms)"
+        );
+    }
+}
@@ -0,0 +1,90 @@
+//! Finger paths of multi-touch gestures. Each gesture is a list of frames, and each frame has
+//! the position of every finger, the first frame being where the fingers go down.
+
+use std::f64::consts::TAU;
+
+/// How far the fingers of a rotation are from its center.
+const ROTATE_RADIUS: f64 = 100.0;
+
+/// `fingers` fingers spread evenly on a circle around `center`, for `frames + 1` frames. `at(t)`
+/// gives the radius of the circle and how far it has turned, in radians, at `t` from 0 to 1.
+fn circle(
+    center: (f64, f64),
+    fingers: u8,
+    frames: usize,
+    at: impl Fn(f64) -> (f64, f64),
+) -> Vec<Vec<(f64, f64)>> {
+    (0..=frames)
+        .map(|frame| {
+            let t = frame as f64 / frames.max(1) as f64;
+            let (radius, turn) = at(t);
+            (0..fingers)
+                .map(|finger| {
+                    let angle = turn + TAU * f64::from(finger) / f64::from(fingers);
+                    (
+                        center.0 + radius * angle.cos(),
+                        center.1 + radius * angle.sin(),
+                    )
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Fingers moving from `start_dist` to `end_dist` apart. The distance is between opposite
+/// fingers, i.e. the diameter of the circle they are on.
+pub(crate) fn pinch(
+    center: (f64, f64),
+    start_dist: u16,
+    end_dist: u16,
+    fingers: u8,
+    frames: usize,
+) -> Vec<Vec<(f64, f64)>> {
+    let start = f64::from(start_dist) / 2.0;
+    let end = f64::from(end_dist) / 2.0;
+    circle(center, fingers, frames, |t| {
+        (start + (end - start) * t, 0.0)
+    })
+}
+
+/// Two fingers turning `degrees` around `center`, clockwise on screen.
+pub(crate) fn rotate(center: (f64, f64), degrees: f64, frames: usize) -> Vec<Vec<(f64, f64)>> {
+    let turn = degrees.to_radians();
+    circle(center, 2, frames, |t| (ROTATE_RADIUS, turn * t))
+}
+
+#[cfg(test)]
+mod test {
+    fn assert_near(actual: &[(f64, f64)], expected: &[(f64, f64)]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(
+                (a.0 - e.0).abs() < 1e-9 && (a.1 - e.1).abs() < 1e-9,
+                "{actual:?} != {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_pinch() {
+        let frames = super::pinch((100.0, 100.0), 100, 200, 2, 2);
+        assert_eq!(frames.len(), 3);
+        assert_near(&frames[0], &[(150.0, 100.0), (50.0, 100.0)]);
+        assert_near(&frames[1], &[(175.0, 100.0), (25.0, 100.0)]);
+        assert_near(&frames[2], &[(200.0, 100.0), (0.0, 100.0)]);
+
+        let frames = super::pinch((0.0, 0.0), 200, 200, 4, 1);
+        assert_near(
+            &frames[1],
+            &[(100.0, 0.0), (0.0, 100.0), (-100.0, 0.0), (0.0, -100.0)],
+        );
+    }
+
+    #[test]
+    fn test_rotate() {
+        let frames = super::rotate((0.0, 0.0), 90.0, 3);
+        assert_eq!(frames.len(), 4);
+        assert_near(&frames[0], &[(100.0, 0.0), (-100.0, 0.0)]);
+        assert_near(&frames[3], &[(0.0, 100.0), (0.0, -100.0)]);
+    }
+}
@@ -0,0 +1,57 @@
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{self, ConnectionExt as _, EventMask, GrabMode, GrabStatus};
+
+use crate::Result;
+
+/// Which devices another client has grabbed, see [`crate::InputSynth::check_grabs`].
+///
+/// While a device is grabbed, e.g. by an open menu or a screen locker, all its input goes to
+/// the grabbing client, including ours.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GrabState {
+    pub keyboard: bool,
+    pub pointer: bool,
+}
+
+/// Whether a grab attempt failed because someone else holds a grab.
+fn is_grabbed(status: GrabStatus) -> bool {
+    status == GrabStatus::ALREADY_GRABBED || status == GrabStatus::FROZEN
+}
+
+/// Find out about grabs by trying to grab both devices ourselves, releasing them right away.
+pub(crate) fn probe(conn: &impl Connection, root: xproto::Window) -> Result<GrabState> {
+    let keyboard = conn
+        .grab_keyboard(
+            true,
+            root,
+            x11rb::CURRENT_TIME,
+            GrabMode::ASYNC,
+            GrabMode::ASYNC,
+        )?
+        .reply()?
+        .status;
+    if keyboard == GrabStatus::SUCCESS {
+        conn.ungrab_keyboard(x11rb::CURRENT_TIME)?;
+    }
+    let pointer = conn
+        .grab_pointer(
+            true,
+            root,
+            EventMask::NO_EVENT,
+            GrabMode::ASYNC,
+            GrabMode::ASYNC,
+            x11rb::NONE,
+            x11rb::NONE,
+            x11rb::CURRENT_TIME,
+        )?
+        .reply()?
+        .status;
+    if pointer == GrabStatus::SUCCESS {
+        conn.ungrab_pointer(x11rb::CURRENT_TIME)?;
+    }
+    conn.flush()?;
+    Ok(GrabState {
+        keyboard: is_grabbed(keyboard),
+        pointer: is_grabbed(pointer),
+    })
+}
@@ -0,0 +1,100 @@
+//! Keys held down by guards, like [`crate::KeyGuard`] and [`crate::ModifierGuard`].
+//!
+//! Several guards can hold the same key, e.g. Shift held on its own and as the modifier of a
+//! held `A`. The key is pressed by the first of them and released once the last is gone.
+
+/// Identifies the keys of one guard.
+pub(crate) type HolderId = u64;
+
+#[derive(Debug, Default)]
+pub(crate) struct HeldKeys {
+    next_id: HolderId,
+    /// Keycodes and who holds them, in the order they were pressed.
+    keys: Vec<(HolderId, u32)>,
+}
+
+impl HeldKeys {
+    /// A new holder, which holds nothing yet.
+    pub(crate) fn holder(&mut self) -> HolderId {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    /// Whether any guard holds `keycode`.
+    pub(crate) fn contains(&self, keycode: u32) -> bool {
+        self.keys.iter().any(|&(_, k)| k == keycode)
+    }
+
+    /// Let `holder` hold `keycode`. Returns whether it has to be pressed, i.e. nobody held it
+    /// before.
+    pub(crate) fn add(&mut self, holder: HolderId, keycode: u32) -> bool {
+        let press = !self.contains(keycode);
+        self.keys.push((holder, keycode));
+        press
+    }
+
+    /// Forget the keys of `holder`. Returns those nobody holds anymore, in the order they were
+    /// pressed. Empty if `holder` holds nothing, e.g. because `take_all` released its keys.
+    pub(crate) fn remove(&mut self, holder: HolderId) -> Vec<u32> {
+        let mut removed = Vec::new();
+        self.keys.retain(|&(h, k)| {
+            if h == holder {
+                removed.push(k);
+            }
+            h != holder
+        });
+        removed.retain(|&k| !self.contains(k));
+        removed
+    }
+
+    /// Forget all keys. Returns each of them once, in the order they were pressed.
+    pub(crate) fn take_all(&mut self) -> Vec<u32> {
+        let mut keys: Vec<u32> = Vec::new();
+        for (_, k) in self.keys.drain(..) {
+            if !keys.contains(&k) {
+                keys.push(k);
+            }
+        }
+        keys
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::HeldKeys;
+
+    const SHIFT: u32 = 50;
+    const A: u32 = 38;
+    const CONTROL: u32 = 37;
+
+    #[test]
+    fn test_shared_keys() {
+        let mut held = HeldKeys::default();
+        let shift = held.holder();
+        assert!(held.add(shift, SHIFT));
+        let a = held.holder();
+        assert!(!held.add(a, SHIFT));
+        assert!(held.add(a, A));
+        // Shift stays held for `a`.
+        assert!(held.remove(shift).is_empty());
+        assert!(held.contains(SHIFT));
+        assert_eq!(held.remove(a), [SHIFT, A]);
+        assert!(!held.contains(SHIFT));
+        assert!(held.remove(a).is_empty());
+    }
+
+    #[test]
+    fn test_take_all() {
+        let mut held = HeldKeys::default();
+        let first = held.holder();
+        held.add(first, CONTROL);
+        held.add(first, SHIFT);
+        let second = held.holder();
+        held.add(second, SHIFT);
+        held.add(second, A);
+        assert_eq!(held.take_all(), [CONTROL, SHIFT, A]);
+        // The guards find nothing left to release.
+        assert!(held.remove(first).is_empty());
+        assert!(held.remove(second).is_empty());
+    }
+}
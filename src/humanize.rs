@@ -0,0 +1,147 @@
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::delay::gaussian;
+
+/// Randomization applied to synthesized input so it looks less mechanical. Nothing is randomized
+/// unless this is installed with [`InputSynth::set_humanize`](crate::InputSynth::set_humanize).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Humanize {
+    /// Standard deviation, in pixels, of the sideways wobble added to pointer paths.
+    pub path_jitter: f64,
+    /// How much the pointer speed varies along a path, relative to the average speed.
+    pub speed_variation: f64,
+    /// Shortest time a button is held down by [`InputSynth::click_at`](crate::InputSynth::click_at).
+    pub min_click_duration: Duration,
+    /// Longest time a button is held down by [`InputSynth::click_at`](crate::InputSynth::click_at).
+    pub max_click_duration: Duration,
+    /// Standard deviation of the noise added to delays between events.
+    pub delay_noise: Duration,
+    /// Seed for the random number generator, to make runs reproducible. A random seed is used if
+    /// this is `None`.
+    pub seed: Option<u64>,
+}
+
+impl Default for Humanize {
+    fn default() -> Self {
+        Self {
+            path_jitter: 2.0,
+            speed_variation: 0.3,
+            min_click_duration: Duration::from_millis(40),
+            max_click_duration: Duration::from_millis(120),
+            delay_noise: Duration::from_millis(15),
+            seed: None,
+        }
+    }
+}
+
+pub(crate) struct Humanizer {
+    config: Humanize,
+    rng: StdRng,
+}
+
+impl Humanizer {
+    pub(crate) fn new(config: Humanize) -> Self {
+        let rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        Self { config, rng }
+    }
+
+    pub(crate) fn config(&self) -> &Humanize {
+        &self.config
+    }
+
+    pub(crate) fn click_duration(&mut self) -> Duration {
+        let Humanize {
+            min_click_duration: min,
+            max_click_duration: max,
+            ..
+        } = self.config;
+        if max <= min {
+            min
+        } else {
+            self.rng.gen_range(min..=max)
+        }
+    }
+
+    pub(crate) fn noisy_delay(&mut self, delay: Duration) -> Duration {
+        let secs = gaussian(
+            &mut self.rng,
+            delay.as_secs_f64(),
+            self.config.delay_noise.as_secs_f64(),
+        );
+        Duration::from_secs_f64(secs.max(0.0))
+    }
+
+    /// Push the points of a straight path from `from` to `to` sideways. The offset fades out
+    /// towards both ends so the path still starts and ends where it should.
+    pub(crate) fn perturb_path(
+        &mut self,
+        from: (f64, f64),
+        to: (f64, f64),
+        path: &mut [(f64, f64)],
+    ) {
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let len = (dx * dx + dy * dy).sqrt();
+        if len == 0.0 || self.config.path_jitter <= 0.0 {
+            return;
+        }
+        let normal = (-dy / len, dx / len);
+        let n = path.len();
+        let mut offset = 0.0;
+        for (i, point) in path.iter_mut().enumerate() {
+            // Low-pass filtered noise, so the path wobbles instead of zig-zagging. The filter
+            // shrinks the standard deviation to a third, so scale it back up.
+            offset = 0.8 * offset + 0.2 * gaussian(&mut self.rng, 0.0, self.config.path_jitter);
+            let envelope = (std::f64::consts::PI * (i + 1) as f64 / n as f64).sin();
+            point.0 += normal.0 * offset * envelope * 3.0;
+            point.1 += normal.1 * offset * envelope * 3.0;
+        }
+    }
+
+    /// Split `total` into `steps` delays of varying length, which makes the pointer speed change
+    /// along the path.
+    pub(crate) fn step_delays(&mut self, total: Duration, steps: usize) -> Vec<Duration> {
+        let weights: Vec<f64> = (0..steps)
+            .map(|_| gaussian(&mut self.rng, 1.0, self.config.speed_variation).max(0.05))
+            .collect();
+        let sum: f64 = weights.iter().sum();
+        weights
+            .into_iter()
+            .map(|w| total.mul_f64(w / sum))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Humanize, Humanizer};
+    use std::time::Duration;
+
+    #[test]
+    fn test_humanizer_is_reproducible() {
+        let config = Humanize {
+            seed: Some(42),
+            ..Default::default()
+        };
+        let mut a = Humanizer::new(config.clone());
+        let mut b = Humanizer::new(config);
+        let mut path_a = vec![(10.0, 10.0); 20];
+        let mut path_b = path_a.clone();
+        a.perturb_path((0.0, 0.0), (100.0, 100.0), &mut path_a);
+        b.perturb_path((0.0, 0.0), (100.0, 100.0), &mut path_b);
+        assert_eq!(path_a, path_b);
+        assert_eq!(a.click_duration(), b.click_duration());
+
+        let delays = a.step_delays(Duration::from_millis(500), 10);
+        assert_eq!(delays.len(), 10);
+        let total: Duration = delays.iter().sum();
+        assert!((total.as_secs_f64() - 0.5).abs() < 1e-3);
+    }
+}
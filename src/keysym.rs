@@ -0,0 +1,369 @@
+use std::fmt;
+
+use xkbcommon::xkb;
+
+use crate::Error;
+
+/// A keysym, the symbol a key produces, like `a` or `Return`.
+///
+/// Made from a character with `From<char>`, or from a name like `"Return"` with `TryFrom<&str>`,
+/// see [`keysym_from_name`]. Displayed as its name.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key(u32);
+
+impl Key {
+    /// The Return key of the main keyboard.
+    pub const RETURN: Self = Self(0xff0d);
+    /// The Enter key of the keypad, which some applications tell apart from Return. Unlike the
+    /// keypad digits, it doesn't depend on NumLock.
+    pub const KP_ENTER: Self = Self(0xff8d);
+
+    pub const fn new(keysym: u32) -> Self {
+        Self(keysym)
+    }
+
+    pub const fn keysym(self) -> u32 {
+        self.0
+    }
+}
+
+impl From<u32> for Key {
+    fn from(keysym: u32) -> Self {
+        Self(keysym)
+    }
+}
+
+impl From<Key> for u32 {
+    fn from(key: Key) -> Self {
+        key.0
+    }
+}
+
+/// The keysym typing `ch`, e.g. `a` for `'a'`, or its Unicode keysym if there is no named one.
+impl From<char> for Key {
+    fn from(ch: char) -> Self {
+        Self(char_to_keysym(ch))
+    }
+}
+
+impl TryFrom<&str> for Key {
+    type Error = Error;
+
+    fn try_from(name: &str) -> Result<Self, Error> {
+        keysym_from_name(name)
+            .map(Self)
+            .ok_or_else(|| Error::UnknownKeyName(name.to_owned()))
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match keysym_to_name(self.0) {
+            Some(name) => f.write_str(&name),
+            None => write!(f, "{:#x}", self.0),
+        }
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Key({:#x} {self})", self.0)
+    }
+}
+
+/// A key of the numeric keypad, see [`crate::InputSynth::press_numpad`]. These have keysyms of
+/// their own, different from the digits and operators of the main keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NumpadKey {
+    Digit0,
+    Digit1,
+    Digit2,
+    Digit3,
+    Digit4,
+    Digit5,
+    Digit6,
+    Digit7,
+    Digit8,
+    Digit9,
+    Decimal,
+    Enter,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl NumpadKey {
+    const DIGITS: [Self; 10] = [
+        Self::Digit0,
+        Self::Digit1,
+        Self::Digit2,
+        Self::Digit3,
+        Self::Digit4,
+        Self::Digit5,
+        Self::Digit6,
+        Self::Digit7,
+        Self::Digit8,
+        Self::Digit9,
+    ];
+
+    /// The key of `digit`, `None` if it's more than 9.
+    pub fn digit(digit: u8) -> Option<Self> {
+        Self::DIGITS.get(usize::from(digit)).copied()
+    }
+
+    /// Whether the key only produces its keysym while NumLock is on, like the digits. Without
+    /// NumLock, they move the cursor instead.
+    pub(crate) fn needs_num_lock(self) -> bool {
+        matches!(self, Self::Decimal) || Self::DIGITS.contains(&self)
+    }
+}
+
+impl From<NumpadKey> for Key {
+    fn from(key: NumpadKey) -> Self {
+        Self(match key {
+            NumpadKey::Decimal => 0xffae,
+            NumpadKey::Enter => Key::KP_ENTER.0,
+            NumpadKey::Add => 0xffab,
+            NumpadKey::Subtract => 0xffad,
+            NumpadKey::Multiply => 0xffaa,
+            NumpadKey::Divide => 0xffaf,
+            NumpadKey::Digit0 => 0xffb0,
+            NumpadKey::Digit1 => 0xffb1,
+            NumpadKey::Digit2 => 0xffb2,
+            NumpadKey::Digit3 => 0xffb3,
+            NumpadKey::Digit4 => 0xffb4,
+            NumpadKey::Digit5 => 0xffb5,
+            NumpadKey::Digit6 => 0xffb6,
+            NumpadKey::Digit7 => 0xffb7,
+            NumpadKey::Digit8 => 0xffb8,
+            NumpadKey::Digit9 => 0xffb9,
+        })
+    }
+}
+
+/// A media or brightness key of multimedia keyboards, see
+/// [`crate::InputSynth::press_media_key`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MediaKey {
+    AudioPlay,
+    AudioPause,
+    AudioStop,
+    AudioNext,
+    AudioPrev,
+    AudioMute,
+    AudioRaiseVolume,
+    AudioLowerVolume,
+    MonBrightnessUp,
+    MonBrightnessDown,
+}
+
+impl From<MediaKey> for Key {
+    fn from(key: MediaKey) -> Self {
+        // The XF86 keysyms, from XF86keysym.h.
+        Self(match key {
+            MediaKey::AudioPlay => 0x1008ff14,
+            MediaKey::AudioPause => 0x1008ff31,
+            MediaKey::AudioStop => 0x1008ff15,
+            MediaKey::AudioNext => 0x1008ff17,
+            MediaKey::AudioPrev => 0x1008ff16,
+            MediaKey::AudioMute => 0x1008ff12,
+            MediaKey::AudioRaiseVolume => 0x1008ff13,
+            MediaKey::AudioLowerVolume => 0x1008ff11,
+            MediaKey::MonBrightnessUp => 0x1008ff02,
+            MediaKey::MonBrightnessDown => 0x1008ff03,
+        })
+    }
+}
+
+/// A web browser navigation key of multimedia keyboards, see
+/// [`crate::InputSynth::browser_key`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BrowserKey {
+    Back,
+    Forward,
+    Refresh,
+    Stop,
+    Search,
+    Favorites,
+    HomePage,
+}
+
+impl From<BrowserKey> for Key {
+    fn from(key: BrowserKey) -> Self {
+        // The XF86 keysyms, from XF86keysym.h.
+        Self(match key {
+            BrowserKey::Back => 0x1008ff26,
+            BrowserKey::Forward => 0x1008ff27,
+            BrowserKey::Refresh => 0x1008ff29,
+            BrowserKey::Stop => 0x1008ff28,
+            BrowserKey::Search => 0x1008ff1b,
+            BrowserKey::Favorites => 0x1008ff30,
+            BrowserKey::HomePage => 0x1008ff18,
+        })
+    }
+}
+
+/// The keysym named `name`, like `"Return"`, `"KP_Add"` or `"XF86AudioPlay"`, or `None` if
+/// there is no such keysym. Case is ignored, unless that makes the name ambiguous: `"a"` and
+/// `"A"` are different keysyms, but `"return"` is `"Return"`.
+pub fn keysym_from_name(name: &str) -> Option<u32> {
+    const NO_SYMBOL: u32 = 0;
+    match xkb::keysym_from_name(name, xkb::KEYSYM_NO_FLAGS) {
+        NO_SYMBOL => match xkb::keysym_from_name(name, xkb::KEYSYM_CASE_INSENSITIVE) {
+            NO_SYMBOL => None,
+            keysym => Some(keysym),
+        },
+        keysym => Some(keysym),
+    }
+}
+
+/// The canonical name of `keysym`, like `"Return"`, the inverse of [`keysym_from_name`].
+/// Keysyms without a name are given in hex, like `"0x00001234"`. `None` if `keysym` isn't a
+/// valid keysym.
+pub fn keysym_to_name(keysym: u32) -> Option<String> {
+    // Keysyms are 29 bits. xkbcommon's wrapper doesn't handle the error libxkbcommon returns
+    // for anything larger, so those are rejected here.
+    const MAX_KEYSYM: u32 = 0x1fff_ffff;
+    if keysym > MAX_KEYSYM {
+        return None;
+    }
+    let name = xkb::keysym_get_name(keysym);
+    (!name.is_empty()).then_some(name)
+}
+
+/// The keysym typing `ch`. Latin-1 characters are their own keysyms, others mostly their code
+/// point plus `0x1000000`, but some have older keysyms, like `0x20ac` for `'€'`. Control
+/// characters with a key, like `'\t'`, give the keysym of that key.
+pub fn char_to_keysym(ch: char) -> u32 {
+    xkb::utf32_to_keysym(ch.into())
+}
+
+/// The character `keysym` stands for, the inverse of [`char_to_keysym`] for printable
+/// characters. `None` for keysyms of keys that aren't characters, like `Return`, `F1` or those
+/// of the keypad, even if they insert text, so control characters like `'\t'` don't round-trip.
+pub fn keysym_to_char(keysym: u32) -> Option<char> {
+    /// Function keys, the keypad and modifiers.
+    const FUNCTION_KEYSYMS: std::ops::RangeInclusive<u32> = 0xff00..=0xffff;
+    if FUNCTION_KEYSYMS.contains(&keysym) {
+        return None;
+    }
+    char::from_u32(xkb::keysym_to_utf32(keysym)).filter(|&ch| ch != '\0')
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        char_to_keysym, keysym_from_name, keysym_to_char, keysym_to_name, BrowserKey, Key,
+        MediaKey, NumpadKey,
+    };
+
+    #[test]
+    fn test_keysym_from_name() {
+        assert_eq!(keysym_from_name("Return"), Some(0xff0d));
+        assert_eq!(keysym_from_name("return"), Some(0xff0d));
+        assert_eq!(keysym_from_name("KP_Add"), Some(0xffab));
+        assert_eq!(keysym_from_name("XF86AudioPlay"), Some(0x1008ff14));
+        assert_eq!(keysym_from_name("a"), Some(0x61));
+        assert_eq!(keysym_from_name("A"), Some(0x41));
+        assert_eq!(keysym_from_name("NotAKeysym"), None);
+        assert_eq!(keysym_from_name(""), None);
+    }
+    #[test]
+    fn test_char_keysym() {
+        for (ch, keysym) in [
+            ('a', 0x61),
+            ('~', 0x7e),
+            ('ñ', 0xf1),
+            ('€', 0x20ac),
+            ('🦀', 0x101_f980),
+        ] {
+            assert_eq!(char_to_keysym(ch), keysym, "{ch}");
+            assert_eq!(keysym_to_char(keysym), Some(ch), "{keysym:#x}");
+        }
+        assert_eq!(keysym_to_char(char_to_keysym('→')), Some('→'));
+        assert_eq!(keysym_to_char(0x100_2192), Some('→'));
+        // Return, Tab, KP_0, KP_Add, F1 and Shift_L.
+        for keysym in [0xff0d, 0xff09, 0xffb0, 0xffab, 0xffbe, 0xffe1] {
+            assert_eq!(keysym_to_char(keysym), None, "{keysym:#x}");
+        }
+        assert_eq!(keysym_to_char(0), None);
+    }
+
+    #[test]
+    fn test_keysym_to_name() {
+        assert_eq!(keysym_to_name(0xff0d).as_deref(), Some("Return"));
+        assert_eq!(keysym_to_name(0x1008ff14).as_deref(), Some("XF86AudioPlay"));
+        assert_eq!(keysym_to_name(0x41).as_deref(), Some("A"));
+        assert_eq!(keysym_to_name(0xffff_ffff), None);
+        for keysym in [0xff0d, 0xffab, 0x61] {
+            assert_eq!(
+                keysym_to_name(keysym).and_then(|name| keysym_from_name(&name)),
+                Some(keysym)
+            );
+        }
+    }
+    #[test]
+    fn test_key() {
+        assert_eq!(Key::from('a').keysym(), 0x61);
+        assert_eq!(Key::from('€').keysym(), 0x20ac);
+        assert_eq!(Key::try_from("Return").unwrap(), Key::new(0xff0d));
+        assert!(Key::try_from("NotAKeysym").is_err());
+        assert_eq!(Key::RETURN.to_string(), "Return");
+        assert_eq!(Key::KP_ENTER.to_string(), "KP_Enter");
+        assert_eq!(Key::new(0xffff_ffff).to_string(), "0xffffffff");
+        assert_eq!(format!("{:?}", Key::from('a')), "Key(0x61 a)");
+    }
+
+    #[test]
+    fn test_numpad_key() {
+        assert_eq!(Key::from(NumpadKey::Digit0).to_string(), "KP_0");
+        assert_eq!(Key::from(NumpadKey::digit(7).unwrap()).to_string(), "KP_7");
+        assert_eq!(NumpadKey::digit(10), None);
+        for (key, name) in [
+            (NumpadKey::Decimal, "KP_Decimal"),
+            (NumpadKey::Enter, "KP_Enter"),
+            (NumpadKey::Add, "KP_Add"),
+            (NumpadKey::Subtract, "KP_Subtract"),
+            (NumpadKey::Multiply, "KP_Multiply"),
+            (NumpadKey::Divide, "KP_Divide"),
+        ] {
+            assert_eq!(Key::from(key).to_string(), name);
+        }
+        assert!(NumpadKey::Digit3.needs_num_lock());
+        assert!(!NumpadKey::Enter.needs_num_lock());
+    }
+
+    #[test]
+    fn test_media_key() {
+        for (key, name) in [
+            (MediaKey::AudioPlay, "XF86AudioPlay"),
+            (MediaKey::AudioPause, "XF86AudioPause"),
+            (MediaKey::AudioStop, "XF86AudioStop"),
+            (MediaKey::AudioNext, "XF86AudioNext"),
+            (MediaKey::AudioPrev, "XF86AudioPrev"),
+            (MediaKey::AudioMute, "XF86AudioMute"),
+            (MediaKey::AudioRaiseVolume, "XF86AudioRaiseVolume"),
+            (MediaKey::AudioLowerVolume, "XF86AudioLowerVolume"),
+            (MediaKey::MonBrightnessUp, "XF86MonBrightnessUp"),
+            (MediaKey::MonBrightnessDown, "XF86MonBrightnessDown"),
+        ] {
+            assert_eq!(Key::from(key).to_string(), name);
+        }
+    }
+
+    #[test]
+    fn test_browser_key() {
+        for (key, name) in [
+            (BrowserKey::Back, "XF86Back"),
+            (BrowserKey::Forward, "XF86Forward"),
+            (BrowserKey::Refresh, "XF86Refresh"),
+            (BrowserKey::Stop, "XF86Stop"),
+            (BrowserKey::Search, "XF86Search"),
+            (BrowserKey::Favorites, "XF86Favorites"),
+            (BrowserKey::HomePage, "XF86HomePage"),
+        ] {
+            assert_eq!(Key::from(key).to_string(), name);
+        }
+    }
+}
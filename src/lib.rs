@@ -1,300 +1,4573 @@
-use std::cell::RefCell;
+#![cfg_attr(not(test), deny(clippy::unwrap_used))]
+
 use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
 use thiserror::Error;
-use x11rb::connection::Connection;
-use x11rb::protocol::xproto;
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::cookie::VoidCookie;
+use x11rb::errors::{ConnectError, ConnectionError, ReplyError, ReplyOrIdError};
+use x11rb::protocol::xproto::{self, ConnectionExt as _};
 use x11rb::protocol::xtest::ConnectionExt as _;
-use x11rb::xcb_ffi::{ConnectError, ConnectionError, ReplyError, XCBConnection};
 use xkbcommon::xkb::KeyDirection;
 
+mod backend;
+mod builder;
+mod call_lock;
+mod checking;
+mod clipboard;
+mod compose;
+mod core_keymap;
+mod delay;
+mod event;
+#[cfg(feature = "uinput")]
+mod gesture;
+mod grab;
+mod held;
+#[cfg(feature = "rand")]
+mod humanize;
+mod keysym;
+#[cfg(feature = "mock")]
+mod mock;
+mod modifiers;
+mod monitor;
+mod newline;
+mod rate_limit;
+#[cfg(feature = "record")]
+mod record;
+mod script;
+mod scroll;
+mod sender;
+mod stats;
+mod stylus;
+#[cfg(feature = "testing")]
+mod testing;
+mod touch;
+#[cfg(feature = "uinput")]
+mod uinput;
+#[cfg(feature = "wayland")]
+mod wayland;
+mod window;
+mod xi1;
+mod xkb_extra;
+pub use backend::{BackendKind, InputBackend, X11Backend};
+pub use builder::{InputSynthBuilder, ReconnectPolicy};
+use call_lock::CallLock;
+use checking::PendingInputs;
+pub use checking::{FakeInput, RequestChecking};
+use clipboard::{Selections, Transfer};
+pub use delay::TypingDelay;
+pub use event::{EventSequence, InputEvent};
+pub use grab::GrabState;
+use held::{HeldKeys, HolderId};
+#[cfg(feature = "rand")]
+pub use humanize::Humanize;
+#[cfg(feature = "rand")]
+use humanize::Humanizer;
+pub use keysym::{keysym_from_name, keysym_to_name, BrowserKey, Key, MediaKey, NumpadKey};
+#[cfg(feature = "mock")]
+pub use mock::{MockInputSynth, SyntheticEvent};
+pub use modifiers::{ModifierState, Modifiers, ShortcutModifiers};
+pub use monitor::Monitor;
+pub use newline::NewlinePolicy;
+pub use rate_limit::RateLimit;
+use rate_limit::RateLimiter;
+#[cfg(feature = "record")]
+pub use record::{RecordTiming, Recorder};
+pub use script::{Action, KeyCombo, Script, ScriptReport};
+use scroll::ScrollDevice;
+pub use sender::InputSender;
+pub use stats::InputStats;
+use stats::Stats;
+use stylus::Stylus;
+#[cfg(feature = "testing")]
+pub use testing::TestServer;
+use touch::{TouchAction, Touches};
+#[cfg(feature = "uinput")]
+pub use uinput::{UinputBackend, UinputTouchscreen};
+#[cfg(feature = "wayland")]
+pub use wayland::WaylandBackend;
+
+/// Conversions between characters, keysyms and keysym names, the same ones used for typing.
+pub mod keysyms {
+    pub use crate::keysym::{
+        char_to_keysym, keysym_from_name, keysym_to_char, keysym_to_name as keysym_name,
+    };
+}
+
+/// Emit a `tracing` event at debug level, if the `tracing` feature is enabled. Compiles to
+/// nothing otherwise.
+macro_rules! trace_event {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+
 struct KeymapState {
+    /// The context `mapping` was created in. Kept together with the keymap since they share
+    /// reference counts.
+    context: xkbcommon::xkb::Context,
     mapping: xkbcommon::xkb::Keymap,
     // Which keycode activate which modifier, assuming modifiers are independent.
     modifier_keycode: HashMap<u8, u32>,
+    /// The effective layout (XKB group) of the keyboard, which keys are looked up in.
+    layout: u32,
+    /// The modifiers to hold and the key to press for each layout and keysym.
+    key_sequences: HashMap<(u32, u32), (Vec<u32>, u32)>,
+    /// The XInput id of the keyboard the keymap was loaded from, 0 if it wasn't.
+    device_id: u8,
+}
+
+// SAFETY: libxkbcommon objects aren't tied to a thread, but their reference counting isn't atomic.
+// A `KeymapState` holds the context and every object created from it, and never lets clones of
+// them escape, so all reference count changes happen through whoever has the `KeymapState`.
+// Moving all of it to another thread at once is fine; shared access is serialized by the `Mutex`
+// in `InputSynth`.
+unsafe impl Send for KeymapState {}
+
+impl KeymapState {
+    fn new(context: &xkbcommon::xkb::Context, mapping: xkbcommon::xkb::Keymap) -> Self {
+        let mut state = xkbcommon::xkb::State::new(&mapping);
+
+        let mut modifier_keycode = HashMap::new();
+        mapping.key_for_each(|map, k| {
+            // reset mask
+            state.update_mask(0, 0, 0, 0, 0, 0);
+            state.update_key(k, KeyDirection::Down);
+            for m in 0..map.num_mods() {
+                if state.mod_index_is_active(m, xkbcommon::xkb::STATE_MODS_DEPRESSED) {
+                    modifier_keycode.insert(m as u8, k);
+                }
+            }
+        });
+
+        KeymapState {
+            context: context.clone(),
+            key_sequences: Self::key_sequences(&mapping, &modifier_keycode),
+            mapping,
+            modifier_keycode,
+            layout: 0,
+            device_id: 0,
+        }
+    }
+
+    /// A copy of this keymap that doesn't share reference counts with it, so it can be used
+    /// from another thread. `None` if the copy fails to compile.
+    pub(crate) fn duplicate(&self) -> Option<Self> {
+        let context = xkbcommon::xkb::Context::new(xkbcommon::xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkbcommon::xkb::Keymap::new_from_string(
+            &context,
+            self.mapping
+                .get_as_string(xkbcommon::xkb::KEYMAP_FORMAT_TEXT_V1),
+            xkbcommon::xkb::KEYMAP_FORMAT_TEXT_V1,
+            xkbcommon::xkb::COMPILE_NO_FLAGS,
+        )?;
+        let mut copy = Self::new(&context, keymap);
+        copy.layout = self.layout;
+        copy.device_id = self.device_id;
+        Some(copy)
+    }
+
+    /// Names of the modifiers, with their indices.
+    fn modifier_names(&self) -> HashMap<String, u8> {
+        (0..self.mapping.num_mods())
+            .map(|m| (self.mapping.mod_get_name(m).to_owned(), m as u8))
+            .collect()
+    }
+
+    /// A key that activates the modifier named `name`.
+    fn modifier_keycode(&self, name: &str) -> Option<u32> {
+        let index = self.mapping.mod_get_index(name);
+        if index >= self.mapping.num_mods() {
+            return None;
+        }
+        self.modifier_keycode.get(&(index as u8)).copied()
+    }
+
+    /// The core modifier mask set by holding the modifier keys `keycodes`.
+    fn modifier_mask(&self, keycodes: &[u32]) -> u16 {
+        self.modifier_keycode
+            .iter()
+            .filter(|&(&m, k)| m < 8 && keycodes.contains(k))
+            .fold(0, |mask, (&m, _)| mask | 1 << m)
+    }
+
+    /// An XKB state with `mods` held, in the effective layout.
+    fn state(&self, mods: u32) -> xkbcommon::xkb::State {
+        let mut state = xkbcommon::xkb::State::new(&self.mapping);
+        state.update_mask(mods, 0, 0, 0, 0, self.layout);
+        state
+    }
+
+    /// The keysym `keycode` produces with the modifiers in the mask `mods` held.
+    pub(crate) fn keycode_to_keysym(&self, keycode: u32, mods: u32) -> Option<u32> {
+        let state = self.state(mods);
+        match state.key_get_one_sym(keycode) {
+            0 => None,
+            keysym => Some(keysym),
+        }
+    }
+
+    /// Every key that produces `keysym` at some level in the effective layout.
+    fn keycodes_for_keysym(&self, keysym: u32) -> Vec<u32> {
+        let state = self.state(0);
+        let mut keycodes = Vec::new();
+        self.mapping.key_for_each(|map, k| {
+            let layout = state.key_get_layout(k);
+            if (0..map.num_levels_for_key(k, layout)).any(|level| {
+                map.key_get_syms_by_level(k, layout, level)
+                    .contains(&keysym)
+            }) {
+                keycodes.push(k);
+            }
+        });
+        keycodes
+    }
+
+    /// Index every keysym the keymap can produce, for each layout that can be active.
+    fn key_sequences(
+        mapping: &xkbcommon::xkb::Keymap,
+        modifier_keycode: &HashMap<u8, u32>,
+    ) -> HashMap<(u32, u32), (Vec<u32>, u32)> {
+        let mut sequences = HashMap::new();
+        for layout in 0..mapping.num_layouts() {
+            // Keys can have fewer layouts than the keymap, the state knows how they wrap.
+            let mut state = xkbcommon::xkb::State::new(mapping);
+            state.update_mask(0, 0, 0, 0, 0, layout);
+            mapping.key_for_each(|map, k| {
+                let key_layout = state.key_get_layout(k);
+                for level in 0..map.num_levels_for_key(k, key_layout) {
+                    let &[sym] = map.key_get_syms_by_level(k, key_layout, level) else {
+                        continue;
+                    };
+                    if sequences.contains_key(&(layout, sym)) {
+                        continue;
+                    }
+                    if let Some(mods) =
+                        Self::modifier_keys(map, modifier_keycode, k, key_layout, level)
+                    {
+                        sequences.insert((layout, sym), (mods, k));
+                    }
+                }
+            });
+        }
+        sequences
+    }
+
+    /// The keycodes of modifiers to hold for `keycode` to produce `level`, if there are keys for
+    /// all of them.
+    fn modifier_keys(
+        mapping: &xkbcommon::xkb::Keymap,
+        modifier_keycode: &HashMap<u8, u32>,
+        keycode: u32,
+        layout: u32,
+        level: u32,
+    ) -> Option<Vec<u32>> {
+        // Use the first mask we have keys for all modifiers of.
+        let mut state = xkbcommon::xkb::State::new(mapping);
+        xkb_extra::mods_for_level(mapping, keycode, layout, level)
+            .into_iter()
+            .find_map(|mask| {
+                let mask = Self::real_mods(&mut state, mask);
+                (0..mapping.num_mods().min(u32::BITS))
+                    .filter(|&m| mask & (1 << m) != 0)
+                    .map(|m| modifier_keycode.get(&(m as _)).copied())
+                    .collect()
+            })
+    }
+
+    /// `mask` with its virtual modifiers (LevelThree, NumLock, ...) replaced by the real
+    /// modifiers they are mapped to in the keymap, which are what `modifier_keycode` has keys for.
+    fn real_mods(state: &mut xkbcommon::xkb::State, mask: u32) -> u32 {
+        /// Shift, Lock, Control and Mod1 to Mod5 always come first in the keymap.
+        const REAL_MODS: u32 = 0xff;
+        // The state resolves virtual modifiers when they are set. Newer libxkbcommon versions
+        // keep the virtual modifiers in the result as well.
+        state.update_mask(mask, 0, 0, 0, 0, 0);
+        state.serialize_mods(xkbcommon::xkb::STATE_MODS_DEPRESSED) & REAL_MODS
+    }
+
+    /// Every keysym the effective layout can produce, in order.
+    fn keysyms(&self) -> Vec<u32> {
+        let mut keysyms: Vec<u32> = self
+            .key_sequences
+            .keys()
+            .filter(|&&(layout, _)| layout == self.layout)
+            .map(|&(_, keysym)| keysym)
+            .collect();
+        keysyms.sort_unstable();
+        keysyms
+    }
+
+    /// The key producing `sym` in the effective layout, and the modifiers to hold for it.
+    pub(crate) fn find_key_sequence(&self, key: Key) -> Option<(Vec<u32>, u32)> {
+        self.key_sequences
+            .get(&(self.layout, key.keysym()))
+            .cloned()
+    }
+}
+
+/// Lock `mutex`, ignoring poisoning. None of our locked state can be left inconsistent by a panic.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
 }
 
+/// A key press or release received from the server, see [`InputSynth::wait_for_key_event`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub keycode: u32,
+    pub pressed: bool,
+    /// Server time of the event, in milliseconds.
+    pub time: u32,
+}
+
+/// A button press or release received from the server, see
+/// [`InputSynth::wait_for_button_event`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ButtonEvent {
+    pub button: u8,
+    pub pressed: bool,
+    /// Server time of the event, in milliseconds.
+    pub time: u32,
+}
+
+/// What an input device is used as, see [`InputDevice`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceUse {
+    /// The core pointer.
+    Pointer,
+    /// The core keyboard.
+    Keyboard,
+    /// A device that is neither a pointer nor a keyboard, or of a kind we don't know.
+    ExtensionDevice,
+    ExtensionKeyboard,
+    ExtensionPointer,
+}
+
+impl From<x11rb::protocol::xinput::DeviceUse> for DeviceUse {
+    fn from(device_use: x11rb::protocol::xinput::DeviceUse) -> Self {
+        use x11rb::protocol::xinput::DeviceUse as XDeviceUse;
+        match device_use {
+            XDeviceUse::IS_X_POINTER => Self::Pointer,
+            XDeviceUse::IS_X_KEYBOARD => Self::Keyboard,
+            XDeviceUse::IS_X_EXTENSION_KEYBOARD => Self::ExtensionKeyboard,
+            XDeviceUse::IS_X_EXTENSION_POINTER => Self::ExtensionPointer,
+            _ => Self::ExtensionDevice,
+        }
+    }
+}
+
+/// An input device of the server, see [`InputSynth::list_devices`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputDevice {
+    /// XInput device id.
+    pub id: u8,
+    pub name: String,
+    pub device_use: DeviceUse,
+}
+
+/// A keyboard device the keymap can be taken from, see [`InputSynth::keyboards`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyboardInfo {
+    /// XInput device id, to pass to [`InputSynth::set_keyboard_device`].
+    pub id: u8,
+    pub name: String,
+    /// Whether this is the XKB core keyboard, whose keymap is used by default.
+    pub is_core: bool,
+}
+
+/// Sends a release when dropped, so a button pressed by `hold_button` isn't left pressed when
+/// something in between fails or panics. Keys are held by a [`KeyGuard`] instead.
+struct ReleaseGuard<'a> {
+    synth: &'a InputSynth,
+    release: InputEvent,
+}
+
+impl ReleaseGuard<'_> {
+    /// Release now, reporting errors.
+    fn release(self) -> Result<()> {
+        let result = self.release.send(self.synth);
+        std::mem::forget(self);
+        result
+    }
+}
+
+impl Drop for ReleaseGuard<'_> {
+    fn drop(&mut self) {
+        let _ = self.release.send(self.synth);
+    }
+}
+
+/// A key held down by [`InputSynth::hold`], together with the modifiers it needs. It's released
+/// when this is dropped, also when unwinding from a panic. Several guards are released in the
+/// order they are dropped, which for local variables is the reverse of the order they were
+/// created in.
+///
+/// Keys held by several guards, like Shift held on its own and for a held `A`, stay down until
+/// the last of them is gone.
+pub struct KeyGuard<'a> {
+    synth: &'a InputSynth,
+    holder: HolderId,
+    keysym: u32,
+}
+
+impl KeyGuard<'_> {
+    /// The keysym of the held key.
+    pub fn keysym(&self) -> u32 {
+        self.keysym
+    }
+
+    /// Release the key now. Unlike dropping the guard, this reports errors.
+    pub fn release(self) -> Result<()> {
+        let result = self.synth.release_held(self.holder);
+        std::mem::forget(self);
+        result
+    }
+}
+
+impl Drop for KeyGuard<'_> {
+    fn drop(&mut self) {
+        let _ = self.synth.release_held(self.holder);
+    }
+}
+
+/// Modifier keys held by [`InputSynth::with_modifiers`], released when this is dropped.
+pub struct ModifierGuard<'a> {
+    synth: &'a InputSynth,
+    /// Holds the keys this guard pressed, not those that were already held.
+    holder: HolderId,
+    modifiers: Modifiers,
+}
+
+impl ModifierGuard<'_> {
+    /// The modifiers held while this guard exists.
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+    /// Release the keys now. Unlike dropping the guard, this reports errors.
+    pub fn release(self) -> Result<()> {
+        let result = self.synth.release_held(self.holder);
+        std::mem::forget(self);
+        result
+    }
+}
+
+impl Drop for ModifierGuard<'_> {
+    fn drop(&mut self) {
+        let _ = self.synth.release_held(self.holder);
+    }
+}
+
+impl std::fmt::Debug for ModifierGuard<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ModifierGuard")
+            .field("modifiers", &self.modifiers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl std::fmt::Debug for KeyGuard<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyGuard")
+            .field("keysym", &self.keysym)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Synthesizes input events on an X server.
+///
+/// `InputSynth` can be shared between threads. Every public method runs as a unit: while one
+/// thread is, for example, typing a string, other threads calling into the same `InputSynth`
+/// wait, so their events are never interleaved with it.
 pub struct InputSynth {
-    connection: XCBConnection,
+    /// Replaced when reconnecting, see [`ReconnectPolicy`].
+    connection: Mutex<Arc<XConnection>>,
+    /// The display we connected to, `None` for `$DISPLAY`.
+    display: Option<String>,
+    reconnect_policy: ReconnectPolicy,
+    on_reconnect: Option<Box<dyn Fn() + Send + Sync>>,
+    call_lock: CallLock,
+    /// The outermost public method running, to say which one sent a rejected event.
+    current_method: Mutex<&'static str>,
+    /// Whether we read events from the connection ourselves. Not the case if the connection is
+    /// shared with someone else, who then passes relevant events on through `notify_event`.
+    poll_events: bool,
     screen: usize,
-    mapping: RefCell<KeymapState>,
-    xkb_context: xkbcommon::xkb::Context,
+    /// Width and height of every screen, which change when RandR changes the resolution.
+    screen_sizes: Mutex<Vec<(u16, u16)>>,
+    /// The device the keymap is taken from, `None` for the core keyboard.
+    keyboard_device: Option<u8>,
+    mapping: Mutex<KeymapState>,
+    typing_delay: TypingDelay,
+    newline_policy: NewlinePolicy,
+    rate_limiter: Mutex<RateLimiter>,
+    stats: Stats,
+    #[cfg(feature = "rand")]
+    humanizer: Mutex<Option<Humanizer>>,
+    /// Created the first time we need to own a selection.
+    selections: Mutex<Option<Selections>>,
+    selection_timeout: Duration,
+    touches: Mutex<Touches>,
+    /// Keys pressed by live `KeyGuard`s and `ModifierGuard`s.
+    held_keys: Mutex<HeldKeys>,
+    /// Looked up on first use, `None` inside if there is no tablet.
+    stylus: Mutex<Option<Option<Stylus>>>,
+    /// Looked up on first use, `None` inside if there is no device with scroll valuators.
+    scroll_device: Mutex<Option<Option<ScrollDevice>>>,
+    /// Compiled by the first `type_char_compose`.
+    compose_table: Mutex<Option<compose::LocaleTable>>,
+    /// Whether our requests ignore grabs, see `set_grab_control`.
+    impervious_to_grabs: std::sync::atomic::AtomicBool,
+    /// Whether to check for grabs before clicking and typing, see `set_fail_on_grab`.
+    fail_on_grab: bool,
+    request_checking: RequestChecking,
+    /// What coordinates given to `click` and `move_cursor` are multiplied with.
+    coordinate_scale: f64,
+    /// Events sent with [`RequestChecking::Unchecked`] that errors may still arrive for.
+    pending_inputs: Mutex<PendingInputs>,
+    /// The queue of [`InputSender`]s, created by the first `make_sender`.
+    sender: Mutex<Option<std::sync::mpsc::Sender<InputEvent>>>,
+    receiver: Mutex<Option<std::sync::mpsc::Receiver<InputEvent>>>,
 }
 
-unsafe impl Send for InputSynth {}
-
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error("{0}")]
-    Connect(#[from] ConnectError),
+    #[error("failed to connect to display {display:?}: {source}")]
+    Connect {
+        display: String,
+        #[source]
+        source: ConnectError,
+    },
     #[error("{0}")]
     Connection(#[from] ConnectionError),
     #[error("{0}")]
     Reply(#[from] ReplyError),
+    #[error("{0}")]
+    ReplyOrId(#[from] ReplyOrIdError),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("no key produces keysym {keysym:#x} in the current keymap")]
+    NoKeySequence { keysym: u32 },
+    #[error("operation timed out after {0:?}")]
+    Timeout(Duration),
+    #[error("the X server does not support a compatible version of the {name} extension")]
+    ExtensionMissing { name: &'static str },
+    #[error("the X server has no keyboard device")]
+    NoKeyboardDevice,
+    #[error("the keymap could not be copied, its copy failed to compile")]
+    KeymapCopyFailed,
+    #[error(
+        "input focus is on window {actual:#x}{} instead of {expected:#x}",
+        .name.as_ref().map(|name| format!(" ({name:?})")).unwrap_or_default()
+    )]
+    FocusChanged {
+        expected: xproto::Window,
+        actual: xproto::Window,
+        /// `WM_NAME` of the focused window, if it has one.
+        name: Option<String>,
+    },
+    /// The error is decoded in the message: its kind, code, the value it complains about, and
+    /// the opcodes of the rejected request.
+    #[error(
+        "{} failed, the X server rejected the {input}: {:?} error (code {}, bad value {}, \
+         opcode {}.{})",
+        .input.method,
+        .error.error_kind,
+        .error.error_code,
+        .error.bad_value,
+        .error.major_opcode,
+        .error.minor_opcode
+    )]
+    FakeInputFailed {
+        input: FakeInput,
+        error: x11rb::x11_utils::X11Error,
+    },
+    #[error("input is grabbed by another client (keyboard: {keyboard}, pointer: {pointer})")]
+    InputGrabbed { keyboard: bool, pointer: bool },
+    #[error("no key sets the modifiers {0:?}")]
+    NoModifierKey(Modifiers),
+    #[error("no key in the keymap sets the {0:?} modifier")]
+    NoKeyForModifier(ShortcutModifiers),
+    #[error("window {0:#x} is not viewable")]
+    WindowNotViewable(xproto::Window),
+    #[error("input device {0} is not a keyboard")]
+    NotAKeyboard(u8),
+    #[error("screen {0} does not exist")]
+    InvalidScreen(usize),
+    #[error("monitor {0} does not exist")]
+    InvalidMonitor(usize),
+    #[error("character {0:?} cannot be typed with the current keymap")]
+    Untypeable(char),
+    #[error("{failed} out of {total} characters could not be typed")]
+    TypingFailed { failed: usize, total: usize },
+    #[error("{0} is not supported")]
+    Unsupported(&'static str),
+    #[error("touch {0} is already active or was never started")]
+    InvalidTouch(u32),
+    #[cfg(feature = "uinput")]
+    #[error("a gesture needs at least two fingers, not {0}")]
+    TooFewFingers(u8),
+    #[error("{0} is not a single digit")]
+    NotADigit(u8),
+    #[error("pressing {key} didn't turn it {}", if *on { "on" } else { "off" })]
+    LockUnchanged { key: Key, on: bool },
+    #[error("({x}, {y}) is outside of the {width}x{height} screen")]
+    CoordinatesOutOfBounds {
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+    },
+    #[error("timing factor {0} is not positive")]
+    InvalidFactor(f64),
+    #[error("coordinate scale {0} is not positive")]
+    InvalidScale(f64),
+    #[error("screen fraction {0} is not between 0 and 1")]
+    InvalidFraction(f64),
+    #[error("unknown key name {0:?}")]
+    UnknownKeyName(String),
+    #[error("step {step} of the script failed: {source}")]
+    ScriptFailed {
+        step: usize,
+        #[source]
+        source: Box<Error>,
+    },
+    #[cfg(feature = "uinput")]
+    #[error("no permission to open /dev/uinput, the user needs write access to it")]
+    UinputPermissionDenied,
+    #[cfg(feature = "wayland")]
+    #[error("Wayland error: {0}")]
+    Wayland(String),
 }
 
-extern "C" {
-    fn xkb_keymap_key_get_mods_for_level(
-        _: *mut xkbcommon::xkb::ffi::xkb_keymap,
-        _: xkbcommon::xkb::ffi::xkb_keycode_t,
-        _: xkbcommon::xkb::ffi::xkb_layout_index_t,
-        _: xkbcommon::xkb::ffi::xkb_level_index_t,
-        _: *mut xkbcommon::xkb::ffi::xkb_mod_mask_t,
-        _: usize,
-    ) -> usize;
+impl Error {
+    /// Whether the connection to the X server is gone, as opposed to a request having failed.
+    fn is_connection_lost(&self) -> bool {
+        let connection = match self {
+            Error::Connection(e)
+            | Error::Reply(ReplyError::ConnectionError(e))
+            | Error::ReplyOrId(ReplyOrIdError::ConnectionError(e)) => e,
+            _ => return false,
+        };
+        matches!(
+            connection,
+            ConnectionError::IoError(_) | ConnectionError::UnknownError
+        )
+    }
 }
+
+const CONTROL_L_KEYSYM: u32 = 0xffe3;
+const CAPS_LOCK_KEYSYM: u32 = 0xffe5;
+const NUM_LOCK_KEYSYM: u32 = 0xff7f;
+
 type Result<T, E = Error> = std::result::Result<T, E>;
-mod xkb_extra {
-    use xkbcommon::xkb::{
-        x11::ffi::{xkb_x11_keymap_new_from_device, xkb_x11_state_new_from_device},
-        Context, Keymap, KeymapCompileFlags, State,
-    };
 
-    #[must_use]
-    pub(super) fn keymap_new_from_device(
-        context: &Context,
-        connection: &x11rb::xcb_ffi::XCBConnection,
-        device_id: i32,
-        flags: KeymapCompileFlags,
-    ) -> Keymap {
-        unsafe {
-            Keymap::from_raw_ptr(xkb_x11_keymap_new_from_device(
-                context.get_raw_ptr(),
-                connection.get_raw_xcb_connection() as *mut _,
-                device_id,
-                flags,
-            ))
-        }
-    }
-
-    #[must_use]
-    pub(super) fn state_new_from_device(
-        keymap: &Keymap,
-        connection: &x11rb::xcb_ffi::XCBConnection,
-        device_id: i32,
-    ) -> State {
-        unsafe {
-            State::from_raw_ptr(xkb_x11_state_new_from_device(
-                keymap.get_raw_ptr(),
-                connection.get_raw_xcb_connection() as *mut _,
-                device_id,
-            ))
+/// The connection to the X server. libxcb's with the `xcb-ffi` feature, which is on by default,
+/// x11rb's pure Rust implementation without it.
+#[cfg(feature = "xcb-ffi")]
+pub type XConnection = x11rb::xcb_ffi::XCBConnection;
+/// The connection to the X server. libxcb's with the `xcb-ffi` feature, which is on by default,
+/// x11rb's pure Rust implementation without it.
+#[cfg(not(feature = "xcb-ffi"))]
+pub type XConnection = x11rb::rust_connection::RustConnection;
+
+/// Ask XKB for the id of the core keyboard device. `None` if the server doesn't report one.
+fn core_keyboard_device_id(conn: &impl Connection) -> Result<Option<i32>> {
+    use x11rb::protocol::xkb;
+    let info = xkb::get_device_info(
+        conn,
+        xkb::ID::USE_CORE_KBD.into(),
+        0u16.into(),
+        false,
+        0,
+        0,
+        0u16.into(),
+        0u16,
+    )?
+    .reply();
+    match info {
+        // Servers without a usable core keyboard answer with an error.
+        Err(ReplyError::X11Error(_)) => Ok(None),
+        Err(e) => Err(e.into()),
+        // XInput reserves 0 and 1 for all devices and all master devices.
+        Ok(info) if info.device_id < 2 => Ok(None),
+        Ok(info) => Ok(Some(info.device_id.into())),
+    }
+}
+
+/// Pick the core keyboard from the devices listed by `ListInputDevices`.
+fn select_keyboard_device(devices: &[x11rb::protocol::xinput::DeviceInfo]) -> Option<u8> {
+    devices
+        .iter()
+        .find(|d| d.device_use == x11rb::protocol::xinput::DeviceUse::IS_X_KEYBOARD)
+        .map(|d| d.device_id)
+}
+
+/// The keyboard to take the keymap from when none was chosen: `core`, the core keyboard as
+/// reported by XKB, or else the core keyboard among the listed `devices`.
+fn default_keyboard_device(
+    core: Option<i32>,
+    devices: &[x11rb::protocol::xinput::DeviceInfo],
+) -> Option<i32> {
+    core.or_else(|| select_keyboard_device(devices).map(i32::from))
+}
+
+/// `(x, y)` multiplied by `scale`, rounded to the nearest pixel.
+fn scale_point(x: i16, y: i16, scale: f64) -> (i16, i16) {
+    let scale = |v: i16| (f64::from(v) * scale).round() as i16;
+    (scale(x), scale(y))
+}
+
+/// The value of `Xft.dpi` in the X resources `resources`, as in the `RESOURCE_MANAGER`
+/// property.
+fn xft_dpi(resources: &[u8]) -> Option<f64> {
+    String::from_utf8_lossy(resources).lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if name.trim() != "Xft.dpi" {
+            return None;
         }
+        let dpi: f64 = value.trim().parse().ok()?;
+        (dpi > 0.0).then_some(dpi)
+    })
+}
+
+/// Whether a device listed by `ListInputDevices` is a keyboard, core or not.
+fn is_keyboard_device(device: &x11rb::protocol::xinput::DeviceInfo) -> bool {
+    use x11rb::protocol::xinput::DeviceUse;
+    device.device_use == DeviceUse::IS_X_KEYBOARD
+        || device.device_use == DeviceUse::IS_X_EXTENSION_KEYBOARD
+}
+
+/// Connects like [`InputSynth::new`].
+///
+/// # Panics
+///
+/// If connecting fails, e.g. because `$DISPLAY` isn't set. Use [`InputSynth::new`] to handle
+/// that instead.
+impl Default for InputSynth {
+    fn default() -> Self {
+        Self::new().expect("failed to connect to the X display, check that $DISPLAY is set")
+    }
+}
+
+impl std::fmt::Debug for InputSynth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let vendor = String::from_utf8_lossy(&self.conn().setup().vendor).into_owned();
+        let layout = {
+            let mapping = lock(&self.mapping);
+            mapping.mapping.layout_get_name(mapping.layout).to_owned()
+        };
+        f.debug_struct("InputSynth")
+            .field("display", &self.display)
+            .field("vendor", &vendor)
+            .field("screen", &self.screen)
+            .field("layout", &layout)
+            .finish_non_exhaustive()
     }
 }
+
 impl InputSynth {
+    /// Connect to the display named by `$DISPLAY` with the default settings.
     pub fn new() -> Result<Self> {
-        let (connection, screen) = XCBConnection::connect(None)?;
-        let (xkb_major, xkb_minor) = x11rb::protocol::xkb::X11_XML_VERSION;
-        x11rb::protocol::xkb::use_extension(&connection, xkb_major as _, xkb_minor as _)?
-            .reply()?;
+        Self::builder().build()
+    }
 
-        let (xtest_major, xtest_minor) = x11rb::protocol::xtest::X11_XML_VERSION;
-        connection
-            .xtest_get_version(xtest_major as _, xtest_minor as _)?
-            .reply()?;
+    /// Like [`Self::new`], but fail with [`Error::Timeout`] if connecting takes longer than
+    /// `timeout`, e.g. because `$DISPLAY` names a server that isn't answering. The attempt goes
+    /// on in the background after timing out, and its connection is closed if it succeeds.
+    pub fn new_timeout(timeout: Duration) -> Result<Self> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(Self::new());
+        });
+        receiver
+            .recv_timeout(timeout)
+            .unwrap_or(Err(Error::Timeout(timeout)))
+    }
+
+    /// Configure a new [`InputSynth`] before connecting.
+    pub fn builder() -> InputSynthBuilder {
+        InputSynthBuilder::default()
+    }
+
+    /// Connect to `display` (e.g. `":1"` or `":1.1"`) instead of the one named by `$DISPLAY`.
+    /// The screen number in the display string, if any, selects the screen to inject on.
+    pub fn new_with_display(display: &str) -> Result<Self> {
+        Self::builder().display(display).build()
+    }
+
+    fn from_builder(builder: &InputSynthBuilder) -> Result<Self> {
+        let (connection, display_screen) = Self::connect(builder.display.as_deref())?;
+        // `display_screen` comes from the display string, or is 0 if it didn't name one.
+        let screen = builder.screen.unwrap_or(display_screen);
+        Self::setup(Arc::new(connection), screen, builder, true)
+    }
+
+    /// Use a connection that is shared with the rest of the application, instead of opening a
+    /// new one.
+    ///
+    /// Since the application owns the event loop of a shared connection, we won't read events
+    /// from it on our own. The application has to pass events to [`Self::notify_event`] so we
+    /// notice keymap changes and clipboard requests. The exception is [`Self::get_clipboard`] and
+    /// [`Self::set_clipboard`], which do read events while waiting for an answer.
+    pub fn from_connection(connection: Arc<XConnection>, screen: usize) -> Result<Self> {
+        Self::setup(connection, screen, &InputSynthBuilder::default(), false)
+    }
+
+    /// Use an existing connection, injecting on `screen`. Unlike [`Self::from_connection`], the
+    /// connection is handed over to us, so we read events from it like from our own.
+    ///
+    /// Fails if the server lacks the XKB or XTEST extension. [`Self::reconnect`] connects to
+    /// `$DISPLAY`, since the display of the connection isn't known. Only available with the
+    /// `xcb-ffi` feature.
+    #[cfg(feature = "xcb-ffi")]
+    pub fn new_from_xcb(connection: x11rb::xcb_ffi::XCBConnection, screen: usize) -> Result<Self> {
+        Self::setup(
+            Arc::new(connection),
+            screen,
+            &InputSynthBuilder::default(),
+            true,
+        )
+    }
+
+    /// Open another connection to the same display, with the same screen, keyboard device and
+    /// settings, e.g. for another thread to inject on without waiting for this one. The reconnect
+    /// callback and the statistics are not carried over.
+    ///
+    /// Instances created from a connection connect to `$DISPLAY`, since the display of their
+    /// connection isn't known.
+    pub fn clone_connection(&self) -> Result<InputSynth> {
+        let mut builder = Self::builder()
+            .screen(self.screen)
+            .typing_delay(self.typing_delay)
+            .newline_policy(self.newline_policy)
+            .rate_limit(self.rate_limit())
+            .selection_timeout(self.selection_timeout)
+            .reconnect_policy(self.reconnect_policy)
+            .impervious_to_grabs(self.impervious_to_grabs.load(Ordering::Relaxed))
+            .fail_on_grab(self.fail_on_grab)
+            .request_checking(self.request_checking)
+            .coordinate_scale(self.coordinate_scale);
+        builder.display = self.display.clone();
+        builder.keyboard_device = self.keyboard_device;
+        #[cfg(feature = "rand")]
+        if let Some(config) = self.humanize() {
+            builder = builder.humanize(config);
+        }
+        builder.build()
+    }
+
+    fn setup(
+        connection: Arc<XConnection>,
+        screen: usize,
+        builder: &InputSynthBuilder,
+        poll_events: bool,
+    ) -> Result<Self> {
+        Self::check_server(&*connection, screen)?;
+        Self::check_scale(builder.coordinate_scale)?;
+        Self::select_xkb_events(&*connection)?;
+        Self::select_randr_events(&*connection)?;
+        if builder.impervious_to_grabs {
+            connection.xtest_grab_control(true)?;
+        }
         let context = xkbcommon::xkb::Context::new(0);
 
         connection.flush()?;
         Ok(Self {
-            mapping: RefCell::new(Self::get_keymap_state(&connection, &context)?),
-            connection,
+            screen_sizes: Mutex::new(Self::setup_screen_sizes(&*connection)),
+            mapping: Mutex::new(Self::get_keymap_state(
+                &connection,
+                &context,
+                builder.keyboard_device,
+            )?),
+            connection: Mutex::new(connection),
+            display: builder.display.clone(),
+            reconnect_policy: if poll_events {
+                builder.reconnect_policy
+            } else {
+                ReconnectPolicy::Never
+            },
+            on_reconnect: None,
+            call_lock: CallLock::default(),
+            current_method: Mutex::new(""),
+            poll_events,
             screen,
-            xkb_context: context,
+            keyboard_device: builder.keyboard_device,
+            typing_delay: builder.typing_delay,
+            newline_policy: builder.newline_policy,
+            rate_limiter: Mutex::new(RateLimiter::new(builder.rate_limit)),
+            stats: Stats::default(),
+            #[cfg(feature = "rand")]
+            humanizer: Mutex::new(builder.humanize.clone().map(Humanizer::new)),
+            selections: Mutex::new(None),
+            selection_timeout: builder.selection_timeout,
+            touches: Mutex::new(Touches::default()),
+            held_keys: Mutex::new(HeldKeys::default()),
+            stylus: Mutex::new(None),
+            scroll_device: Mutex::new(None),
+            compose_table: Mutex::new(None),
+            sender: Mutex::new(None),
+            receiver: Mutex::new(None),
+            impervious_to_grabs: builder.impervious_to_grabs.into(),
+            fail_on_grab: builder.fail_on_grab,
+            request_checking: builder.request_checking,
+            coordinate_scale: builder.coordinate_scale,
+            pending_inputs: Mutex::new(PendingInputs::default()),
+        })
+    }
+
+    /// Connect to `display`, or `$DISPLAY`. Returns the screen named in the display string.
+    fn connect(display: Option<&str>) -> Result<(XConnection, usize)> {
+        #[cfg(feature = "xcb-ffi")]
+        let connection = display
+            .map(std::ffi::CString::new)
+            .transpose()
+            .map_err(|_| ConnectError::DisplayParsingError)
+            .and_then(|name| XConnection::connect(name.as_deref()));
+        #[cfg(not(feature = "xcb-ffi"))]
+        let connection = XConnection::connect(display);
+        connection.map_err(|source| Error::Connect {
+            display: display
+                .map(str::to_owned)
+                .or_else(|| std::env::var("DISPLAY").ok())
+                .unwrap_or_default(),
+            source,
         })
     }
 
+    /// Ask to be told about changes of the keymap and the active layout, see `handle_event`.
+    fn select_xkb_events(connection: &impl Connection) -> Result<()> {
+        use x11rb::protocol::xkb::{self, EventType, MapPart};
+        let map_parts = MapPart::KEY_TYPES
+            | MapPart::KEY_SYMS
+            | MapPart::MODIFIER_MAP
+            | MapPart::EXPLICIT_COMPONENTS
+            | MapPart::KEY_ACTIONS
+            | MapPart::KEY_BEHAVIORS
+            | MapPart::VIRTUAL_MODS
+            | MapPart::VIRTUAL_MOD_MAP;
+        xkb::select_events(
+            connection,
+            xkb::ID::USE_CORE_KBD.into(),
+            0u16.into(),
+            EventType::NEW_KEYBOARD_NOTIFY | EventType::MAP_NOTIFY | EventType::STATE_NOTIFY,
+            map_parts,
+            map_parts,
+            &xkb::SelectEventsAux::new(),
+        )?;
+        Ok(())
+    }
+
+    /// Ask to be told about resolution changes, see `handle_event`. Servers without RandR have a
+    /// fixed resolution.
+    fn select_randr_events(connection: &impl Connection) -> Result<()> {
+        use x11rb::protocol::randr::{self, NotifyMask};
+        if connection
+            .extension_information(randr::X11_EXTENSION_NAME)?
+            .is_none()
+        {
+            return Ok(());
+        }
+        let (major, minor) = randr::X11_XML_VERSION;
+        randr::query_version(connection, major, minor)?.reply()?;
+        // On every screen, so nothing changes when switching screens.
+        for screen in &connection.setup().roots {
+            randr::select_input(connection, screen.root, NotifyMask::SCREEN_CHANGE)?;
+        }
+        Ok(())
+    }
+
+    /// The size of every screen when we connected.
+    fn setup_screen_sizes(connection: &impl Connection) -> Vec<(u16, u16)> {
+        connection
+            .setup()
+            .roots
+            .iter()
+            .map(|screen| (screen.width_in_pixels, screen.height_in_pixels))
+            .collect()
+    }
+
+    /// Make sure the server has `screen` and all the extensions we need.
+    fn check_server(connection: &impl Connection, screen: usize) -> Result<()> {
+        if screen >= connection.setup().roots.len() {
+            return Err(Error::InvalidScreen(screen));
+        }
+        for name in [
+            x11rb::protocol::xkb::X11_EXTENSION_NAME,
+            x11rb::protocol::xtest::X11_EXTENSION_NAME,
+            x11rb::protocol::xinput::X11_EXTENSION_NAME,
+        ] {
+            if connection.extension_information(name)?.is_none() {
+                return Err(Error::ExtensionMissing { name });
+            }
+        }
+        let (xkb_major, xkb_minor) = x11rb::protocol::xkb::X11_XML_VERSION;
+        let xkb = x11rb::protocol::xkb::use_extension(connection, xkb_major as _, xkb_minor as _)?
+            .reply()?;
+        if !xkb.supported {
+            return Err(Error::ExtensionMissing {
+                name: x11rb::protocol::xkb::X11_EXTENSION_NAME,
+            });
+        }
+
+        let (xtest_major, xtest_minor) = x11rb::protocol::xtest::X11_XML_VERSION;
+        let xtest = connection
+            .xtest_get_version(xtest_major as _, xtest_minor as _)?
+            .reply()?;
+        // FakeInput has been around since 2.0, but 1.x is something else entirely.
+        if xtest.major_version < 2 {
+            return Err(Error::ExtensionMissing {
+                name: x11rb::protocol::xtest::X11_EXTENSION_NAME,
+            });
+        }
+        Ok(())
+    }
+
+    /// Load the keymap of `device`, or of the core keyboard if `None`.
     fn get_keymap_state(
-        conn: &XCBConnection,
+        conn: &XConnection,
         ctx: &xkbcommon::xkb::Context,
+        device: Option<u8>,
     ) -> Result<KeymapState> {
         conn.flush()?;
         let devices = x11rb::protocol::xinput::list_input_devices(conn)?.reply()?;
-        let device = devices
-            .devices
-            .iter()
-            .find(|d| d.device_use == x11rb::protocol::xinput::DeviceUse::IS_X_KEYBOARD)
-            .unwrap();
-        let mapping = xkb_extra::keymap_new_from_device(ctx, conn, device.device_id as _, 0);
-        let mut state = xkb_extra::state_new_from_device(&mapping, conn, device.device_id as _);
-
-        let mut modifier_keycode = HashMap::new();
-        mapping.key_for_each(|map, k| {
-            // reset mask
-            state.update_mask(0, 0, 0, 0, 0, 0);
-            state.update_key(k, KeyDirection::Down);
-            for m in 0..map.num_mods() {
-                if state.mod_index_is_active(m, xkbcommon::xkb::STATE_MODS_DEPRESSED) {
-                    modifier_keycode.insert(m as u8, k);
+        let device_id = match device {
+            Some(id) => {
+                if !devices
+                    .devices
+                    .iter()
+                    .any(|d| d.device_id == id && is_keyboard_device(d))
+                {
+                    return Err(Error::NotAKeyboard(id));
                 }
+                i32::from(id)
             }
-        });
-
-        Ok(KeymapState {
-            mapping,
-            modifier_keycode,
-        })
-    }
-
-    fn handle_events(&self) -> Result<()> {
-        while let Some(event) = self.connection.poll_for_event()? {
-            use x11rb::protocol::Event;
-            if let Event::MappingNotify(_) = event {
-                self.mapping
-                    .replace(Self::get_keymap_state(&self.connection, &self.xkb_context)?);
+            // The first keyboard listed can be any keyboard-like device, like a security key
+            // with a stripped down keymap, so ask XKB first.
+            None => default_keyboard_device(core_keyboard_device_id(conn)?, &devices.devices)
+                .ok_or(Error::NoKeyboardDevice)?,
+        };
+        let mapping = match (
+            xkb_extra::keymap_new_from_device(ctx, conn, device_id, 0),
+            device,
+        ) {
+            (Some(mapping), _) => mapping,
+            // The core mapping only describes the core keyboard.
+            (None, Some(_)) if !cfg!(feature = "xcb-ffi") => {
+                return Err(Error::Unsupported(
+                    "keymaps of other keyboards without the `xcb-ffi` feature",
+                ))
             }
-        }
-        Ok(())
+            (None, Some(id)) => return Err(Error::NotAKeyboard(id)),
+            (None, None) => {
+                core_keymap::keymap_from_core_mapping(conn, ctx)?.ok_or(Error::NoKeyboardDevice)?
+            }
+        };
+        let mut state = KeymapState::new(ctx, mapping);
+        state.device_id = device_id as u8;
+        let device_spec = device.map_or(x11rb::protocol::xkb::ID::USE_CORE_KBD.into(), u16::from);
+        let xkb_state = x11rb::protocol::xkb::get_state(conn, device_spec)?.reply()?;
+        state.layout = u8::from(xkb_state.group).into();
+        Ok(state)
     }
 
-    /// Generate a mouse click at `(x, y)`, with `button`. `press` indicates if the click is a
-    /// press, if it's false, a release will be generated.
-    pub fn click(&self, x: i16, y: i16, button: u8, press: bool) -> Result<()> {
-        self.handle_events()?;
-        self.connection
-            .xtest_fake_input(
-                if press {
-                    xproto::BUTTON_PRESS_EVENT
-                } else {
-                    xproto::BUTTON_RELEASE_EVENT
-                },
-                button,
-                x11rb::CURRENT_TIME,
-                self.connection.setup().roots[self.screen].root,
-                x,
-                y,
-                x11rb::NONE as _,
-            )?
-            .check()?;
+    /// Replace the keymap with the server's current one. The keymap and everything derived from
+    /// it are swapped together, under the lock.
+    fn load_keymap(&self) -> Result<()> {
+        let mut mapping = lock(&self.mapping);
+        *mapping = Self::get_keymap_state(&self.conn(), &mapping.context, self.keyboard_device)?;
         Ok(())
     }
-    pub fn move_cursor(&self, x: i16, y: i16) -> Result<()> {
-        self.handle_events()?;
-        self.connection
-            .xtest_fake_input(
-                xproto::MOTION_NOTIFY_EVENT,
-                0,
-                x11rb::CURRENT_TIME,
-                self.connection.setup().roots[self.screen].root,
-                x,
-                y,
-                x11rb::NONE as _,
-            )?
-            .check()?;
-        Ok(())
+
+    /// Load the keymap from the server again, e.g. right after changing it with `setxkbmap`,
+    /// instead of waiting for the server to announce the change.
+    pub fn refresh_keymap(&self) -> Result<()> {
+        self.call("refresh_keymap", || self.load_keymap())
     }
 
-    pub(crate) fn find_key_sequence(&self, sym: u16) -> Option<(Vec<u32>, u32)> {
-        // TODO: handle layouts, now we always assume layout 0
-        let mapping = self.mapping.borrow();
-        let mut ans = None;
-        mapping.mapping.key_for_each(|map, k| {
-            if ans.is_none() {
-                let nlevels = map.num_levels_for_key(k, 0);
-                for level in 0..nlevels {
-                    let syms = map.key_get_syms_by_level(k, 0, level);
-                    if syms.len() == 1 && syms[0] == sym.into() {
-                        ans.replace((level, k));
-                    }
-                }
+    /// Run the body `f` of a public call: stop other threads from injecting events until it's
+    /// done, and catch up on events from the server first.
+    ///
+    /// If the connection to the server is lost and the [`ReconnectPolicy`] allows it, reconnect
+    /// and run `f` once more. Only the outermost call does this, when public methods call each
+    /// other.
+    fn call<T>(&self, method: &'static str, mut f: impl FnMut() -> Result<T>) -> Result<T> {
+        let guard = self.call_lock.enter();
+        if guard.is_outermost() {
+            *lock(&self.current_method) = method;
+        }
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("inputsynth", method).entered();
+        #[cfg(not(feature = "tracing"))]
+        let _ = method;
+        match self.handle_events().and_then(|()| f()) {
+            Err(e)
+                if e.is_connection_lost()
+                    && guard.is_outermost()
+                    && self.reconnect_policy.attempts() > 0 =>
+            {
+                self.reconnect_with_retries(self.reconnect_policy.attempts())?;
+                self.handle_events()?;
+                f()
             }
-        });
+            result => result,
+        }
+    }
 
-        // Get the key sequence that will produce level + keycode
-        let mut mods = Vec::new();
-        if let Some((level, keycode)) = ans {
-            let mut masks = [0; 4];
-            unsafe {
-                xkb_keymap_key_get_mods_for_level(
-                    mapping.mapping.get_raw_ptr(),
-                    keycode,
-                    0,
-                    level,
-                    masks.as_mut_ptr(),
-                    4,
-                )
-            };
-            'next_mask: for mask in masks.iter() {
-                for m in 0..mapping.mapping.num_mods() {
-                    if (*mask & (1 << m)) != 0 && !mapping.modifier_keycode.contains_key(&(m as _))
-                    {
-                        continue 'next_mask;
-                    }
+    /// Drop the connection to the X server and connect to the same display again, e.g. after
+    /// the server was restarted. The keymap is loaded again and all state tied to the old server,
+    /// like active touches, is reset.
+    ///
+    /// Only one attempt is made. With a [`ReconnectPolicy`], methods do this on their own when
+    /// they find the connection lost. Fails with [`Error::Unsupported`] for instances created
+    /// with [`Self::from_connection`], since the connection belongs to the application.
+    pub fn reconnect(&self) -> Result<()> {
+        let _guard = self.call_lock.enter();
+        if !self.poll_events {
+            return Err(Error::Unsupported("reconnecting a shared connection"));
+        }
+        self.reconnect_with_retries(1)
+    }
+
+    /// Replace the connection with a new one to the same display, making up to `retries`
+    /// attempts.
+    fn reconnect_with_retries(&self, retries: u32) -> Result<()> {
+        /// Wait before the second attempt, doubled for each attempt after that.
+        const FIRST_BACKOFF: Duration = Duration::from_millis(100);
+        const MAX_BACKOFF: Duration = Duration::from_secs(5);
+        let mut backoff = FIRST_BACKOFF;
+        let mut attempt = 1;
+        let connection = loop {
+            let result = Self::connect(self.display.as_deref()).and_then(|(connection, _)| {
+                Self::check_server(&connection, self.screen)?;
+                Self::select_xkb_events(&connection)?;
+                Self::select_randr_events(&connection)?;
+                if self.impervious_to_grabs.load(Ordering::Relaxed) {
+                    connection.xtest_grab_control(true)?;
                 }
-                // We are able to find all the modifiers
-                for m in 0..mapping.mapping.num_mods() {
-                    if (*mask & (1 << m)) != 0 {
-                        mods.push(*mapping.modifier_keycode.get(&(m as _)).unwrap())
-                    }
+                Ok(connection)
+            });
+            match result {
+                Ok(connection) => break connection,
+                Err(e) if attempt >= retries => return Err(e),
+                Err(_) => {
+                    std::thread::sleep(backoff);
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    attempt += 1;
                 }
-                return Some((mods, keycode));
             }
+        };
+        {
+            let mut mapping = lock(&self.mapping);
+            *mapping = Self::get_keymap_state(&connection, &mapping.context, self.keyboard_device)?;
         }
-        None
+        let screen_sizes = Self::setup_screen_sizes(&connection);
+        if self.screen >= screen_sizes.len() {
+            return Err(Error::InvalidScreen(self.screen));
+        }
+        *lock(&self.screen_sizes) = screen_sizes;
+        *lock(&self.connection) = Arc::new(connection);
+        // Nothing is pressed on the new server, and devices and windows have to be found again.
+        *lock(&self.selections) = None;
+        *lock(&self.touches) = Touches::default();
+        // Not replaced, so guards from before don't share holder ids with new ones.
+        lock(&self.held_keys).take_all();
+        *lock(&self.stylus) = None;
+        *lock(&self.scroll_device) = None;
+        lock(&self.rate_limiter).flushed();
+        if let Some(on_reconnect) = &self.on_reconnect {
+            on_reconnect();
+        }
+        Ok(())
     }
 
-    pub fn ascii_char(&self, ch: u8) -> Result<()> {
-        self.handle_events()?;
-        let mut keysym: u16 = ch as _;
-        if (8..=17).contains(&ch) {
-            // Function keysyms are encoded in X as 0xffxx,
-            // we cover the most often used ones here.
-            keysym += 0xff00;
-        }
-
-        if let Some((mods, keycode)) = self.find_key_sequence(keysym) {
-            for &m in &mods {
-                self.connection.xtest_fake_input(
-                    xproto::KEY_PRESS_EVENT,
-                    m as _,
-                    x11rb::CURRENT_TIME,
-                    self.connection.setup().roots[self.screen].root,
+    /// Call `callback` after reconnecting to the X server, see [`ReconnectPolicy`]. State of the
+    /// session, like the focused window, is most likely lost at that point.
+    pub fn set_reconnect_callback(&mut self, callback: impl Fn() + Send + Sync + 'static) {
+        self.on_reconnect = Some(Box::new(callback));
+    }
+
+    fn conn(&self) -> Arc<XConnection> {
+        Arc::clone(&lock(&self.connection))
+    }
+
+    fn handle_events(&self) -> Result<()> {
+        if !self.poll_events {
+            return Ok(());
+        }
+        // A single change to the keymap is announced by several events, e.g. MappingNotify
+        // together with XkbMapNotify, or one XkbMapNotify per changed part. Load it once.
+        let mut keymap_changed = false;
+        while let Some(event) = self.conn().poll_for_event()? {
+            if Self::is_keymap_change(&event) {
+                keymap_changed = true;
+            } else {
+                self.handle_event(&event)?;
+            }
+        }
+        if keymap_changed {
+            self.load_keymap()?;
+        }
+        Ok(())
+    }
+
+    /// Whether `event` means the keymap has to be loaded again.
+    fn is_keymap_change(event: &x11rb::protocol::Event) -> bool {
+        use x11rb::protocol::Event;
+        // Core clients are told about keymap changes with MappingNotify, but XKB clients get
+        // XkbMapNotify instead on some servers. Switching to another keyboard, e.g. by plugging
+        // one in, is only announced with XkbNewKeyboardNotify, and needs the devices listed
+        // again, which loading the keymap does.
+        matches!(
+            event,
+            Event::MappingNotify(_) | Event::XkbNewKeyboardNotify(_) | Event::XkbMapNotify(_)
+        )
+    }
+
+    /// Start a thread that keeps reading events from the X server in the background, so keymap
+    /// changes are picked up and clipboard requests answered even while no method is being
+    /// called. The thread exits once the last reference to this `InputSynth` is dropped.
+    ///
+    /// Errors are left for the next method call to report. This does nothing for instances
+    /// created with [`Self::from_connection`], whose events are passed in by the application.
+    pub fn spawn_event_handler(self: &Arc<Self>) -> std::thread::JoinHandle<()> {
+        /// How often to check for new events.
+        const INTERVAL: Duration = Duration::from_millis(10);
+        let this = Arc::downgrade(self);
+        std::thread::spawn(move || {
+            while let Some(this) = this.upgrade() {
+                {
+                    let _guard = this.call_lock.enter();
+                    let _ = this.handle_events();
+                }
+                drop(this);
+                std::thread::sleep(INTERVAL);
+            }
+        })
+    }
+
+    /// Let us see an event received on a connection shared through [`Self::from_connection`].
+    /// Events we aren't interested in are ignored, so it's fine to pass every event.
+    pub fn notify_event(&self, event: &x11rb::protocol::Event) -> Result<()> {
+        let _guard = self.call_lock.enter();
+        self.handle_event(event)?;
+        Ok(())
+    }
+
+    /// Check that the X server is still there with a round trip. Unlike other methods, this
+    /// doesn't reconnect when the connection is lost, so the error can be seen.
+    pub fn ping(&self) -> Result<()> {
+        let _guard = self.call_lock.enter();
+        self.conn().get_input_focus()?.reply()?;
+        Ok(())
+    }
+
+    /// Wait until the server has processed everything sent so far, so e.g. a screenshot taken
+    /// afterwards shows the effect of the input.
+    pub fn sync(&self) -> Result<()> {
+        self.call("sync", || {
+            self.flush()?;
+            // Requests are processed in order, so the reply comes after all earlier events, and
+            // after errors for them.
+            self.conn().get_input_focus()?.reply()?;
+            self.handle_events()?;
+            lock(&self.pending_inputs).clear();
+            Ok(())
+        })
+    }
+
+    /// Returns true if the event was a request for the text of one of our selections, and it was
+    /// answered, or the end of a text handed out in chunks.
+    fn handle_event(&self, event: &x11rb::protocol::Event) -> Result<bool> {
+        use x11rb::protocol::Event;
+        match event {
+            _ if Self::is_keymap_change(event) => self.load_keymap()?,
+            Event::XkbStateNotify(ev) => {
+                lock(&self.mapping).layout = u8::from(ev.group).into();
+            }
+            Event::RandrScreenChangeNotify(ev) => {
+                use x11rb::protocol::randr::Rotation;
+                let conn = self.conn();
+                let Some(screen) = conn.setup().roots.iter().position(|s| s.root == ev.root) else {
+                    return Ok(false);
+                };
+                // The size is given before rotation.
+                let sideways = u16::from(Rotation::ROTATE90) | u16::from(Rotation::ROTATE270);
+                lock(&self.screen_sizes)[screen] = if u16::from(ev.rotation) & sideways != 0 {
+                    (ev.height, ev.width)
+                } else {
+                    (ev.width, ev.height)
+                };
+            }
+            Event::SelectionRequest(ev) => {
+                if let Some(selections) = lock(&self.selections).as_mut() {
+                    return selections.handle_request(&*self.conn(), ev);
+                }
+            }
+            Event::SelectionClear(ev) => {
+                if let Some(selections) = lock(&self.selections).as_mut() {
+                    selections.handle_clear(ev);
+                }
+            }
+            Event::Error(error) => {
+                let input = lock(&self.pending_inputs).take(error);
+                if let Some(input) = input {
+                    return Err(Error::FakeInputFailed {
+                        input,
+                        error: error.clone(),
+                    });
+                }
+            }
+            Event::PropertyNotify(ev) => {
+                if let Some(selections) = lock(&self.selections).as_mut() {
+                    return selections.handle_property_notify(&*self.conn(), ev);
+                }
+            }
+            _ => (),
+        }
+        Ok(false)
+    }
+
+    /// Inject on `screen` from now on. Fails with [`Error::InvalidScreen`] if the server has
+    /// no such screen. To use another screen for a single call, see [`Self::click_on_screen`]
+    /// and [`Self::move_cursor_on_screen`].
+    pub fn set_screen(&mut self, screen: usize) -> Result<()> {
+        self.screen_size_of(screen)?;
+        self.screen = screen;
+        Ok(())
+    }
+
+    /// How many screens the server has, e.g. 2 with `:0.0` and `:0.1`.
+    pub fn screen_count(&self) -> usize {
+        lock(&self.screen_sizes).len()
+    }
+
+    /// The screen events are injected on.
+    pub fn screen_index(&self) -> usize {
+        self.screen
+    }
+
+    /// Width and height of the screen in pixels, following resolution changes.
+    pub fn screen_size(&self) -> (u16, u16) {
+        lock(&self.screen_sizes)[self.screen]
+    }
+
+    /// Like [`Self::screen_size`], for any `screen`. Fails with [`Error::InvalidScreen`] if
+    /// the server has no such screen.
+    pub fn screen_size_of(&self, screen: usize) -> Result<(u16, u16)> {
+        lock(&self.screen_sizes)
+            .get(screen)
+            .copied()
+            .ok_or(Error::InvalidScreen(screen))
+    }
+
+    /// The root window of the screen.
+    pub fn root_window(&self) -> Result<xproto::Window> {
+        self.root()
+    }
+
+    /// A handle other threads can queue events with, for the thread owning this `InputSynth` to
+    /// send with [`Self::drain_sender`] or [`Self::run_sender_loop`]. All senders share one
+    /// queue.
+    pub fn make_sender(&self) -> InputSender {
+        let mut sender = lock(&self.sender);
+        let sender = sender.get_or_insert_with(|| {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            *lock(&self.receiver) = Some(receiver);
+            sender
+        });
+        InputSender::new(sender.clone())
+    }
+
+    /// Send the events queued by [`InputSender`]s so far, without waiting for more. Returns how
+    /// many were sent. The first event that fails stops this, the rest stay queued.
+    pub fn drain_sender(&self) -> Result<usize> {
+        let receiver = lock(&self.receiver);
+        let Some(receiver) = receiver.as_ref() else {
+            return Ok(0);
+        };
+        let mut sent = 0;
+        while let Ok(event) = receiver.try_recv() {
+            event.send(self)?;
+            sent += 1;
+        }
+        Ok(sent)
+    }
+
+    /// Send events queued by [`InputSender`]s as they arrive, until every sender is dropped or
+    /// an event fails. The queue is closed when this returns, so existing senders stop working
+    /// and [`Self::make_sender`] starts a new one.
+    pub fn run_sender_loop(&self) -> Result<()> {
+        // Without our own sender, the queue disconnects once the last `InputSender` is gone.
+        drop(lock(&self.sender).take());
+        let Some(receiver) = lock(&self.receiver).take() else {
+            return Ok(());
+        };
+        receiver.iter().try_for_each(|event| event.send(self))
+    }
+
+    /// How much input was synthesized since this was created, or since [`Self::reset_stats`].
+    pub fn stats(&self) -> InputStats {
+        self.stats.get()
+    }
+
+    /// Start counting [`Self::stats`] from zero.
+    pub fn reset_stats(&self) {
+        self.stats.reset();
+    }
+
+    /// The modifiers of the keyboard right now, e.g. to check that the user isn't holding Ctrl
+    /// before sending a shortcut.
+    pub fn modifier_state(&self) -> Result<ModifierState> {
+        self.call("modifier_state", || {
+            let device_spec = self
+                .keyboard_device
+                .map_or(x11rb::protocol::xkb::ID::USE_CORE_KBD.into(), u16::from);
+            let state = x11rb::protocol::xkb::get_state(&*self.conn(), device_spec)?.reply()?;
+            // Only the eight real modifiers are reported.
+            let mods = |mask| Modifiers::from_bits(u16::from(mask) as u8);
+            Ok(ModifierState {
+                depressed: mods(state.base_mods),
+                latched: mods(state.latched_mods),
+                locked: mods(state.locked_mods),
+            })
+        })
+    }
+
+    /// Whether the server considers key `keycode` to be held down, by the user or by us.
+    pub fn is_keycode_pressed(&self, keycode: u8) -> Result<bool> {
+        self.call("is_keycode_pressed", || {
+            let keys = self.conn().query_keymap()?.reply()?.keys;
+            Ok(keys[usize::from(keycode / 8)] & (1 << (keycode % 8)) != 0)
+        })
+    }
+
+    /// Whether a key producing `keysym` is held down. If several keys produce it, like the
+    /// digits of the main and the keypad, any of them counts.
+    pub fn is_key_pressed(&self, keysym: u32) -> Result<bool> {
+        self.call("is_key_pressed", || {
+            let keycodes = lock(&self.mapping).keycodes_for_keysym(keysym);
+            if keycodes.is_empty() {
+                return Err(Error::NoKeySequence { keysym });
+            }
+            let keys = self.conn().query_keymap()?.reply()?.keys;
+            Ok(keycodes.into_iter().any(|k| {
+                let k = k as usize;
+                keys.get(k / 8)
+                    .is_some_and(|byte| byte & (1 << (k % 8)) != 0)
+            }))
+        })
+    }
+
+    /// All input devices of the server, as listed by XInput.
+    pub fn list_devices(&self) -> Result<Vec<InputDevice>> {
+        self.call("list_devices", || {
+            let devices = x11rb::protocol::xinput::list_input_devices(&*self.conn())?.reply()?;
+            Ok(devices
+                .devices
+                .iter()
+                .zip(&devices.names)
+                .map(|(device, name)| InputDevice {
+                    id: device.device_id,
+                    name: String::from_utf8_lossy(&name.name).into_owned(),
+                    device_use: device.device_use.into(),
+                })
+                .collect())
+        })
+    }
+
+    /// The XInput id of the keyboard the keymap is taken from: the core keyboard, unless
+    /// another one was chosen with [`Self::set_keyboard_device`].
+    pub fn core_keyboard_device_id(&self) -> u8 {
+        lock(&self.mapping).device_id
+    }
+
+    /// The keyboard devices of the server, to choose one with [`Self::set_keyboard_device`].
+    pub fn keyboards(&self) -> Result<Vec<KeyboardInfo>> {
+        self.call("keyboards", || {
+            let conn = self.conn();
+            let core = core_keyboard_device_id(&*conn)?;
+            let devices = x11rb::protocol::xinput::list_input_devices(&*conn)?.reply()?;
+            Ok(devices
+                .devices
+                .iter()
+                .zip(&devices.names)
+                .filter(|(device, _)| is_keyboard_device(device))
+                .map(|(device, name)| KeyboardInfo {
+                    id: device.device_id,
+                    name: String::from_utf8_lossy(&name.name).into_owned(),
+                    is_core: core == Some(device.device_id.into()),
+                })
+                .collect())
+        })
+    }
+
+    /// Take the keymap from keyboard device `id` instead of the core keyboard, e.g. when the
+    /// core keyboard has a different layout than the keyboard the user types on. The keymap is
+    /// loaded right away. Fails with [`Error::NotAKeyboard`] if there is no such keyboard, see
+    /// [`Self::keyboards`], and with [`Error::Unsupported`] without the `xcb-ffi` feature.
+    pub fn set_keyboard_device(&mut self, id: u8) -> Result<()> {
+        self.call("set_keyboard_device", || {
+            let mut mapping = lock(&self.mapping);
+            *mapping = Self::get_keymap_state(&self.conn(), &mapping.context, Some(id))?;
+            Ok(())
+        })?;
+        self.keyboard_device = Some(id);
+        Ok(())
+    }
+
+    /// Multiply the coordinates given to [`Self::click`], [`Self::move_cursor`] and
+    /// [`Self::click_at`] by `scale`, rounding to the nearest pixel, e.g. 2 for positions
+    /// reported by a toolkit scaling its UI by 2. Methods working in pixels, like
+    /// [`Self::click_raw`] and those taking windows or fractions of the screen, aren't affected.
+    /// See [`Self::detect_coordinate_scale`] for the scale the desktop asks for. Fails with
+    /// [`Error::InvalidScale`] unless `scale` is positive.
+    pub fn set_coordinate_scale(&mut self, scale: f64) -> Result<()> {
+        Self::check_scale(scale)?;
+        self.coordinate_scale = scale;
+        Ok(())
+    }
+
+    pub fn coordinate_scale(&self) -> f64 {
+        self.coordinate_scale
+    }
+
+    fn check_scale(scale: f64) -> Result<()> {
+        if !(scale.is_finite() && scale > 0.0) {
+            return Err(Error::InvalidScale(scale));
+        }
+        Ok(())
+    }
+
+    /// The UI scale the desktop asks toolkits to use, from the `Xft.dpi` X resource relative to
+    /// 96 DPI. 1 if it isn't set. This is a guess: toolkits can be configured otherwise.
+    pub fn detect_coordinate_scale(&self) -> Result<f64> {
+        self.call("detect_coordinate_scale", || {
+            // Resources are always on the first screen.
+            let resources = self
+                .conn()
+                .get_property(
+                    false,
+                    self.screen_root(0)?,
+                    xproto::AtomEnum::RESOURCE_MANAGER,
+                    xproto::AtomEnum::STRING,
                     0,
+                    u32::MAX,
+                )?
+                .reply()?;
+            Ok(xft_dpi(&resources.value).map_or(1.0, |dpi| dpi / 96.0))
+        })
+    }
+
+    /// `(x, y)` multiplied by the coordinate scale.
+    fn scale_point(&self, x: i16, y: i16) -> (i16, i16) {
+        scale_point(x, y, self.coordinate_scale)
+    }
+
+    /// Choose whether to wait for the server to accept each event, see [`RequestChecking`].
+    pub fn set_request_checking(&mut self, checking: RequestChecking) {
+        self.request_checking = checking;
+    }
+
+    /// Limit how fast events are sent to the X server, see [`RateLimit`].
+    pub fn set_rate_limit(&mut self, limit: RateLimit) {
+        self.rate_limiter = Mutex::new(RateLimiter::new(limit));
+    }
+
+    /// The currently active rate limit.
+    pub fn rate_limit(&self) -> RateLimit {
+        lock(&self.rate_limiter).limit()
+    }
+
+    /// Randomize pointer paths, click durations and typing delays, see [`Humanize`]. `None`
+    /// turns humanization off, which is the default.
+    #[cfg(feature = "rand")]
+    pub fn set_humanize(&mut self, config: Option<Humanize>) {
+        self.humanizer = Mutex::new(config.map(Humanizer::new));
+    }
+
+    /// The currently active humanization settings, if any.
+    #[cfg(feature = "rand")]
+    pub fn humanize(&self) -> Option<Humanize> {
+        lock(&self.humanizer).as_ref().map(|h| h.config().clone())
+    }
+
+    /// The screen we inject on. Fails if the server no longer has it after reconnecting.
+    fn screen_info<'c>(&self, conn: &'c XConnection) -> Result<&'c xproto::Screen> {
+        conn.setup()
+            .roots
+            .get(self.screen)
+            .ok_or(Error::InvalidScreen(self.screen))
+    }
+
+    fn root(&self) -> Result<xproto::Window> {
+        Ok(self.screen_info(&self.conn())?.root)
+    }
+
+    /// The root window of any `screen`.
+    fn screen_root(&self, screen: usize) -> Result<xproto::Window> {
+        self.conn()
+            .setup()
+            .roots
+            .get(screen)
+            .map(|s| s.root)
+            .ok_or(Error::InvalidScreen(screen))
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.conn().flush()?;
+        lock(&self.rate_limiter).flushed();
+        Ok(())
+    }
+
+    /// Wait for the rate limit to allow sending another event.
+    fn throttle(&self) -> Result<()> {
+        let (flush, wait) = lock(&self.rate_limiter).next_event(std::time::Instant::now());
+        if flush {
+            self.conn().flush()?;
+        }
+        if wait > Duration::ZERO {
+            std::thread::sleep(wait);
+        }
+        Ok(())
+    }
+
+    /// Send one fake input event through XTEST. All injected events go through here so they are
+    /// subject to the rate limit and the [`RequestChecking`].
+    fn fake_input(&self, type_: u8, detail: u8, x: i16, y: i16) -> Result<()> {
+        self.fake_input_on(self.root()?, type_, detail, x, y)
+    }
+
+    /// Like [`Self::fake_input`], on the screen of `root`. Only matters for motion, which
+    /// moves the pointer to that screen.
+    fn fake_input_on(
+        &self,
+        root: xproto::Window,
+        type_: u8,
+        detail: u8,
+        x: i16,
+        y: i16,
+    ) -> Result<()> {
+        let conn = self.conn();
+        let cookie = self.send_fake_input(&conn, root, type_, detail, x, y)?;
+        let input = FakeInput {
+            method: *lock(&self.current_method),
+            event_type: type_,
+            detail,
+            x,
+            y,
+        };
+        match self.request_checking {
+            RequestChecking::Checked => cookie.check().map_err(|e| input.error(e)),
+            RequestChecking::Unchecked => {
+                // Errors arrive as events, see `handle_event`.
+                lock(&self.pending_inputs).push(cookie.sequence_number(), input);
+                Ok(())
+            }
+        }
+    }
+
+    fn send_fake_input<'c>(
+        &self,
+        conn: &'c XConnection,
+        root: xproto::Window,
+        type_: u8,
+        detail: u8,
+        x: i16,
+        y: i16,
+    ) -> Result<VoidCookie<'c, XConnection>> {
+        self.throttle()?;
+        trace_event!(event_type = type_, detail, x, y, "fake_input");
+        match type_ {
+            xproto::KEY_PRESS_EVENT => self.stats.key_pressed(),
+            xproto::BUTTON_PRESS_EVENT => self.stats.mouse_clicked(),
+            xproto::MOTION_NOTIFY_EVENT => self.stats.moved_to(x, y),
+            _ => (),
+        }
+        Ok(conn.xtest_fake_input(
+            type_,
+            detail,
+            x11rb::CURRENT_TIME,
+            root,
+            x,
+            y,
+            x11rb::NONE as _,
+        )?)
+    }
+
+    /// Generate a mouse click at `(x, y)`, with `button`. `press` indicates if the click is a
+    /// press, if it's false, a release will be generated. Fails with
+    /// [`Error::CoordinatesOutOfBounds`] if `(x, y)` is outside the screen.
+    ///
+    /// The coordinates are multiplied by the [coordinate scale](Self::set_coordinate_scale).
+    pub fn click(&self, x: i16, y: i16, button: u8, press: bool) -> Result<()> {
+        self.call("click", || {
+            let (x, y) = self.scale_point(x, y);
+            self.click_raw(x, y, button, press)
+        })
+    }
+
+    /// Like [`Self::click`], with `(x, y)` in pixels, ignoring the coordinate scale.
+    pub fn click_raw(&self, x: i16, y: i16, button: u8, press: bool) -> Result<()> {
+        self.call("click_raw", || {
+            self.check_bounds(self.screen, x, y)?;
+            self.check_not_grabbed(false)?;
+            self.fake_input(
+                if press {
+                    xproto::BUTTON_PRESS_EVENT
+                } else {
+                    xproto::BUTTON_RELEASE_EVENT
+                },
+                button,
+                x,
+                y,
+            )
+        })
+    }
+
+    /// Like [`Self::click`], at a position given as fractions of the screen size, e.g.
+    /// `(0.5, 0.5)` for the center. Fails with [`Error::InvalidFraction`] for fractions outside
+    /// of 0 to 1.
+    pub fn click_at_percent(&self, fx: f64, fy: f64, button: u8, press: bool) -> Result<()> {
+        self.call("click_at_percent", || {
+            let (x, y) = self.fraction_to_pixels(fx, fy)?;
+            self.click_raw(x, y, button, press)
+        })
+    }
+
+    /// Like [`Self::move_cursor`], at a position given as fractions of the screen size, see
+    /// [`Self::click_at_percent`].
+    pub fn move_cursor_percent(&self, fx: f64, fy: f64) -> Result<()> {
+        self.call("move_cursor_percent", || {
+            let (x, y) = self.fraction_to_pixels(fx, fy)?;
+            self.move_cursor_raw(x, y)
+        })
+    }
+
+    /// Move the cursor to the center of `window`, rounded down for odd sizes. Fails with
+    /// [`Error::WindowNotViewable`] if the window or one of its ancestors isn't mapped.
+    pub fn move_cursor_to_window_center(&self, window: xproto::Window) -> Result<()> {
+        self.call("move_cursor_to_window_center", || {
+            let (x, y) = self.window_center(window)?;
+            self.move_cursor_raw(x, y)
+        })
+    }
+
+    /// Like [`Self::move_cursor_to_window_center`], with `activate` bringing the window to the
+    /// front first with [`Self::activate_window`], so the pointer isn't over another window
+    /// covering it.
+    pub fn move_cursor_center_of_window(
+        &self,
+        window: xproto::Window,
+        activate: bool,
+    ) -> Result<()> {
+        self.call("move_cursor_center_of_window", || {
+            if activate {
+                self.activate_window(window)?;
+            }
+            self.move_cursor_to_window_center(window)
+        })
+    }
+
+    /// Move the cursor to the center of the screen, rounded down for odd sizes.
+    pub fn move_cursor_center(&self) -> Result<()> {
+        self.call("move_cursor_center", || {
+            let (width, height) = self.screen_size();
+            self.move_cursor_raw((width / 2) as i16, (height / 2) as i16)
+        })
+    }
+
+    /// Move the cursor to the center of `monitor`, from [`Self::randr_screen_layout`].
+    pub fn move_cursor_center_of_monitor(&self, monitor: &Monitor) -> Result<()> {
+        self.call("move_cursor_center_of_monitor", || {
+            let (x, y) = monitor.center();
+            self.move_cursor_raw(x, y)
+        })
+    }
+
+    /// Move the cursor to the center of `window`, and press, or release if `press` is false,
+    /// `button` there.
+    pub fn click_window_center(
+        &self,
+        window: xproto::Window,
+        button: u8,
+        press: bool,
+    ) -> Result<()> {
+        self.call("click_window_center", || {
+            let (x, y) = self.window_center(window)?;
+            self.move_cursor_raw(x, y)?;
+            self.click_raw(x, y, button, press)
+        })
+    }
+
+    /// Double click the left button in the center of `window`.
+    pub fn double_click_window_center(&self, window: xproto::Window) -> Result<()> {
+        self.call("double_click_window_center", || {
+            let (x, y) = self.window_center(window)?;
+            self.click_at_raw(x, y, 1)?;
+            self.click_at_raw(x, y, 1)
+        })
+    }
+
+    /// The center of `window`, in root window coordinates.
+    fn window_center(&self, window: xproto::Window) -> Result<(i16, i16)> {
+        let conn = self.conn();
+        let attributes = conn.get_window_attributes(window)?.reply()?;
+        if attributes.map_state != xproto::MapState::VIEWABLE {
+            return Err(Error::WindowNotViewable(window));
+        }
+        let geometry = conn.get_geometry(window)?.reply()?;
+        let center = conn
+            .translate_coordinates(
+                window,
+                geometry.root,
+                (geometry.width / 2) as i16,
+                (geometry.height / 2) as i16,
+            )?
+            .reply()?;
+        Ok((center.dst_x, center.dst_y))
+    }
+
+    /// The monitors showing the screen set with [`Self::set_screen`], in the order RandR lists
+    /// them. Their positions are in
+    /// screen coordinates, so e.g. with two 1920 pixels wide monitors side by side, the second
+    /// one starts at `(1920, 0)`. Fails with [`Error::Unsupported`] without RandR 1.5.
+    pub fn randr_screen_layout(&self) -> Result<Vec<Monitor>> {
+        self.call("randr_screen_layout", || {
+            monitor::monitors(&*self.conn(), self.root()?)
+        })
+    }
+
+    /// Like [`Self::click`], at `(x, y)` relative to the top-left corner of the monitor at
+    /// `monitor_index` in [`Self::randr_screen_layout`]. Fails with [`Error::InvalidMonitor`]
+    /// if there is no such monitor, and with [`Error::CoordinatesOutOfBounds`] if `(x, y)` is
+    /// outside of it.
+    ///
+    /// `(x, y)` is in pixels, the coordinate scale doesn't apply: monitors can have different
+    /// densities, see [`Monitor::scale`].
+    pub fn click_on_monitor(
+        &self,
+        monitor_index: usize,
+        x: i16,
+        y: i16,
+        button: u8,
+        press: bool,
+    ) -> Result<()> {
+        self.call("click_on_monitor", || {
+            let monitors = self.randr_screen_layout()?;
+            let monitor = monitors
+                .get(monitor_index)
+                .ok_or(Error::InvalidMonitor(monitor_index))?;
+            let (screen_x, screen_y) =
+                monitor
+                    .to_screen(x, y)
+                    .ok_or(Error::CoordinatesOutOfBounds {
+                        x,
+                        y,
+                        width: monitor.width,
+                        height: monitor.height,
+                    })?;
+            self.click_raw(screen_x, screen_y, button, press)
+        })
+    }
+
+    /// The pixel at fractions `fx` and `fy` of the screen size, 1 being the last pixel.
+    fn fraction_to_pixels(&self, fx: f64, fy: f64) -> Result<(i16, i16)> {
+        let (width, height) = self.screen_size();
+        let pixel = |fraction: f64, size: u16| {
+            if !(0.0..=1.0).contains(&fraction) {
+                return Err(Error::InvalidFraction(fraction));
+            }
+            Ok((fraction * f64::from(size.saturating_sub(1))).round() as i16)
+        };
+        Ok((pixel(fx, width)?, pixel(fy, height)?))
+    }
+
+    /// Move the cursor to `(x, y)`. Fails with [`Error::CoordinatesOutOfBounds`] if that is
+    /// outside the screen.
+    ///
+    /// The coordinates are multiplied by the [coordinate scale](Self::set_coordinate_scale).
+    pub fn move_cursor(&self, x: i16, y: i16) -> Result<()> {
+        self.call("move_cursor", || {
+            let (x, y) = self.scale_point(x, y);
+            self.move_cursor_raw(x, y)
+        })
+    }
+
+    /// Like [`Self::move_cursor`], with `(x, y)` in pixels, ignoring the coordinate scale.
+    pub fn move_cursor_raw(&self, x: i16, y: i16) -> Result<()> {
+        self.call("move_cursor_raw", || {
+            self.check_bounds(self.screen, x, y)?;
+            self.fake_input(xproto::MOTION_NOTIFY_EVENT, 0, x, y)
+        })
+    }
+
+    /// Like [`Self::move_cursor`], on `screen` instead of the one set with
+    /// [`Self::set_screen`]. The pointer stays on that screen until it's moved again. Fails with
+    /// [`Error::InvalidScreen`] if the server has no such screen.
+    pub fn move_cursor_on_screen(&self, screen: usize, x: i16, y: i16) -> Result<()> {
+        self.call("move_cursor_on_screen", || {
+            self.check_bounds(screen, x, y)?;
+            self.fake_input_on(
+                self.screen_root(screen)?,
+                xproto::MOTION_NOTIFY_EVENT,
+                0,
+                x,
+                y,
+            )
+        })
+    }
+
+    /// Like [`Self::click`], on `screen` instead of the one set with [`Self::set_screen`]. The
+    /// pointer is moved to `(x, y)` on that screen first.
+    pub fn click_on_screen(
+        &self,
+        screen: usize,
+        x: i16,
+        y: i16,
+        button: u8,
+        press: bool,
+    ) -> Result<()> {
+        self.call("click_on_screen", || {
+            self.move_cursor_on_screen(screen, x, y)?;
+            self.check_not_grabbed(false)?;
+            self.fake_input_on(
+                self.screen_root(screen)?,
+                if press {
+                    xproto::BUTTON_PRESS_EVENT
+                } else {
+                    xproto::BUTTON_RELEASE_EVENT
+                },
+                button,
+                x,
+                y,
+            )
+        })
+    }
+
+    /// Fail with [`Error::CoordinatesOutOfBounds`] if `(x, y)` is outside `screen`.
+    fn check_bounds(&self, screen: usize, x: i16, y: i16) -> Result<()> {
+        let (width, height) = self.screen_size_of(screen)?;
+        if x < 0 || y < 0 || x as u16 >= width || y as u16 >= height {
+            return Err(Error::CoordinatesOutOfBounds {
+                x,
+                y,
+                width,
+                height,
+            });
+        }
+        Ok(())
+    }
+
+    fn with_selections<R>(&self, f: impl FnOnce(&mut Selections) -> Result<R>) -> Result<R> {
+        let mut selections = lock(&self.selections);
+        let selections = match &mut *selections {
+            Some(selections) => selections,
+            empty => empty.insert(Selections::new(&*self.conn(), self.root()?)?),
+        };
+        f(selections)
+    }
+
+    fn set_selection(
+        &self,
+        selection: impl FnOnce(&Selections) -> xproto::Atom,
+        text: &str,
+    ) -> Result<()> {
+        self.with_selections(|selections| {
+            let atom = selection(selections);
+            selections.set(&*self.conn(), atom, text)
+        })
+    }
+
+    /// Read the text currently on the clipboard, e.g. after copying something with Ctrl+C.
+    /// Returns an empty string if the clipboard is empty or its contents aren't text. Fails with
+    /// [`Error::Timeout`] if the clipboard owner doesn't answer within the selection timeout.
+    pub fn get_clipboard(&self) -> Result<String> {
+        self.call("get_clipboard", || {
+            let clipboard = self.with_selections(|selections| {
+                selections.request(&*self.conn(), selections.clipboard)?;
+                Ok(selections.clipboard)
+            })?;
+            let deadline = std::time::Instant::now() + self.selection_timeout;
+            while std::time::Instant::now() < deadline {
+                use x11rb::protocol::Event;
+                match self.conn().poll_for_event()? {
+                    Some(Event::SelectionNotify(ev))
+                        if self.with_selections(|s| Ok(s.is_answer(&ev, clipboard)))? =>
+                    {
+                        return match self
+                            .with_selections(|s| s.read_transfer(&*self.conn(), &ev))?
+                        {
+                            Transfer::Complete(text) => Ok(text),
+                            Transfer::Incremental => self.read_incremental(),
+                        };
+                    }
+                    Some(event) => {
+                        self.handle_event(&event)?;
+                    }
+                    None => std::thread::sleep(Duration::from_millis(5)),
+                }
+            }
+            Err(Error::Timeout(self.selection_timeout))
+        })
+    }
+
+    /// Collect the chunks of an INCR transfer of a selection to us. Each chunk has to arrive
+    /// within the selection timeout, the whole text can take longer.
+    fn read_incremental(&self) -> Result<String> {
+        use x11rb::protocol::Event;
+        let (window, property) = self.with_selections(|s| Ok(s.transfer_property()))?;
+        let mut data = Vec::new();
+        loop {
+            self.wait_for_event(self.selection_timeout, |event| match event {
+                Event::PropertyNotify(ev)
+                    if ev.window == window
+                        && ev.atom == property
+                        && ev.state == xproto::Property::NEW_VALUE =>
+                {
+                    Some(())
+                }
+                _ => None,
+            })?;
+            let chunk = self.with_selections(|s| s.read_chunk(&*self.conn()))?;
+            if chunk.is_empty() {
+                return Ok(String::from_utf8_lossy(&data).into_owned());
+            }
+            data.extend_from_slice(&chunk);
+        }
+    }
+
+    /// How long [`Self::get_clipboard`] waits for the clipboard owner to answer.
+    pub fn set_selection_timeout(&mut self, timeout: Duration) {
+        self.selection_timeout = timeout;
+    }
+
+    /// Wait for an event that `filter` accepts, handling everything else as usual. Fails with
+    /// [`Error::Timeout`] if none arrives within `timeout`.
+    fn wait_for_event<T>(
+        &self,
+        timeout: Duration,
+        mut filter: impl FnMut(&x11rb::protocol::Event) -> Option<T>,
+    ) -> Result<T> {
+        let deadline = std::time::Instant::now() + timeout;
+        while std::time::Instant::now() < deadline {
+            match self.conn().poll_for_event()? {
+                Some(event) => {
+                    if let Some(value) = filter(&event) {
+                        return Ok(value);
+                    }
+                    self.handle_event(&event)?;
+                }
+                None => std::thread::sleep(Duration::from_millis(5)),
+            }
+        }
+        Err(Error::Timeout(timeout))
+    }
+
+    /// Wait for the next key press or release to arrive on our connection, e.g. to check in a
+    /// test that typed keys were delivered. The server only sends us key events for windows we
+    /// selected them on.
+    pub fn wait_for_key_event(&self, timeout: Duration) -> Result<KeyEvent> {
+        use x11rb::protocol::Event;
+        self.call("wait_for_key_event", || {
+            self.wait_for_event(timeout, |event| match event {
+                Event::KeyPress(ev) | Event::KeyRelease(ev) => Some(KeyEvent {
+                    keycode: ev.detail.into(),
+                    pressed: ev.response_type & 0x7f == xproto::KEY_PRESS_EVENT,
+                    time: ev.time,
+                }),
+                _ => None,
+            })
+        })
+    }
+
+    /// Like [`Self::wait_for_key_event`], for pointer button presses and releases.
+    pub fn wait_for_button_event(&self, timeout: Duration) -> Result<ButtonEvent> {
+        use x11rb::protocol::Event;
+        self.call("wait_for_button_event", || {
+            self.wait_for_event(timeout, |event| match event {
+                Event::ButtonPress(ev) | Event::ButtonRelease(ev) => Some(ButtonEvent {
+                    button: ev.detail,
+                    pressed: ev.response_type & 0x7f == xproto::BUTTON_PRESS_EVENT,
+                    time: ev.time,
+                }),
+                _ => None,
+            })
+        })
+    }
+
+    /// Put `text` on the clipboard and paste it into the focused window with Ctrl+V.
+    ///
+    /// We keep serving the clipboard for as long as this `InputSynth` is alive, but only while
+    /// one of its methods is being called. This method waits a short while for the paste to be
+    /// requested before returning, and fails with [`Error::Timeout`] if it isn't. The text stays
+    /// on the clipboard in that case.
+    pub fn set_clipboard(&self, text: &str) -> Result<()> {
+        /// How long to wait for the focused window to ask for the clipboard contents.
+        const PASTE_TIMEOUT: Duration = Duration::from_millis(500);
+        self.call("set_clipboard", || {
+            self.set_selection(|s| s.clipboard, text)?;
+            self.send_shortcut_keysyms(&[CONTROL_L_KEYSYM], b'v' as _)?;
+            self.wait_for_paste(PASTE_TIMEOUT)
+        })
+    }
+
+    /// Enter `text` into the focused window by pasting it, which is much faster than typing
+    /// long texts. Like [`Self::paste_text_with`], with Ctrl+V, restoring the clipboard.
+    pub fn paste_text(&self, text: &str) -> Result<()> {
+        let ctrl_v = KeyCombo::new(vec![CONTROL_L_KEYSYM, b'v'.into()]);
+        self.paste_text_with(text, &ctrl_v, true)
+    }
+
+    /// Put `text` on the clipboard, press `combo` to paste it, e.g. Ctrl+Shift+V for terminals,
+    /// and wait for the focused window to read it, up to the selection timeout. Large texts are
+    /// handed out in chunks, as long as the window keeps reading. Fails with [`Error::Timeout`]
+    /// if nothing asks for the text in time.
+    ///
+    /// With `restore`, the text on the clipboard before is read first and put back afterwards,
+    /// also when pasting fails. Only its text survives, and we become the owner of the
+    /// clipboard, serving it while one of our methods is being called. An empty clipboard, or
+    /// one that couldn't be read, isn't restored.
+    pub fn paste_text_with(&self, text: &str, combo: &KeyCombo, restore: bool) -> Result<()> {
+        self.call("paste_text", || {
+            self.check_not_grabbed(true)?;
+            // Better to leave our text on the clipboard than to restore something wrong.
+            let previous = if restore {
+                self.get_clipboard().ok()
+            } else {
+                None
+            };
+            self.set_selection(|s| s.clipboard, text)?;
+            let result = self
+                .key_combo(combo)
+                .and_then(|()| self.wait_for_paste(self.selection_timeout));
+            match previous {
+                Some(previous) if !previous.is_empty() => {
+                    self.set_selection(|s| s.clipboard, &previous)?;
+                }
+                _ => (),
+            }
+            result
+        })
+    }
+
+    /// Handle events until one of our selections was read. Fails with [`Error::Timeout`] if
+    /// `timeout` passes without any event.
+    fn wait_for_paste(&self, timeout: Duration) -> Result<()> {
+        let mut deadline = std::time::Instant::now() + timeout;
+        while std::time::Instant::now() < deadline {
+            match self.conn().poll_for_event()? {
+                Some(event) => {
+                    if self.handle_event(&event)? {
+                        return Ok(());
+                    }
+                    // Reading a large text takes a while, but keeps us busy.
+                    deadline = std::time::Instant::now() + timeout;
+                }
+                None => std::thread::sleep(Duration::from_millis(5)),
+            }
+        }
+        Err(Error::Timeout(timeout))
+    }
+
+    /// Make `text` the PRIMARY selection, which is what middle click pastes.
+    pub fn set_primary_selection(&self, text: &str) -> Result<()> {
+        self.call("set_primary_selection", || {
+            self.set_selection(|_| xproto::AtomEnum::PRIMARY.into(), text)
+        })
+    }
+
+    /// Press and release `key` while holding `mods`, e.g. Ctrl+Alt+T. The modifier keys are
+    /// found in the keymap by name, pressed in the order of their bits, and released in reverse
+    /// order, also when pressing the key fails. Fails with [`Error::NoKeyForModifier`] if the
+    /// keymap has no key for one of the modifiers.
+    ///
+    /// `mods` can also be real [`Modifiers`], which are converted as described in
+    /// [`ShortcutModifiers`].
+    pub fn send_shortcut(&self, mods: impl Into<ShortcutModifiers>, key: Key) -> Result<()> {
+        let mods = mods.into();
+        self.call("send_shortcut", || {
+            self.check_not_grabbed(true)?;
+            let mut keycodes = Vec::new();
+            {
+                let mapping = lock(&self.mapping);
+                for m in mods.iter() {
+                    let keycode = m
+                        .xkb_names()
+                        .iter()
+                        .find_map(|name| mapping.modifier_keycode(name))
+                        .ok_or(Error::NoKeyForModifier(m))?;
+                    // Alt and Meta are often the same key.
+                    if !keycodes.contains(&keycode) {
+                        keycodes.push(keycode);
+                    }
+                }
+            }
+            let (mut key_mods, keycode) =
+                self.find_key_sequence(key).ok_or(Error::NoKeySequence {
+                    keysym: key.keysym(),
+                })?;
+            key_mods.retain(|m| !keycodes.contains(m));
+            let mut pressed = 0;
+            let result = keycodes
+                .iter()
+                .try_for_each(|&k| {
+                    self.fake_input(xproto::KEY_PRESS_EVENT, k as _, 0, 0)?;
+                    pressed += 1;
+                    Ok(())
+                })
+                .and_then(|()| {
+                    for &m in &key_mods {
+                        self.fake_input(xproto::KEY_PRESS_EVENT, m as _, 0, 0)?;
+                    }
+                    self.fake_input(xproto::KEY_PRESS_EVENT, keycode as _, 0, 0)?;
+                    self.fake_input(xproto::KEY_RELEASE_EVENT, keycode as _, 0, 0)?;
+                    for &m in key_mods.iter().rev() {
+                        self.fake_input(xproto::KEY_RELEASE_EVENT, m as _, 0, 0)?;
+                    }
+                    Ok(())
+                });
+            let released = keycodes[..pressed]
+                .iter()
+                .rev()
+                .try_for_each(|&k| self.fake_input(xproto::KEY_RELEASE_EVENT, k as _, 0, 0));
+            let flushed = self.flush();
+            result.and(released).and(flushed)
+        })
+    }
+
+    /// Press `key` while holding down the keys for each of the `held` keysyms.
+    fn send_shortcut_keysyms(&self, held: &[u32], key: u32) -> Result<()> {
+        let mut held_keycodes = Vec::with_capacity(held.len());
+        for &sym in held {
+            let (_, keycode) = self
+                .find_key_sequence(Key::new(sym))
+                .ok_or(Error::NoKeySequence { keysym: sym })?;
+            held_keycodes.push(keycode);
+        }
+        let (mods, keycode) = self
+            .find_key_sequence(Key::new(key))
+            .ok_or(Error::NoKeySequence { keysym: key })?;
+        for &k in &held_keycodes {
+            self.fake_input(xproto::KEY_PRESS_EVENT, k as _, 0, 0)?;
+        }
+        self.send_key_sequence(key, &mods, keycode)?;
+        for &k in held_keycodes.iter().rev() {
+            self.fake_input(xproto::KEY_RELEASE_EVENT, k as _, 0, 0)?;
+        }
+        self.flush()
+    }
+
+    /// Move the cursor to `(x, y)` and click `button` there. With humanization enabled, the
+    /// button is held down for a random duration.
+    ///
+    /// The coordinates are multiplied by the [coordinate scale](Self::set_coordinate_scale).
+    pub fn click_at(&self, x: i16, y: i16, button: u8) -> Result<()> {
+        self.call("click_at", || {
+            let (x, y) = self.scale_point(x, y);
+            self.click_at_raw(x, y, button)
+        })
+    }
+
+    /// Like [`Self::click_at`], with `(x, y)` in pixels, ignoring the coordinate scale.
+    pub fn click_at_raw(&self, x: i16, y: i16, button: u8) -> Result<()> {
+        self.call("click_at_raw", || {
+            self.move_cursor_raw(x, y)?;
+            self.click_raw(x, y, button, true)?;
+            let hold = self.click_duration();
+            if hold > Duration::ZERO {
+                std::thread::sleep(hold);
+            }
+            self.click_raw(x, y, button, false)
+        })
+    }
+
+    fn click_duration(&self) -> Duration {
+        #[cfg(feature = "rand")]
+        if let Some(humanizer) = lock(&self.humanizer).as_mut() {
+            return humanizer.click_duration();
+        }
+        Duration::ZERO
+    }
+
+    /// The current cursor position on the root window.
+    fn pointer_position(&self) -> Result<(i16, i16)> {
+        let reply = self.conn().query_pointer(self.root()?)?.reply()?;
+        Ok((reply.root_x, reply.root_y))
+    }
+
+    /// Move the cursor from where it is to `(x, y)`, spreading the motion over `duration` instead
+    /// of jumping there directly.
+    pub fn move_cursor_smooth(&self, x: i16, y: i16, duration: Duration) -> Result<()> {
+        /// Time between two intermediate motion events.
+        const STEP: Duration = Duration::from_millis(10);
+        self.call("move_cursor_smooth", || {
+            self.check_bounds(self.screen, x, y)?;
+            let from = self.pointer_position()?;
+            let steps = (duration.as_nanos() / STEP.as_nanos()).max(1) as usize;
+            let (path, delays) = self.cursor_path(from, (x, y), duration, steps);
+            for ((px, py), delay) in path.into_iter().zip(delays) {
+                std::thread::sleep(delay);
+                self.fake_input(xproto::MOTION_NOTIFY_EVENT, 0, px, py)?;
+                self.flush()?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Points along the way from `from` to `to`, ending at `to`, and how long to wait before
+    /// moving to each of them.
+    fn cursor_path(
+        &self,
+        from: (i16, i16),
+        to: (i16, i16),
+        duration: Duration,
+        steps: usize,
+    ) -> (Vec<(i16, i16)>, Vec<Duration>) {
+        let from = (from.0 as f64, from.1 as f64);
+        let to = (to.0 as f64, to.1 as f64);
+        #[allow(unused_mut)]
+        let mut path: Vec<_> = (1..=steps)
+            .map(|i| {
+                let t = i as f64 / steps as f64;
+                (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t)
+            })
+            .collect();
+        #[allow(unused_mut)]
+        let mut delays = vec![duration / steps as u32; steps];
+        #[cfg(feature = "rand")]
+        if let Some(humanizer) = lock(&self.humanizer).as_mut() {
+            humanizer.perturb_path(from, to, &mut path);
+            delays = humanizer.step_delays(duration, steps);
+        }
+        let path = path
+            .into_iter()
+            .map(|(x, y)| (x.round() as i16, y.round() as i16))
+            .collect();
+        (path, delays)
+    }
+
+    fn touch_event(
+        &self,
+        position: Option<(f64, f64)>,
+        action: impl Fn(&mut Touches) -> Result<TouchAction>,
+    ) -> Result<()> {
+        self.call("touch_event", || {
+            self.check_not_grabbed(false)?;
+            let action = action(&mut lock(&self.touches))?;
+            if let Some((x, y)) = position {
+                self.fake_input(
+                    xproto::MOTION_NOTIFY_EVENT,
                     0,
-                    x11rb::NONE as _,
+                    x.round() as _,
+                    y.round() as _,
                 )?;
             }
-            self.connection.xtest_fake_input(
-                xproto::KEY_PRESS_EVENT,
-                keycode as _,
-                x11rb::CURRENT_TIME,
-                self.connection.setup().roots[self.screen].root,
-                0,
-                0,
-                x11rb::NONE as _,
-            )?;
-            self.connection.xtest_fake_input(
-                xproto::KEY_RELEASE_EVENT,
-                keycode as _,
-                x11rb::CURRENT_TIME,
-                self.connection.setup().roots[self.screen].root,
-                0,
-                0,
-                x11rb::NONE as _,
-            )?;
-            for &m in mods.iter().rev() {
-                self.connection.xtest_fake_input(
-                    xproto::KEY_RELEASE_EVENT,
-                    m as _,
-                    x11rb::CURRENT_TIME,
-                    self.connection.setup().roots[self.screen].root,
-                    0,
+            match action {
+                TouchAction::Press => {
+                    self.fake_input(xproto::BUTTON_PRESS_EVENT, 1, 0, 0)?;
+                }
+                TouchAction::Release => {
+                    self.fake_input(xproto::BUTTON_RELEASE_EVENT, 1, 0, 0)?;
+                }
+                TouchAction::Motion => (),
+            }
+            self.flush()
+        })
+    }
+
+    /// Emulate putting a finger down at `(x, y)`, the way the server emulates a pointer for the
+    /// first touch of a touchscreen: the pointer moves there and button 1 is pressed. `id`
+    /// identifies the touch in later calls to [`Self::emulated_touch_update`] and
+    /// [`Self::emulated_touch_end`].
+    ///
+    /// No actual touch events are sent, XTEST can't inject them and XInput 2 clients can't create
+    /// touch devices. Clients listening for `XI_TouchBegin` and friends only see the emulated
+    /// pointer, and only one touch can be active at a time. For real multi-touch, use a
+    /// `UinputTouchscreen`, which needs the `uinput` feature.
+    pub fn emulated_touch_begin(&self, id: u32, x: f64, y: f64) -> Result<()> {
+        self.touch_event(Some((x, y)), |t| t.begin(id))
+    }
+
+    /// Move the emulated finger of touch `id` to `(x, y)`.
+    pub fn emulated_touch_update(&self, id: u32, x: f64, y: f64) -> Result<()> {
+        self.touch_event(Some((x, y)), |t| t.update(id))
+    }
+
+    /// Lift the emulated finger of touch `id`.
+    pub fn emulated_touch_end(&self, id: u32) -> Result<()> {
+        self.touch_event(None, |t| t.end(id))
+    }
+
+    /// Move a tablet stylus to `(x, y)` with the given pen `pressure`, clamped to `[0, 1]`, and
+    /// tilt, clamped to `[-1, 1]`. These are mapped onto the valuator ranges of the tablet. If
+    /// `proximity` is false, the pen is lifted out of the tablet's range instead and the other
+    /// arguments are ignored.
+    ///
+    /// The events are sent as if they came from a stylus device already known to the server,
+    /// so this fails with [`Error::Unsupported`] if there is no such device.
+    pub fn stylus_event(
+        &self,
+        x: f64,
+        y: f64,
+        pressure: f64,
+        tilt_x: f64,
+        tilt_y: f64,
+        proximity: bool,
+    ) -> Result<()> {
+        self.call("stylus_event", || {
+            let xi = self
+                .conn()
+                .extension_information(x11rb::protocol::xinput::X11_EXTENSION_NAME)?
+                .ok_or(Error::Unsupported("XInput"))?;
+            let mut stylus = lock(&self.stylus);
+            if stylus.is_none() {
+                *stylus = Some(Stylus::find(&*self.conn())?);
+            }
+            let stylus = stylus
+                .as_mut()
+                .and_then(Option::as_mut)
+                .ok_or(Error::Unsupported("stylus input without a tablet device"))?;
+            let events = if proximity {
+                let (width, height) = self.screen_size();
+                let position = (x / f64::from(width), y / f64::from(height));
+                stylus.motion(xi.first_event, position, pressure, (tilt_x, tilt_y))
+            } else {
+                stylus.leave(xi.first_event).into_iter().collect()
+            };
+            if !events.is_empty() {
+                self.throttle()?;
+                xi1::fake_input(&*self.conn(), &events)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Send `event` as if it came from the XInput device `device_id`, e.g. one of several
+    /// keyboards, instead of from the XTEST devices. Keys press the modifiers they need on the
+    /// same device. Button events move the core pointer to `(x, y)` first, motion sets the
+    /// first two valuators of the device, which are its position for absolute devices.
+    ///
+    /// Without the XInput extension, events can't name a device, and this sends `event` like
+    /// [`InputEvent`]s are usually sent, from the XTEST devices.
+    pub fn synthesize_to_device(&self, device_id: u8, event: InputEvent) -> Result<()> {
+        self.call("synthesize_to_device", || {
+            let conn = self.conn();
+            let Some(xi) =
+                conn.extension_information(x11rb::protocol::xinput::X11_EXTENSION_NAME)?
+            else {
+                return event.send(self);
+            };
+            let key_event = |type_, keycode: u32| {
+                xi1::device_event(xi.first_event + type_, device_id, keycode as u8)
+            };
+            let events = match event {
+                InputEvent::KeyPress { keysym } | InputEvent::KeyRelease { keysym } => {
+                    self.check_not_grabbed(true)?;
+                    let (mods, keycode) = self
+                        .find_key_sequence(Key::new(keysym))
+                        .ok_or(Error::NoKeySequence { keysym })?;
+                    if matches!(event, InputEvent::KeyPress { .. }) {
+                        mods.iter()
+                            .chain([&keycode])
+                            .map(|&k| vec![key_event(xi1::DEVICE_KEY_PRESS, k)])
+                            .collect()
+                    } else {
+                        [keycode]
+                            .iter()
+                            .chain(mods.iter().rev())
+                            .map(|&k| vec![key_event(xi1::DEVICE_KEY_RELEASE, k)])
+                            .collect()
+                    }
+                }
+                InputEvent::ButtonPress { x, y, button }
+                | InputEvent::ButtonRelease { x, y, button } => {
+                    self.move_cursor(x, y)?;
+                    self.check_not_grabbed(false)?;
+                    let type_ = if matches!(event, InputEvent::ButtonPress { .. }) {
+                        xi1::DEVICE_BUTTON_PRESS
+                    } else {
+                        xi1::DEVICE_BUTTON_RELEASE
+                    };
+                    vec![vec![xi1::device_event(
+                        xi.first_event + type_,
+                        device_id,
+                        button,
+                    )]]
+                }
+                InputEvent::Motion { x, y } => vec![xi1::motion_events(
+                    xi.first_event,
+                    device_id,
                     0,
-                    x11rb::NONE as _,
+                    &[x.into(), y.into()],
+                )],
+                InputEvent::Delay(_) => return event.send(self),
+            };
+            // Each request carries one event, with its valuators.
+            for events in events {
+                self.throttle()?;
+                xi1::fake_input(&*conn, &events)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn with_scroll_device<R>(&self, f: impl FnOnce(Option<&mut ScrollDevice>) -> R) -> Result<R> {
+        let mut device = lock(&self.scroll_device);
+        if device.is_none() {
+            *device = Some(ScrollDevice::find(&*self.conn())?);
+        }
+        Ok(f(device.as_mut().and_then(Option::as_mut)))
+    }
+
+    /// Whether [`Self::smooth_scroll`] can send smooth scroll events, instead of falling back to
+    /// scroll wheel buttons.
+    pub fn has_smooth_scroll(&self) -> bool {
+        self.with_scroll_device(|device| device.is_some())
+            .unwrap_or(false)
+    }
+
+    /// Scroll by `dx` and `dy` with the pointer at `(x, y)`. The unit is one step of a scroll
+    /// wheel, positive values scroll right and down.
+    ///
+    /// XInput 2 clients, like most browsers, receive this as smooth scrolling, as long as the
+    /// server has a pointer device with scroll valuators for the events to come from. Otherwise,
+    /// or for the axis the device lacks, the scroll is rounded to whole steps and sent as clicks
+    /// of buttons 4 to 7.
+    pub fn smooth_scroll(&self, x: i16, y: i16, dx: f64, dy: f64) -> Result<()> {
+        self.call("smooth_scroll", || {
+            self.check_not_grabbed(false)?;
+            self.fake_input(xproto::MOTION_NOTIFY_EVENT, 0, x, y)?;
+            let xi_first_event = self
+                .conn()
+                .extension_information(x11rb::protocol::xinput::X11_EXTENSION_NAME)?
+                .map(|xi| xi.first_event);
+            let (events, (dx, dy)) = match xi_first_event {
+                Some(first_event) => self.with_scroll_device(|device| match device {
+                    Some(device) => device.scroll(first_event, dx, dy),
+                    None => (Vec::new(), (dx, dy)),
+                })?,
+                None => (Vec::new(), (dx, dy)),
+            };
+            if !events.is_empty() {
+                self.throttle()?;
+                xi1::fake_input(&*self.conn(), &events)?;
+            }
+            for (button, clicks) in scroll::scroll_buttons(dx, dy) {
+                for _ in 0..clicks {
+                    self.fake_input(xproto::BUTTON_PRESS_EVENT, button, 0, 0)?;
+                    self.fake_input(xproto::BUTTON_RELEASE_EVENT, button, 0, 0)?;
+                }
+            }
+            self.flush()
+        })
+    }
+
+    /// Scroll vertically by `dy` wheel steps at `(x, y)` like [`Self::smooth_scroll`], but in
+    /// up to `steps` increments with `delay` between them, so applications see an animated
+    /// scroll. Each increment is at least one unit of the scroll device, or one wheel step when
+    /// falling back to buttons, so there can be fewer increments than `steps`.
+    pub fn scroll_smooth(
+        &self,
+        x: i16,
+        y: i16,
+        dy: f64,
+        steps: u32,
+        delay: Duration,
+    ) -> Result<()> {
+        self.call("scroll_smooth", || {
+            let resolution = self
+                .with_scroll_device(|device| device.and_then(|d| d.vertical_resolution()))?
+                .unwrap_or(1.0);
+            let mut position = 0.0;
+            for (i, next) in scroll::scroll_path(dy, steps, resolution)
+                .into_iter()
+                .enumerate()
+            {
+                if i != 0 {
+                    std::thread::sleep(delay);
+                }
+                self.smooth_scroll(x, y, 0.0, next - position)?;
+                position = next;
+            }
+            Ok(())
+        })
+    }
+
+    pub(crate) fn find_key_sequence(&self, key: Key) -> Option<(Vec<u32>, u32)> {
+        let (mut mods, keycode) = lock(&self.mapping).find_key_sequence(key)?;
+        // Modifiers held by a guard have to stay down until it's gone.
+        let held = lock(&self.held_keys);
+        mods.retain(|&m| !held.contains(m));
+        Some((mods, keycode))
+    }
+
+    /// The keysym that `keycode` produces while the modifiers in `mods` are held, the reverse of
+    /// what typing does. `mods` is a mask of modifier indices, as in the `state` of key events.
+    /// `None` if the key produces no keysym, or more than one.
+    pub fn keycode_to_keysym(&self, keycode: u32, mods: u32) -> Option<u32> {
+        lock(&self.mapping).keycode_to_keysym(keycode, mods)
+    }
+
+    /// The names of the modifiers in the current keymap, like `"Shift"` or `"Mod1"`, mapped to
+    /// their indices. Bit `1 << index` stands for the modifier in modifier masks.
+    pub fn modifier_key_names(&self) -> HashMap<String, u8> {
+        lock(&self.mapping).modifier_names()
+    }
+
+    /// The keycode of a key that activates the modifier named `name`, see
+    /// [`Self::modifier_key_names`]. `None` if there is no such modifier, or no key for it.
+    pub fn modifier_name_to_keycode(&self, name: &str) -> Option<u32> {
+        lock(&self.mapping).modifier_keycode(name)
+    }
+
+    /// Run `f` on a copy of the current keymap, for introspection with the `xkbcommon` API.
+    /// `None` if the keymap couldn't be copied.
+    ///
+    /// The copy lives in its own XKB context: libxkbcommon's reference counting isn't thread
+    /// safe, so the keymap we use ourselves can't be handed out, not even by reference. Since
+    /// copying compiles the keymap again, get what you need in one call.
+    pub fn with_keymap<R>(&self, f: impl FnOnce(&xkbcommon::xkb::Keymap) -> R) -> Option<R> {
+        let copy = lock(&self.mapping).duplicate()?;
+        Some(f(&copy.mapping))
+    }
+
+    /// Set how long to pause between characters in the `type_*` methods.
+    pub fn set_typing_delay(&mut self, delay: TypingDelay) {
+        self.typing_delay = delay;
+    }
+
+    /// Which key the `type_*` methods press for a line break, see [`NewlinePolicy`].
+    pub fn set_newline_policy(&mut self, policy: NewlinePolicy) {
+        self.newline_policy = policy;
+    }
+
+    fn typing_pause(&self) {
+        #[allow(unused_mut)]
+        let mut delay = self.typing_delay.sample();
+        #[cfg(feature = "rand")]
+        if let Some(humanizer) = lock(&self.humanizer).as_mut() {
+            delay = humanizer.noisy_delay(delay);
+        }
+        if delay > Duration::ZERO {
+            std::thread::sleep(delay);
+        }
+    }
+
+    /// The keysym for typing the ASCII character `ch`, or `None` if it's a line break that
+    /// `newline` skips.
+    pub(crate) fn char_keysym(ch: char, newline: NewlinePolicy) -> Option<u32> {
+        match ch {
+            '\n' => newline.key().map(Key::keysym),
+            _ => Some(Self::ascii_keysym(ch as u8)),
+        }
+    }
+
+    fn ascii_keysym(ch: u8) -> u32 {
+        // Control characters with a key, like '\t' or '\x1b', map to that key's keysym.
+        keysym::char_to_keysym(char::from(ch))
+    }
+
+    /// Press and release the key producing `keysym`, together with the modifiers needed to reach
+    /// it. Returns false if the keysym isn't in the current keymap.
+    fn type_keysym(&self, keysym: u32) -> Result<bool> {
+        let Some((mods, keycode)) = self.find_key_sequence(Key::new(keysym)) else {
+            return Ok(false);
+        };
+        self.send_key_sequence(keysym, &mods, keycode)?;
+        Ok(true)
+    }
+
+    // `keysym` is only for the trace.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    fn send_key_sequence(&self, keysym: u32, mods: &[u32], keycode: u32) -> Result<()> {
+        trace_event!(keysym, keycode, modifiers = ?mods, "key_sequence");
+        for &m in mods {
+            self.fake_input(xproto::KEY_PRESS_EVENT, m as _, 0, 0)?;
+        }
+        self.fake_input(xproto::KEY_PRESS_EVENT, keycode as _, 0, 0)?;
+        self.fake_input(xproto::KEY_RELEASE_EVENT, keycode as _, 0, 0)?;
+        for &m in mods.iter().rev() {
+            self.fake_input(xproto::KEY_RELEASE_EVENT, m as _, 0, 0)?;
+        }
+        self.flush()
+    }
+
+    /// Press, or release if `press` is false, the key producing `keysym`. The modifiers needed
+    /// to produce it are pressed before the key, and released after it.
+    ///
+    /// If pressing fails halfway, the keys pressed so far are released again, so no modifier is
+    /// left held.
+    pub fn key(&self, keysym: u32, press: bool) -> Result<()> {
+        self.call("key", || {
+            self.check_not_grabbed(true)?;
+            let (mods, keycode) = self
+                .find_key_sequence(Key::new(keysym))
+                .ok_or(Error::NoKeySequence { keysym })?;
+            trace_event!(keysym, keycode, modifiers = ?mods, press, "key_sequence");
+            if !press {
+                self.fake_input(xproto::KEY_RELEASE_EVENT, keycode as _, 0, 0)?;
+                for &m in mods.iter().rev() {
+                    self.fake_input(xproto::KEY_RELEASE_EVENT, m as _, 0, 0)?;
+                }
+                return self.flush();
+            }
+            let mut pressed = Vec::with_capacity(mods.len() + 1);
+            let result = mods
+                .iter()
+                .chain([&keycode])
+                .try_for_each(|&k| {
+                    // A key the server rejected isn't down, but releasing it does no harm.
+                    pressed.push(k);
+                    self.fake_input(xproto::KEY_PRESS_EVENT, k as _, 0, 0)
+                })
+                .and_then(|()| self.flush());
+            if result.is_err() {
+                let _ = self.release_keycodes(&pressed);
+            }
+            result
+        })
+    }
+
+    /// Release `keycodes` in reverse order.
+    fn release_keycodes(&self, keycodes: &[u32]) -> Result<()> {
+        for &k in keycodes.iter().rev() {
+            self.fake_input(xproto::KEY_RELEASE_EVENT, k as _, 0, 0)?;
+        }
+        self.flush()
+    }
+
+    /// Press and release the key producing `key`, with the modifiers it needs.
+    fn tap_key(&self, key: Key) -> Result<()> {
+        self.key(key.keysym(), true)?;
+        self.key(key.keysym(), false)
+    }
+
+    /// Press and release a media key, e.g. to control a media player or the volume. Fails with
+    /// [`Error::NoKeySequence`] if the keymap has no key for it, which is common for keymaps
+    /// of keyboards without media keys.
+    pub fn press_media_key(&self, key: MediaKey) -> Result<()> {
+        self.call("press_media_key", || self.tap_key(key.into()))
+    }
+
+    /// Press and release a browser navigation key, like Back or Refresh. Fails with
+    /// [`Error::NoKeySequence`] if the keymap has no key for it, like
+    /// [`Self::press_media_key`].
+    pub fn browser_key(&self, key: BrowserKey) -> Result<()> {
+        self.call("browser_key", || self.tap_key(key.into()))
+    }
+
+    /// Press the key producing `keysym`, hold it for `duration`, then release it, e.g. for a long
+    /// press. Modifiers needed for the keysym are held for the whole time. The key is released
+    /// even if this fails or panics after pressing it, and if pressing fails, so are the
+    /// modifiers pressed before it.
+    pub fn hold_key(&self, keysym: u32, duration: Duration) -> Result<()> {
+        self.call("hold_key", || {
+            let guard = self.hold(keysym)?;
+            std::thread::sleep(duration);
+            guard.release()
+        })
+    }
+
+    /// Press the key producing `keysym` and keep it held until the returned guard is dropped or
+    /// released, e.g. to hold Shift over several other calls. Unlike [`Self::hold_key`], other
+    /// threads can use this `InputSynth` while the key is held.
+    ///
+    /// The guard releases exactly the keys it pressed, and none that another guard still holds,
+    /// see [`KeyGuard`]. While it exists, typing doesn't press or release the held keys, like
+    /// with [`Self::with_modifiers`].
+    pub fn hold(&self, keysym: u32) -> Result<KeyGuard<'_>> {
+        self.call("hold", || {
+            self.check_not_grabbed(true)?;
+            // Not `find_key_sequence`, modifiers held by other guards are held by this one too.
+            let (mods, keycode) = lock(&self.mapping)
+                .find_key_sequence(Key::new(keysym))
+                .ok_or(Error::NoKeySequence { keysym })?;
+            // If pressing fails, the guard releases what was pressed so far.
+            let guard = KeyGuard {
+                synth: self,
+                holder: lock(&self.held_keys).holder(),
+                keysym,
+            };
+            for k in mods.into_iter().chain([keycode]) {
+                if lock(&self.held_keys).add(guard.holder, k) {
+                    self.fake_input(xproto::KEY_PRESS_EVENT, k as _, 0, 0)?;
+                }
+            }
+            self.flush()?;
+            Ok(guard)
+        })
+    }
+
+    /// Release every key held by a [`KeyGuard`] or [`ModifierGuard`], e.g. before handing the
+    /// keyboard back to the user. The guards release nothing once they are dropped.
+    pub fn release_all(&self) -> Result<()> {
+        self.call("release_all", || {
+            let keycodes = lock(&self.held_keys).take_all();
+            if keycodes.is_empty() {
+                return Ok(());
+            }
+            self.release_keycodes(&keycodes)
+        })
+    }
+
+    /// Release the keys held by the guard `holder`, in reverse order, except those that other
+    /// guards still hold.
+    fn release_held(&self, holder: HolderId) -> Result<()> {
+        self.call("release_held", || {
+            let keycodes = lock(&self.held_keys).remove(holder);
+            if keycodes.is_empty() {
+                return Ok(());
+            }
+            self.release_keycodes(&keycodes)
+        })
+    }
+
+    /// Press `button` at `(x, y)`, hold it for `duration`, then release it, like
+    /// [`Self::hold_key`].
+    pub fn hold_button(&self, button: u8, x: i16, y: i16, duration: Duration) -> Result<()> {
+        self.call("hold_button", || {
+            self.check_not_grabbed(false)?;
+            self.move_cursor(x, y)?;
+            // Armed before pressing, since a press that fails to flush may still have reached
+            // the server. Releasing a button that isn't down does nothing.
+            let guard = ReleaseGuard {
+                synth: self,
+                release: InputEvent::ButtonRelease { x, y, button },
+            };
+            self.click(x, y, button, true)?;
+            std::thread::sleep(duration);
+            guard.release()
+        })
+    }
+
+    /// Press the keys of `combo` in order, then release them in reverse order.
+    pub fn key_combo(&self, combo: &KeyCombo) -> Result<()> {
+        self.call("key_combo", || {
+            self.check_not_grabbed(true)?;
+            for &keysym in combo.keysyms() {
+                self.key(keysym, true)?;
+            }
+            for &keysym in combo.keysyms().iter().rev() {
+                self.key(keysym, false)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Press the keys producing `keysyms` together, e.g. Ctrl+Alt+Delete, then release them in
+    /// reverse order. Unlike [`Self::key_combo`], all events are sent at once, so the server
+    /// sees every key held at the same time. Nothing is sent if one of the keysyms can't be
+    /// typed.
+    pub fn press_keys_chord(&self, keysyms: &[u32]) -> Result<()> {
+        self.call("press_keys_chord", || {
+            self.check_not_grabbed(true)?;
+            let mut keycodes = Vec::new();
+            for &keysym in keysyms {
+                let (mods, keycode) = self
+                    .find_key_sequence(Key::new(keysym))
+                    .ok_or(Error::NoKeySequence { keysym })?;
+                // Keys can need the same modifier, which is only pressed once.
+                for k in mods.into_iter().chain([keycode]) {
+                    if !keycodes.contains(&k) {
+                        keycodes.push(k);
+                    }
+                }
+            }
+            for &k in &keycodes {
+                self.fake_input(xproto::KEY_PRESS_EVENT, k as _, 0, 0)?;
+            }
+            for &k in keycodes.iter().rev() {
+                self.fake_input(xproto::KEY_RELEASE_EVENT, k as _, 0, 0)?;
+            }
+            self.flush()
+        })
+    }
+
+    /// Hold `modifiers` while typing each of `keysyms`, e.g. Shift while pressing arrow keys to
+    /// select text. The modifiers are pressed once, and released even if typing a key fails.
+    pub fn press_many_with_modifier(&self, modifiers: Modifiers, keysyms: &[u32]) -> Result<()> {
+        self.call("press_many_with_modifier", || {
+            self.holding_modifiers(modifiers, || {
+                keysyms.iter().try_for_each(|&keysym| {
+                    self.key(keysym, true)?;
+                    self.key(keysym, false)
+                })
+            })
+        })
+    }
+
+    /// Click `button` at `(x, y)` while holding `modifiers`, e.g. for Ctrl+click. The modifiers
+    /// are released even if clicking fails.
+    pub fn click_with_mods(&self, x: i16, y: i16, button: u8, modifiers: Modifiers) -> Result<()> {
+        self.call("click_with_mods", || {
+            self.holding_modifiers(modifiers, || {
+                self.move_cursor(x, y)?;
+                self.click(x, y, button, true)?;
+                self.click(x, y, button, false)
+            })
+        })
+    }
+
+    /// Press `button` at `from`, move to `to` and release it there, while holding `modifiers`,
+    /// e.g. Alt to move a window. The button and modifiers are released even if moving fails.
+    pub fn drag_with_mods(
+        &self,
+        from: (i16, i16),
+        to: (i16, i16),
+        button: u8,
+        modifiers: Modifiers,
+    ) -> Result<()> {
+        self.call("drag_with_mods", || {
+            self.holding_modifiers(modifiers, || {
+                self.move_cursor(from.0, from.1)?;
+                self.click(from.0, from.1, button, true)?;
+                let moved = self.move_cursor(to.0, to.1);
+                let (x, y) = if moved.is_ok() { to } else { from };
+                let released = self.click(x, y, button, false);
+                moved.and(released)
+            })
+        })
+    }
+
+    /// Hold the keys of `modifiers` while running `f`, then release them, even if `f` fails.
+    fn holding_modifiers<T>(
+        &self,
+        modifiers: Modifiers,
+        f: impl FnOnce() -> Result<T>,
+    ) -> Result<T> {
+        let guard = self.with_modifiers(modifiers)?;
+        let result = f();
+        let released = guard.release();
+        let value = result?;
+        released?;
+        Ok(value)
+    }
+
+    /// Press the keys of `modifiers` and keep them held until the returned guard is dropped or
+    /// released, e.g. to Ctrl+click several items with [`Self::click_at`]. Modifiers that are
+    /// already held, by the user or an outer guard, are left alone, and stay held after this
+    /// guard is gone. Each guard releases exactly the keys it pressed.
+    ///
+    /// While the guard exists, typing doesn't press or release its modifier keys, even for
+    /// characters that need them, so e.g. Shift held by a guard stays held across typing `A`.
+    pub fn with_modifiers(&self, modifiers: Modifiers) -> Result<ModifierGuard<'_>> {
+        self.call("with_modifiers", || {
+            self.check_not_grabbed(true)?;
+            let held = self.modifier_state()?.depressed;
+            let missing = modifiers.bits() & !held.bits();
+            let keycodes = {
+                let mapping = lock(&self.mapping);
+                // XKB puts the real modifiers first, in the order of their bits.
+                (0..8u8)
+                    .filter(|&m| missing & (1 << m) != 0)
+                    .map(|m| mapping.modifier_keycode.get(&m).copied())
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or(Error::NoModifierKey(modifiers))?
+            };
+            // If pressing fails, the guard releases what was pressed so far.
+            let guard = ModifierGuard {
+                synth: self,
+                holder: lock(&self.held_keys).holder(),
+                modifiers,
+            };
+            for k in keycodes {
+                if lock(&self.held_keys).add(guard.holder, k) {
+                    self.fake_input(xproto::KEY_PRESS_EVENT, k as _, 0, 0)?;
+                }
+            }
+            self.flush()?;
+            Ok(guard)
+        })
+    }
+
+    /// Run the steps of `script` in order. Steps that this version doesn't support are skipped
+    /// and listed in the report. The first step that fails stops the script, and is reported
+    /// through [`Error::ScriptFailed`].
+    pub fn run_script(&self, script: &Script) -> Result<ScriptReport> {
+        self.call("run_script", || {
+            let mut report = ScriptReport::default();
+            for (step, action) in script.actions().iter().enumerate() {
+                let result = match action {
+                    Action::Type { text } => self.type_string(text),
+                    Action::Key { combo } => self.key_combo(combo),
+                    Action::KeyPress { combo } => combo
+                        .keysyms()
+                        .iter()
+                        .try_for_each(|&keysym| self.key(keysym, true)),
+                    Action::KeyRelease { combo } => combo
+                        .keysyms()
+                        .iter()
+                        .rev()
+                        .try_for_each(|&keysym| self.key(keysym, false)),
+                    Action::ButtonPress { x, y, button } => self
+                        .move_cursor(*x, *y)
+                        .and_then(|()| self.click(*x, *y, *button, true)),
+                    Action::ButtonRelease { x, y, button } => self
+                        .move_cursor(*x, *y)
+                        .and_then(|()| self.click(*x, *y, *button, false)),
+                    Action::Click { x, y, button } => self.click_at(*x, *y, *button),
+                    Action::Move { x, y } => self.move_cursor(*x, *y),
+                    Action::Scroll { x, y, dx, dy } => self.smooth_scroll(*x, *y, *dx, *dy),
+                    Action::Wait { duration } => {
+                        std::thread::sleep(*duration);
+                        Ok(())
+                    }
+                    Action::Unsupported => {
+                        report.skipped.push(step);
+                        Ok(())
+                    }
+                };
+                result.map_err(|source| Error::ScriptFailed {
+                    step,
+                    source: Box::new(source),
+                })?;
+                report.completed += 1;
+            }
+            Ok(report)
+        })
+    }
+
+    /// Type the ASCII character `ch`. Fails with [`Error::NoKeySequence`] if the current keymap
+    /// can't produce it.
+    pub fn ascii_char(&self, ch: u8) -> Result<()> {
+        self.call("ascii_char", || {
+            self.check_not_grabbed(true)?;
+            let keysym = Self::ascii_keysym(ch);
+            if !self.type_keysym(keysym)? {
+                return Err(Error::NoKeySequence { keysym });
+            }
+            self.stats.char_typed();
+            Ok(())
+        })
+    }
+
+    /// Press and release the keypad key `key`.
+    ///
+    /// The digits and the decimal point of the keypad are only produced while NumLock is on,
+    /// otherwise the same keys move the cursor. If NumLock is off, it is turned on for the key
+    /// and off again afterwards; pressing the NumLock key toggles it, so it can't just be held
+    /// like Shift. Enter and the operators don't depend on NumLock.
+    pub fn press_numpad(&self, key: NumpadKey) -> Result<()> {
+        self.call("press_numpad", || {
+            self.check_not_grabbed(true)?;
+            let keysym = Key::from(key).keysym();
+            let (mut mods, keycode) = self
+                .find_key_sequence(Key::from(key))
+                .ok_or(Error::NoKeySequence { keysym })?;
+            let num_lock = self
+                .lock_key(NUM_LOCK_KEYSYM)
+                .ok()
+                .filter(|(num_lock, _)| key.needs_num_lock() && mods.contains(num_lock));
+            let toggle = match num_lock {
+                Some((num_lock, locks)) => {
+                    mods.retain(|&m| m != num_lock);
+                    !self.is_locked(locks)?
+                }
+                None => false,
+            };
+            if toggle {
+                self.set_num_lock(true)?;
+            }
+            let result = self.send_key_sequence(keysym, &mods, keycode);
+            if toggle {
+                self.set_num_lock(false)?;
+            }
+            result
+        })
+    }
+
+    /// Whether Caps Lock is on.
+    pub fn caps_lock(&self) -> Result<bool> {
+        self.call("caps_lock", || {
+            let (_, locks) = self.lock_key(CAPS_LOCK_KEYSYM)?;
+            self.is_locked(locks)
+        })
+    }
+
+    /// Whether NumLock is on.
+    pub fn num_lock(&self) -> Result<bool> {
+        self.call("num_lock", || {
+            let (_, locks) = self.lock_key(NUM_LOCK_KEYSYM)?;
+            self.is_locked(locks)
+        })
+    }
+
+    /// Turn Caps Lock on or off, pressing the Caps Lock key only if it isn't that way already.
+    /// Fails with [`Error::LockUnchanged`] if the server doesn't report the new state shortly
+    /// after.
+    pub fn set_caps_lock(&self, on: bool) -> Result<()> {
+        self.call("set_caps_lock", || self.set_locked(CAPS_LOCK_KEYSYM, on))
+    }
+
+    /// Turn NumLock on or off, like [`Self::set_caps_lock`].
+    pub fn set_num_lock(&self, on: bool) -> Result<()> {
+        self.call("set_num_lock", || self.set_locked(NUM_LOCK_KEYSYM, on))
+    }
+
+    /// The key producing the lock keysym `keysym`, like `Caps_Lock`, and the real modifiers
+    /// it locks.
+    fn lock_key(&self, keysym: u32) -> Result<(u32, Modifiers)> {
+        let (_, keycode) = self
+            .find_key_sequence(Key::new(keysym))
+            .ok_or(Error::NoKeySequence { keysym })?;
+        let mask = lock(&self.mapping).modifier_mask(&[keycode]);
+        Ok((keycode, Modifiers::from_bits(mask as u8)))
+    }
+
+    /// Whether the modifiers `locks` of a lock key are locked. A key that locks no modifiers
+    /// is never on.
+    fn is_locked(&self, locks: Modifiers) -> Result<bool> {
+        Ok(!locks.is_empty() && self.modifier_state()?.locked.contains(locks))
+    }
+
+    fn set_locked(&self, keysym: u32, on: bool) -> Result<()> {
+        /// How long to give a slow server before checking the state once more.
+        const RETRY_DELAY: Duration = Duration::from_millis(50);
+        let (keycode, locks) = self.lock_key(keysym)?;
+        if self.is_locked(locks)? == on {
+            return Ok(());
+        }
+        self.check_not_grabbed(true)?;
+        self.fake_input(xproto::KEY_PRESS_EVENT, keycode as _, 0, 0)?;
+        self.fake_input(xproto::KEY_RELEASE_EVENT, keycode as _, 0, 0)?;
+        self.sync()?;
+        if self.is_locked(locks)? == on {
+            return Ok(());
+        }
+        std::thread::sleep(RETRY_DELAY);
+        if self.is_locked(locks)? == on {
+            return Ok(());
+        }
+        Err(Error::LockUnchanged {
+            key: Key::new(keysym),
+            on,
+        })
+    }
+
+    /// Press and release the keypad key of `digit`, see [`Self::press_numpad`]. Fails with
+    /// [`Error::NotADigit`] if `digit` is more than 9.
+    pub fn numpad_digit(&self, digit: u8) -> Result<()> {
+        self.press_numpad(NumpadKey::digit(digit).ok_or(Error::NotADigit(digit))?)
+    }
+
+    /// Type `ch` through a Compose sequence, e.g. a dead key followed by a letter, for
+    /// characters no key of the keymap produces. The sequence is looked up in the Compose table
+    /// of the locale, from `$LC_ALL`, `$LC_CTYPE` or `$LANG`, and only uses keys of the keymap,
+    /// so it needs dead keys or a Compose key (`Multi_key`) in it. The receiving application has
+    /// to do the composing, as most do.
+    ///
+    /// Fails with [`Error::Untypeable`] if no sequence produces `ch`.
+    pub fn type_char_compose(&self, ch: char) -> Result<()> {
+        self.call("type_char_compose", || {
+            let keysyms = lock(&self.mapping).keysyms();
+            let sequence = {
+                let mut compose_table = lock(&self.compose_table);
+                let table = compose::LocaleTable::current(&mut compose_table).ok_or(
+                    Error::Unsupported("Compose sequences without a Compose table"),
                 )?;
+                compose::find_sequence(table, &keysyms, ch)
+            };
+            let sequence = sequence.ok_or(Error::Untypeable(ch))?;
+            trace_event!(?ch, ?sequence, "compose");
+            for keysym in sequence {
+                self.tap_key(Key::new(keysym))?;
+            }
+            self.stats.char_typed();
+            Ok(())
+        })
+    }
+
+    /// Type `s` character by character, pausing between characters according to the configured
+    /// [`TypingDelay`]. Only ASCII is supported, the first character that can't be typed aborts
+    /// with [`Error::Untypeable`]. Line breaks are typed according to the [`NewlinePolicy`].
+    pub fn type_string(&self, s: &str) -> Result<()> {
+        self.call("type_string", || {
+            self.check_not_grabbed(true)?;
+            for (i, ch) in newline::normalize_line_breaks(s).enumerate() {
+                if i != 0 {
+                    self.typing_pause();
+                }
+                self.type_char(ch)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn type_char(&self, ch: char) -> Result<()> {
+        if !ch.is_ascii() {
+            return Err(Error::Untypeable(ch));
+        }
+        let Some(keysym) = Self::char_keysym(ch, self.newline_policy) else {
+            return Ok(());
+        };
+        if !self.type_keysym(keysym)? {
+            return Err(Error::Untypeable(ch));
+        }
+        self.stats.char_typed();
+        Ok(())
+    }
+
+    /// The window with the input focus, which key events go to. Besides windows, this can be
+    /// `x11rb::NONE` or `1` for PointerRoot, when the focus follows the pointer.
+    #[doc(alias = "get_focused_window")]
+    pub fn focused_window(&self) -> Result<xproto::Window> {
+        self.call("focused_window", || {
+            Ok(self.conn().get_input_focus()?.reply()?.focus)
+        })
+    }
+
+    /// Make our requests, including the synthesized input, go through even while another client
+    /// grabs the server, like a screen locker or a menu does, or stop doing so.
+    ///
+    /// Use with care: with this on, input can reach windows hidden behind a locked screen, and
+    /// the grabbing client won't see it. It stays on across reconnects.
+    ///
+    /// Keyboard and pointer grabs are a different matter, XTEST input always goes to the
+    /// grabbing client, see [`Self::check_grabs`].
+    pub fn set_grab_control(&self, impervious: bool) -> Result<()> {
+        self.call("set_grab_control", || {
+            self.conn().xtest_grab_control(impervious)?;
+            self.flush()?;
+            self.impervious_to_grabs
+                .store(impervious, Ordering::Relaxed);
+            Ok(())
+        })
+    }
+
+    /// The same as [`Self::set_grab_control`], named after the XTEST request
+    /// (`XTestGrabControl`).
+    pub fn set_xtest_grab(&self, grab: bool) -> Result<()> {
+        self.set_grab_control(grab)
+    }
+
+    /// Whether another client has grabbed the keyboard or the pointer, in which case our input
+    /// goes to that client, see [`GrabState`]. Found out by briefly grabbing them ourselves.
+    pub fn check_grabs(&self) -> Result<GrabState> {
+        self.call("check_grabs", || grab::probe(&*self.conn(), self.root()?))
+    }
+
+    /// Make the methods that press keys or buttons check for grabs first, and fail with
+    /// [`Error::InputGrabbed`] instead of sending input to the grabbing client. Typing and key
+    /// presses only check the keyboard, clicks, scrolling and touches only the pointer. This
+    /// costs two round trips per call, including the calls methods make to each other, like
+    /// [`Self::key_combo`] to [`Self::key`] for each key.
+    pub fn set_fail_on_grab(&mut self, fail: bool) {
+        self.fail_on_grab = fail;
+    }
+
+    /// With [`Self::set_fail_on_grab`], fail if the keyboard, or the pointer if not `keyboard`,
+    /// is grabbed.
+    fn check_not_grabbed(&self, keyboard: bool) -> Result<()> {
+        if !self.fail_on_grab {
+            return Ok(());
+        }
+        let grabs = grab::probe(&*self.conn(), self.root()?)?;
+        if (keyboard && grabs.keyboard) || (!keyboard && grabs.pointer) {
+            return Err(Error::InputGrabbed {
+                keyboard: grabs.keyboard,
+                pointer: grabs.pointer,
+            });
+        }
+        Ok(())
+    }
+
+    /// Bring `window` to the front and give it the input focus, e.g. before typing into it. The
+    /// window manager is asked through `_NET_ACTIVE_WINDOW`; without a window manager that
+    /// supports it, the window is focused and raised directly. Fails with [`Error::Timeout`] if
+    /// the focus isn't in the window or one of its children within a second.
+    pub fn activate_window(&self, window: xproto::Window) -> Result<()> {
+        /// How long the window manager gets to activate the window.
+        const TIMEOUT: Duration = Duration::from_secs(1);
+        self.call("activate_window", || {
+            let conn = self.conn();
+            window::activate(&*conn, self.root()?, window)?;
+            let deadline = std::time::Instant::now() + TIMEOUT;
+            loop {
+                let focus = conn.get_input_focus()?.reply()?.focus;
+                if window::is_within(&*conn, focus, window)? {
+                    return Ok(());
+                }
+                if std::time::Instant::now() >= deadline {
+                    return Err(Error::Timeout(TIMEOUT));
+                }
+                std::thread::sleep(Duration::from_millis(10));
             }
-            self.connection.flush()?;
+        })
+    }
+
+    /// Give `window` the input focus, without raising it or asking the window manager. Also takes
+    /// `x11rb::NONE` and `1` for PointerRoot, as returned by [`Self::focused_window`]. Prefer
+    /// [`Self::activate_window`] for top-level windows, window managers may take the focus back.
+    pub fn focus_window(&self, window: xproto::Window) -> Result<()> {
+        self.call("focus_window", || {
+            self.conn()
+                .set_input_focus(xproto::InputFocus::PARENT, window, x11rb::CURRENT_TIME)?
+                .check()?;
+            Ok(())
+        })
+    }
+
+    /// Focus `window` with [`Self::focus_window`], type `s` into it, and give the focus back to
+    /// where it was before, even if typing fails.
+    pub fn type_string_to_window(&self, window: xproto::Window, s: &str) -> Result<()> {
+        self.call("type_string_to_window", || {
+            let previous = self.focused_window()?;
+            self.focus_window(window)?;
+            // The keys have to arrive before the focus moves back.
+            let typed = self.type_string(s).and_then(|()| self.sync());
+            let restored = self.focus_window(previous);
+            typed.and(restored)
+        })
+    }
+
+    /// Send a press, or release if `press` is false, of the key producing `keysym` straight to
+    /// `window` with `SendEvent`, whether or not it has the focus. The modifiers the keysym needs
+    /// are set in the event, but not actually pressed.
+    ///
+    /// This is best-effort: events sent this way are marked as such, and many toolkits and
+    /// applications, like xterm by default, ignore them. Prefer [`Self::key`] when possible.
+    pub fn send_synthetic_key_to(
+        &self,
+        window: xproto::Window,
+        keysym: u32,
+        press: bool,
+    ) -> Result<()> {
+        self.call("send_synthetic_key_to", || {
+            let (state, keycode) = {
+                let mapping = lock(&self.mapping);
+                let (mods, keycode) = mapping
+                    .find_key_sequence(Key::new(keysym))
+                    .ok_or(Error::NoKeySequence { keysym })?;
+                (mapping.modifier_mask(&mods), keycode)
+            };
+            let response_type = if press {
+                xproto::KEY_PRESS_EVENT
+            } else {
+                xproto::KEY_RELEASE_EVENT
+            };
+            window::send_input_event(
+                &*self.conn(),
+                window,
+                response_type,
+                keycode as u8,
+                state,
+                None,
+            )
+        })
+    }
+
+    /// Send a press, or release if `press` is false, of pointer `button` at `(x, y)` relative
+    /// to `window` straight to it with `SendEvent`. The pointer isn't moved.
+    ///
+    /// Best-effort like [`Self::send_synthetic_key_to`], prefer [`Self::click`] when possible.
+    pub fn send_synthetic_button_to(
+        &self,
+        window: xproto::Window,
+        x: i16,
+        y: i16,
+        button: u8,
+        press: bool,
+    ) -> Result<()> {
+        self.call("send_synthetic_button_to", || {
+            // The state is from before the event, so a release has its button held.
+            let state = match button {
+                1..=5 if !press => 1 << (7 + button),
+                _ => 0,
+            };
+            let response_type = if press {
+                xproto::BUTTON_PRESS_EVENT
+            } else {
+                xproto::BUTTON_RELEASE_EVENT
+            };
+            window::send_input_event(
+                &*self.conn(),
+                window,
+                response_type,
+                button,
+                state,
+                Some((x, y)),
+            )
+        })
+    }
+
+    /// Fail with [`Error::FocusChanged`] unless the focus is in `expected` or a child of it.
+    fn check_focus(&self, expected: xproto::Window) -> Result<()> {
+        let conn = self.conn();
+        let actual = conn.get_input_focus()?.reply()?.focus;
+        if window::is_within(&*conn, actual, expected)? {
+            return Ok(());
         }
+        Err(Error::FocusChanged {
+            expected,
+            actual,
+            name: window::window_name(&*conn, actual).ok().flatten(),
+        })
+    }
 
-        Ok(())
+    /// Like [`InputSynth::type_string`], but make sure the text goes to the `expected` window,
+    /// or to the window focused right now if `None`. The focus is checked before typing, and
+    /// again once the server has processed all of `s`; if it's somewhere else, this fails with
+    /// [`Error::FocusChanged`]. Children of the window count as the window.
+    pub fn type_string_into_focused(
+        &self,
+        expected: Option<xproto::Window>,
+        s: &str,
+    ) -> Result<()> {
+        self.call("type_string_into_focused", || {
+            let expected = match expected {
+                Some(expected) => {
+                    self.check_focus(expected)?;
+                    expected
+                }
+                None => self.focused_window()?,
+            };
+            self.type_string(s)?;
+            self.sync()?;
+            self.check_focus(expected)
+        })
+    }
+
+    /// Like [`InputSynth::type_string`], then [`InputSynth::sync`].
+    pub fn type_string_synced(&self, s: &str) -> Result<()> {
+        self.call("type_string_synced", || {
+            self.type_string(s)?;
+            self.sync()
+        })
+    }
+
+    /// Like [`InputSynth::type_string`], but characters that can't be typed are skipped instead
+    /// of aborting. The skipped characters are returned in order; the `Result` only reports
+    /// errors talking to the X server.
+    pub fn type_string_skip_missing(&self, s: &str) -> (Result<()>, Vec<char>) {
+        let mut skipped = Vec::new();
+        let result = self.call("type_string_skip_missing", || {
+            skipped.clear();
+            self.check_not_grabbed(true)?;
+            let mut first = true;
+            for ch in newline::normalize_line_breaks(s) {
+                let sequence = if ch.is_ascii() {
+                    let Some(keysym) = Self::char_keysym(ch, self.newline_policy) else {
+                        continue;
+                    };
+                    self.find_key_sequence(Key::new(keysym))
+                        .map(|sequence| (keysym, sequence))
+                } else {
+                    None
+                };
+                let Some((keysym, (mods, keycode))) = sequence else {
+                    skipped.push(ch);
+                    continue;
+                };
+                if !first {
+                    self.typing_pause();
+                }
+                first = false;
+                self.send_key_sequence(keysym, &mods, keycode)?;
+                self.stats.char_typed();
+            }
+            Ok(())
+        });
+        (result, skipped)
+    }
+
+    /// Type `s` character by character, calling `on_char` after each character with the result
+    /// of typing it. Unlike [`InputSynth::type_string`], failures don't stop the typing; if any
+    /// character failed, [`Error::TypingFailed`] is returned at the end.
+    pub fn type_string_with_callback<F: FnMut(char, Result<()>)>(
+        &self,
+        s: &str,
+        mut on_char: F,
+    ) -> Result<()> {
+        self.call("type_string_with_callback", || {
+            self.check_not_grabbed(true)?;
+            let mut failed = 0;
+            let mut total = 0;
+            for ch in newline::normalize_line_breaks(s) {
+                if total != 0 {
+                    self.typing_pause();
+                }
+                total += 1;
+                let result = self.type_char(ch);
+                if result.is_err() {
+                    failed += 1;
+                }
+                on_char(ch, result);
+            }
+            if failed == 0 {
+                Ok(())
+            } else {
+                Err(Error::TypingFailed { failed, total })
+            }
+        })
     }
 }
 
 #[cfg(test)]
 mod test {
+    static_assertions::assert_impl_all!(super::InputSynth: Send, Sync, std::fmt::Debug);
+
+    #[test]
+    fn test_coordinate_scale() {
+        assert_eq!(super::scale_point(10, 21, 1.5), (15, 32));
+        assert_eq!(super::scale_point(-3, 0, 1.5), (-5, 0));
+        assert_eq!(super::scale_point(100, 100, 1.0), (100, 100));
+        let resources = b"Xcursor.size:\t24\nXft.dpi:\t192\nXft.hinting:\t1\n";
+        assert_eq!(super::xft_dpi(resources), Some(192.0));
+        assert_eq!(super::xft_dpi(b"Xft.hinting: 1\n"), None);
+        assert_eq!(super::xft_dpi(b"Xft.dpi: bogus\n"), None);
+    }
+
+    #[test]
+    fn test_missing_modifier_keycode() {
+        use xkbcommon::xkb;
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_names(&context, "", "", "us", "", None, 0).unwrap();
+        let mut state = super::KeymapState::new(&context, keymap);
+        assert_eq!(
+            state
+                .find_key_sequence(super::Key::from('A'))
+                .unwrap()
+                .0
+                .len(),
+            1
+        );
+        // Without a key for Shift, 'A' can't be typed, but 'a' still can.
+        state.modifier_keycode.clear();
+        state.key_sequences =
+            super::KeymapState::key_sequences(&state.mapping, &state.modifier_keycode);
+        assert_eq!(state.find_key_sequence(super::Key::from('A')), None);
+        assert_eq!(
+            state.find_key_sequence(super::Key::from('a')).unwrap().0,
+            []
+        );
+    }
+
+    #[test]
+    fn test_keycode_to_keysym() {
+        use xkbcommon::xkb;
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_names(&context, "", "", "us", "", None, 0).unwrap();
+        let shift = 1 << keymap.mod_get_index(xkb::MOD_NAME_SHIFT);
+        let state = super::KeymapState::new(&context, keymap);
+        // Keycode 38 is the A key in the evdev keymap.
+        assert_eq!(state.keycode_to_keysym(38, 0), Some(b'a'.into()));
+        assert_eq!(state.keycode_to_keysym(38, shift), Some(b'A'.into()));
+        assert_eq!(state.keycode_to_keysym(0, 0), None);
+    }
+
+    #[test]
+    fn test_wide_keysyms() {
+        use super::Key;
+        use xkbcommon::xkb;
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_names(&context, "", "", "de", "", None, 0).unwrap();
+        let state = super::KeymapState::new(&context, keymap);
+        // Cut to 16 bits, XF86AudioPlay would be Scroll_Lock.
+        for key in [Key::new(0x1008ff14), Key::from('€')] {
+            let (mods, keycode) = state.find_key_sequence(key).unwrap();
+            let mask = state.modifier_mask(&mods).into();
+            assert_eq!(state.keycode_to_keysym(keycode, mask), Some(key.keysym()));
+        }
+        assert_eq!(Key::from('€').keysym(), 0x20ac);
+        assert_ne!(
+            state.find_key_sequence(Key::new(0x1008ff14)),
+            state.find_key_sequence(Key::new(0xff14))
+        );
+    }
+
+    #[test]
+    fn test_return_and_kp_enter() {
+        use super::Key;
+        use xkbcommon::xkb;
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_names(&context, "", "pc105", "us", "", None, 0).unwrap();
+        let state = super::KeymapState::new(&context, keymap);
+        let (return_mods, return_key) = state.find_key_sequence(Key::RETURN).unwrap();
+        let (enter_mods, enter_key) = state.find_key_sequence(Key::KP_ENTER).unwrap();
+        // Keycodes 36 and 104 in the evdev keymap.
+        assert_ne!(return_key, enter_key);
+        assert!(return_mods.is_empty() && enter_mods.is_empty());
+    }
+
+    #[test]
+    fn test_modifier_names() {
+        use xkbcommon::xkb;
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_names(&context, "", "", "us", "", None, 0).unwrap();
+        let state = super::KeymapState::new(&context, keymap);
+        let names = state.modifier_names();
+        assert_eq!(names[xkb::MOD_NAME_SHIFT], 0);
+        assert!(names.contains_key(xkb::MOD_NAME_CTRL));
+        // Keycodes 50 and 62 are the Shift keys in the evdev keymap.
+        assert!(matches!(
+            state.modifier_keycode(xkb::MOD_NAME_SHIFT),
+            Some(50 | 62)
+        ));
+        assert_eq!(state.modifier_keycode("NoSuchModifier"), None);
+    }
+
+    #[test]
+    fn test_layouts() {
+        use xkbcommon::xkb;
+        const CYRILLIC_EF: u32 = 0x6c6;
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_names(&context, "", "", "us,ru", "", None, 0).unwrap();
+        let mut state = super::KeymapState::new(&context, keymap);
+        assert_eq!(state.keycode_to_keysym(38, 0), Some(b'a'.into()));
+        assert!(state
+            .find_key_sequence(super::Key::new(CYRILLIC_EF))
+            .is_none());
+        state.layout = 1;
+        assert_eq!(state.keycode_to_keysym(38, 0), Some(CYRILLIC_EF));
+        assert_eq!(
+            state
+                .find_key_sequence(super::Key::new(CYRILLIC_EF))
+                .unwrap()
+                .1,
+            38
+        );
+        // Keys that only have a US layout wrap around to it.
+        assert!(state.find_key_sequence(super::Key::new(0xffe1)).is_some());
+    }
+
+    #[test]
+    fn test_virtual_modifiers() {
+        use xkbcommon::xkb;
+        const AE: u16 = 0xe6;
+        const MOD5: u32 = 1 << 7;
+        let keymap = r#"
+            xkb_keymap {
+                xkb_keycodes { <AC01> = 38; <LVL3> = 92; };
+                xkb_types {
+                    virtual_modifiers LevelThree;
+                    type "ONE_LEVEL" { modifiers = none; level_name[Level1] = "Any"; };
+                    type "THREE_LEVEL" {
+                        modifiers = LevelThree;
+                        map[LevelThree] = Level2;
+                        level_name[Level1] = "Base";
+                        level_name[Level2] = "LevelThree";
+                    };
+                };
+                xkb_compatibility {
+                    virtual_modifiers LevelThree;
+                    interpret ISO_Level3_Shift {
+                        virtualModifier = LevelThree;
+                        action = SetMods(modifiers = LevelThree);
+                    };
+                };
+                xkb_symbols {
+                    key <AC01> { type = "THREE_LEVEL", [ a, ae ] };
+                    key <LVL3> { type = "ONE_LEVEL", [ ISO_Level3_Shift ] };
+                    modifier_map Mod5 { <LVL3> };
+                };
+            };
+        "#;
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_string(
+            &context,
+            keymap.into(),
+            xkb::KEYMAP_FORMAT_TEXT_V1,
+            xkb::KEYMAP_COMPILE_NO_FLAGS,
+        )
+        .unwrap();
+        let level_three = keymap.mod_get_index("LevelThree");
+        let mut xkb_state = xkb::State::new(&keymap);
+        assert_eq!(
+            super::KeymapState::real_mods(&mut xkb_state, 1 << level_three),
+            MOD5
+        );
+        let state = super::KeymapState::new(&context, keymap);
+        assert_eq!(state.modifier_keycode("Mod5"), Some(92));
+        assert_eq!(
+            state.find_key_sequence(super::Key::new(AE.into())),
+            Some((vec![92], 38))
+        );
+    }
+
+    /// Run with `--ignored` to see how long typing a long text takes to look up.
+    #[test]
+    #[ignore]
+    fn bench_find_key_sequence() {
+        use xkbcommon::xkb;
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_names(&context, "", "", "us", "", None, 0).unwrap();
+        let start = std::time::Instant::now();
+        let state = super::KeymapState::new(&context, keymap);
+        let loaded = start.elapsed();
+        let text = "The quick brown fox jumps over the lazy dog. ".repeat(10_000 / 45 + 1);
+        let start = std::time::Instant::now();
+        for ch in text.bytes().take(10_000) {
+            assert!(state
+                .find_key_sequence(super::Key::new(super::InputSynth::ascii_keysym(ch)))
+                .is_some());
+        }
+        println!(
+            "keymap loaded in {loaded:?}, 10000 characters looked up in {:?}",
+            start.elapsed()
+        );
+    }
+
     #[test]
     fn test_find_key_sequence() {
         let is = super::InputSynth::new().unwrap();
-        let (mods, keycode) = is.find_key_sequence(b'A' as _).unwrap();
+        let (mods, keycode) = is.find_key_sequence(super::Key::from('A')).unwrap();
         println!("{mods:?} {keycode}");
     }
+
+    #[test]
+    fn test_reconnect_attempts() {
+        use super::ReconnectPolicy;
+        assert_eq!(ReconnectPolicy::Never.attempts(), 0);
+        assert_eq!(ReconnectPolicy::Retries(0).attempts(), 0);
+        assert_eq!(ReconnectPolicy::Retries(3).attempts(), 3);
+    }
+
+    #[test]
+    fn test_select_keyboard_device() {
+        use x11rb::protocol::xinput::{DeviceInfo, DeviceUse};
+        let device = |device_id, device_use| DeviceInfo {
+            device_type: x11rb::NONE,
+            device_id,
+            num_class_info: 0,
+            device_use,
+        };
+        assert_eq!(super::select_keyboard_device(&[]), None);
+        assert_eq!(
+            super::select_keyboard_device(&[
+                device(2, DeviceUse::IS_X_POINTER),
+                device(3, DeviceUse::IS_X_KEYBOARD),
+            ]),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn test_default_keyboard_device() {
+        use x11rb::protocol::xinput::{DeviceInfo, DeviceUse};
+        let device = |device_id, device_use| DeviceInfo {
+            device_type: x11rb::NONE,
+            device_id,
+            num_class_info: 0,
+            device_use,
+        };
+        let devices = [
+            device(2, DeviceUse::IS_X_POINTER),
+            device(3, DeviceUse::IS_X_KEYBOARD),
+            device(4, DeviceUse::IS_X_EXTENSION_KEYBOARD),
+        ];
+        assert_eq!(super::default_keyboard_device(Some(4), &devices), Some(4));
+        // Without a core keyboard from XKB, the listed one is used.
+        assert_eq!(super::default_keyboard_device(None, &devices), Some(3));
+        assert_eq!(super::default_keyboard_device(None, &devices[..1]), None);
+    }
+
+    #[test]
+    fn test_char_keysym_matches_keysyms() {
+        for ch in (0..=0x7f).map(char::from).filter(|&ch| ch != '\n') {
+            assert_eq!(
+                super::InputSynth::char_keysym(ch, super::NewlinePolicy::default()),
+                Some(super::keysyms::char_to_keysym(ch)),
+                "{ch:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_set_keyboard_device() {
+        let mut is = super::InputSynth::new().unwrap();
+        let keyboards = is.keyboards().unwrap();
+        let core = keyboards.iter().find(|k| k.is_core).unwrap();
+        is.set_keyboard_device(core.id).unwrap();
+        assert!(is.find_key_sequence(super::Key::from('a')).is_some());
+        assert!(matches!(
+            is.set_keyboard_device(u8::MAX),
+            Err(super::Error::NotAKeyboard(u8::MAX))
+        ));
+    }
+
+    #[test]
+    fn test_press_keys_chord() {
+        let is = super::InputSynth::new().unwrap();
+        let before = is.stats().keys_pressed;
+        is.press_keys_chord(&[super::CONTROL_L_KEYSYM, b'A'.into(), b'B'.into()])
+            .unwrap();
+        // Shift is needed by both letters, but pressed once.
+        assert_eq!(is.stats().keys_pressed - before, 4);
+        assert!(matches!(
+            is.press_keys_chord(&[b'a'.into(), 0x1234_5678]),
+            Err(super::Error::NoKeySequence {
+                keysym: 0x1234_5678
+            })
+        ));
+        assert_eq!(is.stats().keys_pressed - before, 4);
+    }
+
+    #[test]
+    fn test_modifier_state() {
+        use super::Modifiers;
+        let is = super::InputSynth::new().unwrap();
+        is.key(super::CONTROL_L_KEYSYM, true).unwrap();
+        let state = is.modifier_state().unwrap();
+        is.key(super::CONTROL_L_KEYSYM, false).unwrap();
+        assert!(state.depressed.contains(Modifiers::CONTROL));
+        assert!(!is
+            .modifier_state()
+            .unwrap()
+            .depressed
+            .contains(Modifiers::CONTROL));
+    }
+
+    #[test]
+    fn test_press_many_with_modifier() {
+        use super::Modifiers;
+        const LEFT: u32 = 0xff51;
+        let is = super::InputSynth::new().unwrap();
+        let before = is.stats().keys_pressed;
+        is.press_many_with_modifier(Modifiers::SHIFT, &[LEFT, LEFT, LEFT])
+            .unwrap();
+        assert_eq!(is.stats().keys_pressed - before, 4);
+        assert!(is.modifier_state().unwrap().depressed.is_empty());
+        // The modifier is released when a key can't be typed.
+        assert!(is
+            .press_many_with_modifier(Modifiers::SHIFT, &[0x1234_5678])
+            .is_err());
+        assert!(is.modifier_state().unwrap().depressed.is_empty());
+    }
+
+    #[test]
+    fn test_click_with_mods() {
+        use super::Modifiers;
+        let is = super::InputSynth::new().unwrap();
+        is.click_with_mods(1, 1, 1, Modifiers::CONTROL | Modifiers::SHIFT)
+            .unwrap();
+        assert!(is.modifier_state().unwrap().depressed.is_empty());
+        is.drag_with_mods((1, 1), (5, 5), 1, Modifiers::MOD1)
+            .unwrap();
+        assert!(is.modifier_state().unwrap().depressed.is_empty());
+        // Modifiers held before stay held.
+        is.key(super::CONTROL_L_KEYSYM, true).unwrap();
+        is.click_with_mods(1, 1, 1, Modifiers::CONTROL).unwrap();
+        let state = is.modifier_state().unwrap();
+        is.key(super::CONTROL_L_KEYSYM, false).unwrap();
+        assert_eq!(state.depressed, Modifiers::CONTROL);
+        // And the modifiers are released when the click fails.
+        assert!(is.click_with_mods(-1, 1, 1, Modifiers::SHIFT).is_err());
+        assert!(is.modifier_state().unwrap().depressed.is_empty());
+    }
+
+    #[test]
+    fn test_hold() {
+        let is = super::InputSynth::new().unwrap();
+        let control = super::CONTROL_L_KEYSYM;
+        let shift = 0xffe1;
+        let guard = is.hold(control).unwrap();
+        assert!(is.is_key_pressed(control).unwrap());
+        guard.release().unwrap();
+        assert!(!is.is_key_pressed(control).unwrap());
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _control = is.hold(control).unwrap();
+            let _shift = is.hold(shift).unwrap();
+            assert!(is.is_key_pressed(shift).unwrap());
+            panic!("leaving the scope early");
+        }));
+        assert!(result.is_err());
+        assert!(!is.is_key_pressed(control).unwrap());
+        assert!(!is.is_key_pressed(shift).unwrap());
+
+        // Shift is held by both guards, and only released with the second one.
+        let shift_guard = is.hold(shift).unwrap();
+        let a = is.hold(u32::from(b'A')).unwrap();
+        a.release().unwrap();
+        assert!(is.is_key_pressed(shift).unwrap());
+        let a = is.hold(u32::from(b'A')).unwrap();
+        shift_guard.release().unwrap();
+        assert!(is.is_key_pressed(shift).unwrap());
+        drop(a);
+        assert!(!is.is_key_pressed(shift).unwrap());
+    }
+
+    #[test]
+    fn test_release_all() {
+        use super::Modifiers;
+        let is = super::InputSynth::new().unwrap();
+        let control = super::CONTROL_L_KEYSYM;
+        let shift = 0xffe1;
+        let control_guard = is.hold(control).unwrap();
+        let shift_guard = is.with_modifiers(Modifiers::SHIFT).unwrap();
+        is.release_all().unwrap();
+        assert!(!is.is_key_pressed(control).unwrap());
+        assert!(!is.is_key_pressed(shift).unwrap());
+        // Held again, not by the old guards, which mustn't release it.
+        is.key(control, true).unwrap();
+        drop(control_guard);
+        shift_guard.release().unwrap();
+        assert!(is.is_key_pressed(control).unwrap());
+        is.key(control, false).unwrap();
+    }
+
+    #[test]
+    fn test_with_modifiers() {
+        use super::Modifiers;
+        let is = super::InputSynth::new().unwrap();
+        let control = is.with_modifiers(Modifiers::CONTROL).unwrap();
+        {
+            let _shift = is.with_modifiers(Modifiers::SHIFT).unwrap();
+            // Already held by the outer guard, so left alone by this one.
+            let inner = is.with_modifiers(Modifiers::CONTROL).unwrap();
+            drop(inner);
+            // 'A' needs Shift, which must not be released after typing it.
+            is.type_string("Aa").unwrap();
+            let state = is.modifier_state().unwrap();
+            assert_eq!(state.depressed, Modifiers::CONTROL | Modifiers::SHIFT);
+        }
+        assert_eq!(is.modifier_state().unwrap().depressed, Modifiers::CONTROL);
+        is.click_at(1, 1, 1).unwrap();
+        control.release().unwrap();
+        assert!(is.modifier_state().unwrap().depressed.is_empty());
+        // Once the guards are gone, typing presses Shift again.
+        let before = is.stats().keys_pressed;
+        is.type_string("A").unwrap();
+        assert_eq!(is.stats().keys_pressed - before, 2);
+    }
+
+    #[test]
+    fn test_send_shortcut() {
+        use super::{Key, Modifiers, ShortcutModifiers};
+        let is = super::InputSynth::new().unwrap();
+        let before = is.stats().keys_pressed;
+        // Shift is needed for 'A' anyway, and only pressed once.
+        is.send_shortcut(
+            ShortcutModifiers::CTRL | ShortcutModifiers::SHIFT,
+            Key::from('A'),
+        )
+        .unwrap();
+        assert_eq!(is.stats().keys_pressed - before, 3);
+        assert!(is.modifier_state().unwrap().depressed.is_empty());
+        assert!(is
+            .send_shortcut(Modifiers::CONTROL, Key::new(0x1234_5678))
+            .is_err());
+        assert!(is.modifier_state().unwrap().depressed.is_empty());
+    }
+
+    #[test]
+    fn test_is_key_pressed() {
+        let is = super::InputSynth::new().unwrap();
+        let control = super::CONTROL_L_KEYSYM;
+        assert!(!is.is_key_pressed(control).unwrap());
+        is.key(control, true).unwrap();
+        let pressed = is.is_key_pressed(control).unwrap();
+        let keycode = is
+            .find_key_sequence(super::Key::new(super::CONTROL_L_KEYSYM))
+            .unwrap()
+            .1;
+        let keycode_pressed = is.is_keycode_pressed(keycode as u8).unwrap();
+        is.key(control, false).unwrap();
+        assert!(pressed);
+        assert!(keycode_pressed);
+        assert!(!is.is_keycode_pressed(keycode as u8).unwrap());
+        assert!(is.is_key_pressed(0x1234_5678).is_err());
+    }
+
+    #[test]
+    fn test_keycodes_for_keysym() {
+        use xkbcommon::xkb;
+        let keymap = r#"
+            xkb_keymap {
+                xkb_keycodes { <AC01> = 38; <AC02> = 39; <FK01> = 67; };
+                xkb_types { include "basic" };
+                xkb_compatibility { include "basic" };
+                xkb_symbols {
+                    key <AC01> { [ a, A ] };
+                    key <AC02> { [ s, S ] };
+                    key <FK01> { [ F1, a ] };
+                };
+            };
+        "#;
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_string(
+            &context,
+            keymap.into(),
+            xkb::KEYMAP_FORMAT_TEXT_V1,
+            xkb::KEYMAP_COMPILE_NO_FLAGS,
+        )
+        .unwrap();
+        let state = super::KeymapState::new(&context, keymap);
+        assert_eq!(state.keycodes_for_keysym(b's'.into()), [39]);
+        let mut a = state.keycodes_for_keysym(b'a'.into());
+        a.sort_unstable();
+        assert_eq!(a, [38, 67]);
+        assert!(state.keycodes_for_keysym(b'z'.into()).is_empty());
+    }
+
+    #[test]
+    fn test_sync() {
+        use x11rb::protocol::xproto::ConnectionExt as _;
+        let is = super::InputSynth::new().unwrap();
+        is.type_string_synced("Hello").unwrap();
+        let keys = is.conn().query_keymap().unwrap().reply().unwrap().keys;
+        assert_eq!(keys, [0; 32]);
+    }
+
+    #[test]
+    fn test_screen_size() {
+        use x11rb::connection::Connection as _;
+        let is = super::InputSynth::new().unwrap();
+        let conn = is.conn();
+        let screen = &conn.setup().roots[is.screen_index()];
+        assert_eq!(is.root_window().unwrap(), screen.root);
+        let (width, height) = is.screen_size();
+        assert_eq!(
+            (width, height),
+            (screen.width_in_pixels, screen.height_in_pixels)
+        );
+        assert!(is.move_cursor(width as i16, 0).is_err());
+    }
+
+    #[test]
+    fn test_screens() {
+        use super::Error;
+        use x11rb::connection::Connection;
+        use x11rb::protocol::xproto::ConnectionExt;
+        let is = super::InputSynth::new().unwrap();
+        let count = is.screen_count();
+        assert!(count > is.screen_index());
+        assert!(matches!(
+            is.move_cursor_on_screen(count, 0, 0),
+            Err(Error::InvalidScreen(_))
+        ));
+        let last = count - 1;
+        let (width, _) = is.screen_size_of(last).unwrap();
+        assert!(matches!(
+            is.click_on_screen(last, width as i16, 0, 1, true),
+            Err(Error::CoordinatesOutOfBounds { .. })
+        ));
+        is.move_cursor_on_screen(last, 3, 4).unwrap();
+        let root = is.conn().setup().roots[last].root;
+        let pointer = is.conn().query_pointer(root).unwrap().reply().unwrap();
+        assert!(pointer.same_screen);
+        assert_eq!((pointer.root_x, pointer.root_y), (3, 4));
+        is.move_cursor(1, 1).unwrap();
+    }
+
+    #[test]
+    fn test_move_cursor_center() {
+        use x11rb::protocol::xproto::ConnectionExt;
+        let is = super::InputSynth::new().unwrap();
+        let (width, height) = is.screen_size();
+        is.move_cursor_center().unwrap();
+        let root = is.root_window().unwrap();
+        let pointer = is.conn().query_pointer(root).unwrap().reply().unwrap();
+        assert_eq!(
+            (pointer.root_x, pointer.root_y),
+            ((width / 2) as i16, (height / 2) as i16)
+        );
+    }
+
+    #[test]
+    fn test_list_devices() {
+        use super::DeviceUse;
+        let is = super::InputSynth::new().unwrap();
+        let devices = is.list_devices().unwrap();
+        let id = is.core_keyboard_device_id();
+        let keyboard = devices.iter().find(|d| d.id == id).unwrap();
+        assert!(matches!(
+            keyboard.device_use,
+            DeviceUse::Keyboard | DeviceUse::ExtensionKeyboard
+        ));
+        assert!(devices.iter().any(|d| d.device_use == DeviceUse::Pointer));
+    }
+
+    #[test]
+    fn test_set_coordinate_scale() {
+        use super::Error;
+        use x11rb::protocol::xproto::ConnectionExt;
+        let mut is = super::InputSynth::new().unwrap();
+        assert!(matches!(
+            is.set_coordinate_scale(0.0),
+            Err(Error::InvalidScale(_))
+        ));
+        assert!(is.detect_coordinate_scale().unwrap() > 0.0);
+        is.set_coordinate_scale(2.0).unwrap();
+        is.move_cursor(3, 4).unwrap();
+        let root = is.root_window().unwrap();
+        let pointer = || is.conn().query_pointer(root).unwrap().reply().unwrap();
+        let p = pointer();
+        assert_eq!((p.root_x, p.root_y), (6, 8));
+        is.move_cursor_raw(3, 4).unwrap();
+        let p = pointer();
+        assert_eq!((p.root_x, p.root_y), (3, 4));
+        assert_eq!(is.clone_connection().unwrap().coordinate_scale(), 2.0);
+    }
+
+    #[test]
+    fn test_synthesize_to_device() {
+        use super::InputEvent;
+        let is = super::InputSynth::new().unwrap();
+        let keyboard = is
+            .list_devices()
+            .unwrap()
+            .into_iter()
+            .find(|d| d.name.contains("XTEST keyboard"))
+            .unwrap();
+        let a = u32::from(b'a');
+        is.synthesize_to_device(keyboard.id, InputEvent::KeyPress { keysym: a })
+            .unwrap();
+        assert!(is.is_key_pressed(a).unwrap());
+        is.synthesize_to_device(keyboard.id, InputEvent::KeyRelease { keysym: a })
+            .unwrap();
+        assert!(!is.is_key_pressed(a).unwrap());
+    }
+
+    #[test]
+    fn test_type_line_breaks() {
+        let mut is = super::InputSynth::new().unwrap();
+        is.type_string("\r\n\t\n").unwrap();
+        is.set_newline_policy(super::NewlinePolicy::Skip);
+        assert_eq!(is.type_string_skip_missing("\n\r\n").1, []);
+    }
+
+    #[test]
+    fn test_press_numpad() {
+        use super::{Error, NumpadKey};
+        let is = super::InputSynth::new().unwrap();
+        let locked = is.modifier_state().unwrap().locked;
+        is.press_numpad(NumpadKey::Digit5).unwrap();
+        is.press_numpad(NumpadKey::Add).unwrap();
+        is.numpad_digit(9).unwrap();
+        assert!(matches!(is.numpad_digit(10), Err(Error::NotADigit(10))));
+        is.sync().unwrap();
+        assert_eq!(is.modifier_state().unwrap().locked, locked);
+    }
+
+    #[test]
+    fn test_lock_keys() {
+        let is = super::InputSynth::new().unwrap();
+        let caps_lock = is.caps_lock().unwrap();
+        is.set_caps_lock(!caps_lock).unwrap();
+        assert_eq!(is.caps_lock().unwrap(), !caps_lock);
+        is.set_caps_lock(caps_lock).unwrap();
+        assert_eq!(is.caps_lock().unwrap(), caps_lock);
+
+        let num_lock = is.num_lock().unwrap();
+        is.set_num_lock(num_lock).unwrap();
+        is.set_num_lock(!num_lock).unwrap();
+        assert_eq!(is.num_lock().unwrap(), !num_lock);
+        is.set_num_lock(num_lock).unwrap();
+    }
+
+    #[test]
+    fn test_type_char_compose() {
+        let is = super::InputSynth::new().unwrap();
+        // The server's keymap may have neither dead keys nor a Compose key.
+        match is.type_char_compose('é') {
+            Ok(()) | Err(super::Error::Untypeable('é') | super::Error::Unsupported(_)) => (),
+            Err(e) => panic!("{e}"),
+        }
+        assert!(matches!(
+            is.type_char_compose('\u{10ffff}'),
+            Err(super::Error::Untypeable(_) | super::Error::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_clone_connection() {
+        let is = super::InputSynth::new().unwrap();
+        let other = is.clone_connection().unwrap();
+        assert!(!std::sync::Arc::ptr_eq(&is.conn(), &other.conn()));
+        assert_eq!(other.screen_index(), is.screen_index());
+        other.type_string("a").unwrap();
+    }
+
+    #[test]
+    fn test_type_string_into_focused() {
+        let is = super::InputSynth::new().unwrap();
+        let focus = is.focused_window().unwrap();
+        is.type_string_into_focused(None, "a").unwrap();
+        is.type_string_into_focused(Some(focus), "a").unwrap();
+        let elsewhere = focus.wrapping_add(0x1234);
+        assert!(matches!(
+            is.type_string_into_focused(Some(elsewhere), "a"),
+            Err(super::Error::FocusChanged { expected, actual, .. })
+                if expected == elsewhere && actual == focus
+        ));
+    }
+
+    #[test]
+    fn test_activate_window() {
+        use x11rb::connection::Connection;
+        use x11rb::protocol::xproto::{ConnectionExt, CreateWindowAux, WindowClass};
+        let is = super::InputSynth::new().unwrap();
+        let conn = is.conn();
+        let window = conn.generate_id().unwrap();
+        conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            window,
+            is.root_window().unwrap(),
+            0,
+            0,
+            10,
+            10,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            x11rb::COPY_FROM_PARENT,
+            &CreateWindowAux::new(),
+        )
+        .unwrap();
+        conn.map_window(window).unwrap();
+        is.activate_window(window).unwrap();
+        assert_eq!(is.focused_window().unwrap(), window);
+        is.move_cursor_to_window_center(window).unwrap();
+        let pointer = conn.query_pointer(window).unwrap().reply().unwrap();
+        assert_eq!((pointer.win_x, pointer.win_y), (5, 5));
+        is.click_window_center(window, 1, true).unwrap();
+        is.click_window_center(window, 1, false).unwrap();
+        is.double_click_window_center(window).unwrap();
+        is.move_cursor_center_of_window(window, true).unwrap();
+        conn.unmap_window(window).unwrap();
+        assert!(matches!(
+            is.move_cursor_to_window_center(window),
+            Err(super::Error::WindowNotViewable(w)) if w == window
+        ));
+        conn.destroy_window(window).unwrap();
+        conn.flush().unwrap();
+    }
+
+    #[test]
+    fn test_focus_window() {
+        use x11rb::connection::Connection;
+        use x11rb::protocol::xproto::{ConnectionExt, CreateWindowAux, WindowClass};
+        let is = super::InputSynth::new().unwrap();
+        let conn = is.conn();
+        let window = conn.generate_id().unwrap();
+        conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            window,
+            is.root_window().unwrap(),
+            0,
+            0,
+            10,
+            10,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            x11rb::COPY_FROM_PARENT,
+            &CreateWindowAux::new(),
+        )
+        .unwrap();
+        conn.map_window(window).unwrap();
+        conn.get_input_focus().unwrap().reply().unwrap();
+        let previous = is.focused_window().unwrap();
+        is.type_string_to_window(window, "a").unwrap();
+        assert_eq!(is.focused_window().unwrap(), previous);
+        is.focus_window(window).unwrap();
+        assert_eq!(is.focused_window().unwrap(), window);
+        is.focus_window(previous).unwrap();
+        conn.destroy_window(window).unwrap();
+        conn.flush().unwrap();
+    }
+
+    #[test]
+    fn test_click_on_monitor() {
+        use super::Error;
+        let is = super::InputSynth::new().unwrap();
+        let monitors = match is.randr_screen_layout() {
+            Err(Error::Unsupported(_)) => return,
+            result => result.unwrap(),
+        };
+        let Some(monitor) = monitors.first() else {
+            return;
+        };
+        is.click_on_monitor(0, 0, 0, 1, true).unwrap();
+        is.click_on_monitor(0, 0, 0, 1, false).unwrap();
+        assert!(matches!(
+            is.click_on_monitor(0, monitor.width as i16, 0, 1, true),
+            Err(Error::CoordinatesOutOfBounds { .. })
+        ));
+        assert!(matches!(
+            is.click_on_monitor(monitors.len(), 0, 0, 1, true),
+            Err(Error::InvalidMonitor(_))
+        ));
+    }
+
+    #[test]
+    fn test_sender() {
+        use super::InputEvent;
+        let is = super::InputSynth::new().unwrap();
+        assert_eq!(is.drain_sender().unwrap(), 0);
+        let sender = is.make_sender();
+        let thread = {
+            let sender = sender.clone();
+            std::thread::spawn(move || {
+                sender.send(InputEvent::Motion { x: 1, y: 1 }).unwrap();
+            })
+        };
+        thread.join().unwrap();
+        sender.send(InputEvent::Motion { x: 2, y: 2 }).unwrap();
+        assert_eq!(is.drain_sender().unwrap(), 2);
+        let thread = std::thread::spawn(move || {
+            sender.send(InputEvent::Motion { x: 3, y: 3 }).unwrap();
+        });
+        is.run_sender_loop().unwrap();
+        thread.join().unwrap();
+        assert!(is
+            .make_sender()
+            .send(InputEvent::Motion { x: 4, y: 4 })
+            .is_ok());
+    }
+
+    #[test]
+    fn test_scroll_smooth() {
+        let is = super::InputSynth::new().unwrap();
+        is.scroll_smooth(10, 10, 2.0, 8, std::time::Duration::from_millis(1))
+            .unwrap();
+        is.scroll_smooth(10, 10, -1.0, 0, std::time::Duration::ZERO)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_send_synthetic_key_to() {
+        use x11rb::connection::Connection;
+        use x11rb::protocol::xproto::{ConnectionExt, CreateWindowAux, EventMask, WindowClass};
+        use x11rb::protocol::Event;
+        let is = super::InputSynth::new().unwrap();
+        let conn = super::XConnection::connect(None).unwrap().0;
+        let window = conn.generate_id().unwrap();
+        conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            window,
+            is.root_window().unwrap(),
+            0,
+            0,
+            10,
+            10,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            x11rb::COPY_FROM_PARENT,
+            &CreateWindowAux::new().event_mask(EventMask::KEY_PRESS | EventMask::BUTTON_RELEASE),
+        )
+        .unwrap();
+        conn.map_window(window).unwrap();
+        conn.get_input_focus().unwrap().reply().unwrap();
+        is.send_synthetic_key_to(window, b'A'.into(), true).unwrap();
+        is.send_synthetic_button_to(window, 3, 4, 1, false).unwrap();
+        match conn.wait_for_event().unwrap() {
+            Event::KeyPress(ev) => {
+                assert_eq!(ev.event, window);
+                assert_eq!(u16::from(ev.state), 1, "Shift is set for 'A'");
+            }
+            event => panic!("unexpected event {event:?}"),
+        }
+        match conn.wait_for_event().unwrap() {
+            Event::ButtonRelease(ev) => {
+                assert_eq!((ev.detail, ev.event_x, ev.event_y), (1, 3, 4));
+                assert_eq!(u16::from(ev.state), 1 << 8);
+            }
+            event => panic!("unexpected event {event:?}"),
+        }
+    }
+
+    #[test]
+    fn test_new_timeout() {
+        let is = super::InputSynth::new_timeout(std::time::Duration::from_secs(5)).unwrap();
+        is.ping().unwrap();
+        assert!(matches!(
+            super::InputSynth::new_timeout(std::time::Duration::ZERO),
+            Err(super::Error::Timeout(_))
+        ));
+    }
+
+    #[test]
+    fn test_grabs() {
+        use x11rb::protocol::xproto::{ConnectionExt, GrabMode};
+        let is = super::InputSynth::new().unwrap();
+        is.set_grab_control(true).unwrap();
+        let other = is.clone_connection().unwrap();
+        other
+            .conn()
+            .grab_keyboard(
+                false,
+                other.root_window().unwrap(),
+                x11rb::CURRENT_TIME,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+            )
+            .unwrap()
+            .reply()
+            .unwrap();
+        let grabs = is.check_grabs().unwrap();
+        drop(other);
+        assert_eq!(
+            grabs,
+            super::GrabState {
+                keyboard: true,
+                pointer: false
+            }
+        );
+        is.set_xtest_grab(false).unwrap();
+        assert!(!is
+            .impervious_to_grabs
+            .load(std::sync::atomic::Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_fail_on_grab() {
+        use x11rb::protocol::xproto::{ConnectionExt, GrabMode};
+        let mut is = super::InputSynth::new().unwrap();
+        is.set_fail_on_grab(true);
+        is.type_string("a").unwrap();
+        let other = is.clone_connection().unwrap();
+        other
+            .conn()
+            .grab_keyboard(
+                false,
+                other.root_window().unwrap(),
+                x11rb::CURRENT_TIME,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+            )
+            .unwrap()
+            .reply()
+            .unwrap();
+        let typed = is.type_string("a");
+        let pressed = is.key(b'a'.into(), true);
+        let clicked = is.click(0, 0, 1, true).and(is.click(0, 0, 1, false));
+        drop(other);
+        assert!(matches!(
+            typed,
+            Err(super::Error::InputGrabbed { keyboard: true, .. })
+        ));
+        assert!(matches!(
+            pressed,
+            Err(super::Error::InputGrabbed { keyboard: true, .. })
+        ));
+        clicked.unwrap();
+    }
+
+    #[test]
+    fn test_percent_coordinates() {
+        let is = super::InputSynth::new().unwrap();
+        let (width, height) = is.screen_size();
+        assert_eq!(is.fraction_to_pixels(0.0, 0.0).unwrap(), (0, 0));
+        assert_eq!(
+            is.fraction_to_pixels(1.0, 1.0).unwrap(),
+            (width as i16 - 1, height as i16 - 1)
+        );
+        is.move_cursor_percent(0.5, 0.5).unwrap();
+        assert!(matches!(
+            is.click_at_percent(1.5, 0.0, 1, true),
+            Err(super::Error::InvalidFraction(f)) if f == 1.5
+        ));
+        assert!(is.move_cursor_percent(f64::NAN, 0.0).is_err());
+    }
+
+    #[test]
+    fn test_paste_text() {
+        let is = super::InputSynth::new().unwrap();
+        let other = is.clone_connection().unwrap();
+        // We only answer while pasting, so reading can time out in between.
+        let reader = std::thread::spawn(move || {
+            (0..)
+                .map(|_| other.get_clipboard().unwrap_or_default())
+                .find(|text| text == "pasted")
+        });
+        let ctrl_v: super::KeyCombo = "ctrl+v".parse().unwrap();
+        while !reader.is_finished() {
+            match is.paste_text_with("pasted", &ctrl_v, false) {
+                Ok(()) | Err(super::Error::Timeout(_)) => (),
+                Err(e) => panic!("{e}"),
+            }
+        }
+        assert_eq!(reader.join().unwrap().as_deref(), Some("pasted"));
+    }
+
+    #[test]
+    fn test_paste_timeout() {
+        let mut is = super::InputSynth::new().unwrap();
+        is.set_selection_timeout(std::time::Duration::from_millis(50));
+        // Nothing on the test server reads the clipboard.
+        let ctrl_v: super::KeyCombo = "ctrl+v".parse().unwrap();
+        assert!(matches!(
+            is.paste_text_with("unread", &ctrl_v, true),
+            Err(super::Error::Timeout(_))
+        ));
+    }
+
+    #[test]
+    fn test_large_clipboard() {
+        let is = super::InputSynth::new().unwrap();
+        let other = is.clone_connection().unwrap();
+        // Larger than a property is written at once, so it's sent with INCR.
+        let text = "0123456789abcdef".repeat(1 << 18);
+        // Not `set_clipboard`, which pastes too.
+        is.set_selection(|s| s.clipboard, &text).unwrap();
+        let reader = std::thread::spawn(move || other.get_clipboard());
+        // The owner answers while handling events, which every call does first.
+        while !reader.is_finished() {
+            is.sync().unwrap();
+        }
+        assert!(reader.join().unwrap().unwrap() == text);
+    }
+
+    #[test]
+    fn test_request_checking() {
+        use super::{Error, RequestChecking};
+        use x11rb::protocol::xproto;
+        let mut is = super::InputSynth::new().unwrap();
+        // Keycodes below 8 don't exist.
+        is.fake_input(xproto::KEY_PRESS_EVENT, 1, 0, 0).unwrap();
+        assert!(matches!(
+            is.sync(),
+            Err(Error::FakeInputFailed { input, .. }) if input.detail == 1
+        ));
+        is.sync().unwrap();
+        is.set_request_checking(RequestChecking::Checked);
+        assert!(matches!(
+            is.fake_input(xproto::KEY_PRESS_EVENT, 1, 0, 0),
+            Err(Error::FakeInputFailed { input, .. }) if input.detail == 1
+        ));
+        is.move_cursor(1, 1).unwrap();
+    }
+
+    #[test]
+    fn test_fake_input_error_message() {
+        use super::RequestChecking;
+        let mut is = super::InputSynth::new().unwrap();
+        for checking in [RequestChecking::Checked, RequestChecking::Unchecked] {
+            is.set_request_checking(checking);
+            // There is no button 0.
+            let error = is
+                .click(1, 1, 0, true)
+                .and_then(|()| is.sync())
+                .unwrap_err();
+            let message = error.to_string();
+            assert!(
+                message.starts_with(
+                    "click failed, the X server rejected the press of button 0: Value error \
+                     (code 2, bad value 0, opcode "
+                ),
+                "{message}"
+            );
+        }
+    }
+
+    /// Run with `--ignored` to compare the cost of checking every event.
+    #[test]
+    #[ignore]
+    fn bench_request_checking() {
+        use super::RequestChecking;
+        let mut is = super::InputSynth::new().unwrap();
+        for checking in [RequestChecking::Unchecked, RequestChecking::Checked] {
+            is.set_request_checking(checking);
+            let start = std::time::Instant::now();
+            for i in 0..1000 {
+                is.move_cursor(i % 100, i % 100).unwrap();
+            }
+            is.sync().unwrap();
+            println!("{checking:?}: 1000 motions in {:?}", start.elapsed());
+        }
+    }
 }
@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use xkbcommon::xkb;
+
+use crate::{
+    lock, newline, Error, InputEvent, InputSynth, Key, KeymapState, NewlinePolicy, Result,
+};
+
+/// An event recorded by [`MockInputSynth`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntheticEvent {
+    /// Which call on the mock produced this event, counting from 0.
+    pub call: usize,
+    /// When the event was recorded, relative to the creation of the mock.
+    pub time: Duration,
+    pub event: InputEvent,
+    /// For key events, the keycode producing the keysym in a US keyboard layout. `None` for
+    /// other events, or if the layout has no such key.
+    pub keycode: Option<u32>,
+    /// For key events, the keycodes of the modifiers that are held to produce the keysym.
+    pub modifiers: Vec<u32>,
+}
+
+struct MockState {
+    events: Vec<SyntheticEvent>,
+    calls: usize,
+    failures: HashMap<usize, Error>,
+    /// `None` if the XKB data files aren't installed.
+    keymap: Option<KeymapState>,
+}
+
+/// Stands in for an [`InputSynth`] in tests that don't have an X server. Instead of being sent
+/// anywhere, events are recorded and can be retrieved with [`Self::take_recorded_events`] or
+/// [`Self::take_synthetic_events`]. Tests of error handling can make chosen calls fail with
+/// [`Self::fail_call`].
+///
+/// Keys are looked up in a US keyboard layout, instead of the keymap of an X server.
+pub struct MockInputSynth {
+    state: Mutex<MockState>,
+    start: Instant,
+    newline_policy: NewlinePolicy,
+}
+
+impl Default for MockInputSynth {
+    fn default() -> Self {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_names(&context, "", "", "us", "", None, 0)
+            .map(|keymap| KeymapState::new(&context, keymap));
+        Self {
+            state: Mutex::new(MockState {
+                events: Vec::new(),
+                calls: 0,
+                failures: HashMap::new(),
+                keymap,
+            }),
+            start: Instant::now(),
+            newline_policy: NewlinePolicy::default(),
+        }
+    }
+}
+
+impl MockInputSynth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take all events recorded so far, leaving the record empty.
+    pub fn take_recorded_events(&self) -> Vec<InputEvent> {
+        self.take_synthetic_events()
+            .into_iter()
+            .map(|e| e.event)
+            .collect()
+    }
+
+    /// Like [`Self::take_recorded_events`], with details about each event.
+    pub fn take_synthetic_events(&self) -> Vec<SyntheticEvent> {
+        std::mem::take(&mut lock(&self.state).events)
+    }
+
+    /// Make call number `call`, counting from 0, fail with `error` instead of recording
+    /// anything. Each public method counts as one call.
+    pub fn fail_call(&self, call: usize, error: Error) {
+        lock(&self.state).failures.insert(call, error);
+    }
+
+    /// How many calls were made so far.
+    pub fn call_count(&self) -> usize {
+        lock(&self.state).calls
+    }
+
+    /// Count a call, and run it unless a failure was injected for it.
+    fn call(&self, f: impl FnOnce(&mut MockState, usize) -> Result<()>) -> Result<()> {
+        let mut state = lock(&self.state);
+        let call = state.calls;
+        state.calls += 1;
+        if let Some(error) = state.failures.remove(&call) {
+            return Err(error);
+        }
+        f(&mut state, call)
+    }
+
+    fn record(&self, state: &mut MockState, call: usize, event: InputEvent) {
+        let keysym = match event {
+            InputEvent::KeyPress { keysym } | InputEvent::KeyRelease { keysym } => Some(keysym),
+            _ => None,
+        };
+        let sequence =
+            keysym.and_then(|keysym| state.keymap.as_ref()?.find_key_sequence(Key::new(keysym)));
+        let (modifiers, keycode) = match sequence {
+            Some((modifiers, keycode)) => (modifiers, Some(keycode)),
+            None => (Vec::new(), None),
+        };
+        state.events.push(SyntheticEvent {
+            call,
+            time: self.start.elapsed(),
+            event,
+            keycode,
+            modifiers,
+        });
+    }
+
+    fn tap(&self, state: &mut MockState, call: usize, keysym: u32) {
+        self.record(state, call, InputEvent::KeyPress { keysym });
+        self.record(state, call, InputEvent::KeyRelease { keysym });
+    }
+
+    /// See [`InputSynth::key`].
+    pub fn key(&self, keysym: u32, press: bool) -> Result<()> {
+        self.call(|state, call| {
+            let event = if press {
+                InputEvent::KeyPress { keysym }
+            } else {
+                InputEvent::KeyRelease { keysym }
+            };
+            self.record(state, call, event);
+            Ok(())
+        })
+    }
+
+    /// See [`InputSynth::click`].
+    pub fn click(&self, x: i16, y: i16, button: u8, press: bool) -> Result<()> {
+        self.call(|state, call| {
+            let event = if press {
+                InputEvent::ButtonPress { x, y, button }
+            } else {
+                InputEvent::ButtonRelease { x, y, button }
+            };
+            self.record(state, call, event);
+            Ok(())
+        })
+    }
+
+    /// See [`InputSynth::move_cursor`].
+    pub fn move_cursor(&self, x: i16, y: i16) -> Result<()> {
+        self.call(|state, call| {
+            self.record(state, call, InputEvent::Motion { x, y });
+            Ok(())
+        })
+    }
+
+    /// See [`InputSynth::click_at`].
+    pub fn click_at(&self, x: i16, y: i16, button: u8) -> Result<()> {
+        self.call(|state, call| {
+            self.record(state, call, InputEvent::Motion { x, y });
+            self.record(state, call, InputEvent::ButtonPress { x, y, button });
+            self.record(state, call, InputEvent::ButtonRelease { x, y, button });
+            Ok(())
+        })
+    }
+
+    /// See [`InputSynth::ascii_char`].
+    pub fn ascii_char(&self, ch: u8) -> Result<()> {
+        self.call(|state, call| {
+            self.tap(state, call, InputSynth::ascii_keysym(ch));
+            Ok(())
+        })
+    }
+
+    /// See [`InputSynth::set_newline_policy`].
+    pub fn set_newline_policy(&mut self, policy: NewlinePolicy) {
+        self.newline_policy = policy;
+    }
+
+    /// See [`InputSynth::type_string`].
+    pub fn type_string(&self, s: &str) -> Result<()> {
+        self.call(|state, call| {
+            for ch in newline::normalize_line_breaks(s) {
+                if !ch.is_ascii() {
+                    return Err(Error::Untypeable(ch));
+                }
+                if let Some(keysym) = InputSynth::char_keysym(ch, self.newline_policy) {
+                    self.tap(state, call, keysym);
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MockInputSynth;
+    use crate::{Error, InputBackend, InputEvent, NewlinePolicy};
+
+    #[test]
+    fn test_mock_records_events() {
+        let mock = MockInputSynth::new();
+        mock.click_at(10, 20, 1).unwrap();
+        mock.type_string("a").unwrap();
+        assert!(matches!(mock.type_string("é"), Err(Error::Untypeable('é'))));
+        assert_eq!(
+            mock.take_recorded_events(),
+            [
+                InputEvent::Motion { x: 10, y: 20 },
+                InputEvent::ButtonPress {
+                    x: 10,
+                    y: 20,
+                    button: 1
+                },
+                InputEvent::ButtonRelease {
+                    x: 10,
+                    y: 20,
+                    button: 1
+                },
+                InputEvent::KeyPress { keysym: 0x61 },
+                InputEvent::KeyRelease { keysym: 0x61 },
+            ]
+        );
+        assert!(mock.take_recorded_events().is_empty());
+    }
+
+    #[test]
+    fn test_mock_details_and_failures() {
+        let mock = MockInputSynth::new();
+        let backend: &dyn InputBackend = &mock;
+        mock.fail_call(1, Error::Unsupported("testing"));
+        backend.key('A' as u32, true).unwrap();
+        assert!(matches!(
+            backend.move_cursor(0, 0),
+            Err(Error::Unsupported("testing"))
+        ));
+        backend.move_cursor(1, 1).unwrap();
+        assert_eq!(mock.call_count(), 3);
+
+        let events = mock.take_synthetic_events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].call, 0);
+        assert_eq!(events[0].modifiers.len(), 1, "Shift is needed for 'A'");
+        assert!(events[0].keycode.is_some());
+        assert_eq!(events[1].call, 2);
+        assert_eq!(events[1].event, InputEvent::Motion { x: 1, y: 1 });
+        assert!(events[0].time <= events[1].time);
+    }
+
+    #[test]
+    fn test_mock_newline_policy() {
+        let mut mock = MockInputSynth::new();
+        for (policy, keysym) in [
+            (NewlinePolicy::Return, Some(0xff0d)),
+            (NewlinePolicy::KpEnter, Some(0xff8d)),
+            (NewlinePolicy::LinefeedKeysym, Some(0xff0a)),
+            (NewlinePolicy::Skip, None),
+        ] {
+            mock.set_newline_policy(policy);
+            mock.type_string("\t\r\n").unwrap();
+            let mut expected = vec![
+                InputEvent::KeyPress { keysym: 0xff09 },
+                InputEvent::KeyRelease { keysym: 0xff09 },
+            ];
+            if let Some(keysym) = keysym {
+                expected.push(InputEvent::KeyPress { keysym });
+                expected.push(InputEvent::KeyRelease { keysym });
+            }
+            assert_eq!(mock.take_recorded_events(), expected, "{policy:?}");
+        }
+    }
+}
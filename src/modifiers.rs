@@ -0,0 +1,215 @@
+use std::fmt;
+use std::ops::BitOr;
+
+/// A set of the eight real X modifiers, as in the `state` of core input events.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers(u8);
+
+impl Modifiers {
+    pub const SHIFT: Self = Self(1 << 0);
+    pub const LOCK: Self = Self(1 << 1);
+    pub const CONTROL: Self = Self(1 << 2);
+    pub const MOD1: Self = Self(1 << 3);
+    pub const MOD2: Self = Self(1 << 4);
+    pub const MOD3: Self = Self(1 << 5);
+    pub const MOD4: Self = Self(1 << 6);
+    pub const MOD5: Self = Self(1 << 7);
+
+    /// The names used by the X server, in bit order.
+    const NAMES: [&'static str; 8] = [
+        "Shift", "Lock", "Control", "Mod1", "Mod2", "Mod3", "Mod4", "Mod5",
+    ];
+
+    pub fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether all modifiers of `other` are in `self`.
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Modifiers {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl fmt::Debug for Modifiers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set()
+            .entries(
+                Self::NAMES
+                    .iter()
+                    .enumerate()
+                    .filter(|&(i, _)| self.0 & (1 << i) != 0)
+                    .map(|(_, name)| name),
+            )
+            .finish()
+    }
+}
+
+bitflags::bitflags! {
+    /// A set of modifiers by what they mean, like Ctrl or Alt, for
+    /// [`crate::InputSynth::send_shortcut`]. Unlike [`Modifiers`], which are the real X
+    /// modifiers, these are found in the keymap by name, since e.g. Alt isn't the same real
+    /// modifier in every keymap.
+    ///
+    /// Converting between the two assumes the usual assignment: Alt is Mod1, Super is Mod4.
+    /// Meta and Hyper have no usual real modifier, nor do Lock, Mod2 and Mod3 have a meaning
+    /// here, so they are left out.
+    #[derive(Default)]
+    pub struct ShortcutModifiers: u8 {
+        const SHIFT = 1 << 0;
+        const CTRL = 1 << 1;
+        const ALT = 1 << 2;
+        const SUPER = 1 << 3;
+        const META = 1 << 4;
+        const HYPER = 1 << 5;
+        const MOD5 = 1 << 6;
+    }
+}
+
+impl ShortcutModifiers {
+    /// In bit order: the XKB modifier names to look for in the keymap, preferred first, and the
+    /// real modifier it usually is. Keymaps built from the core protocol have no virtual
+    /// modifiers, so those fall back to the real modifier.
+    const NAMES: [(&'static [&'static str], Option<Modifiers>); 7] = [
+        (&["Shift"], Some(Modifiers::SHIFT)),
+        (&["Control"], Some(Modifiers::CONTROL)),
+        (&["Alt", "Mod1"], Some(Modifiers::MOD1)),
+        (&["Super", "Mod4"], Some(Modifiers::MOD4)),
+        (&["Meta"], None),
+        (&["Hyper"], None),
+        (&["Mod5"], Some(Modifiers::MOD5)),
+    ];
+
+    /// Each modifier in the set on its own, in bit order.
+    pub fn iter(self) -> impl Iterator<Item = Self> {
+        (0..Self::NAMES.len())
+            .map(|i| Self::from_bits_truncate(1 << i))
+            .filter(move |&m| self.contains(m))
+    }
+
+    /// The XKB names of a single modifier, see `NAMES`.
+    pub(crate) fn xkb_names(self) -> &'static [&'static str] {
+        Self::NAMES
+            .iter()
+            .enumerate()
+            .find(|&(i, _)| self.bits() == 1 << i)
+            .map_or(&[][..], |(_, &(names, _))| names)
+    }
+}
+
+impl From<Modifiers> for ShortcutModifiers {
+    fn from(modifiers: Modifiers) -> Self {
+        Self::NAMES
+            .iter()
+            .enumerate()
+            .filter(|&(_, &(_, real))| real.is_some_and(|real| modifiers.contains(real)))
+            .fold(Self::empty(), |set, (i, _)| {
+                set | Self::from_bits_truncate(1 << i)
+            })
+    }
+}
+
+impl From<ShortcutModifiers> for Modifiers {
+    fn from(modifiers: ShortcutModifiers) -> Self {
+        modifiers
+            .iter()
+            .filter_map(|m| {
+                let i = m.bits().trailing_zeros() as usize;
+                ShortcutModifiers::NAMES[i].1
+            })
+            .fold(Modifiers::default(), |set, real| set | real)
+    }
+}
+
+/// The modifiers of the keyboard, see [`crate::InputSynth::modifier_state`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ModifierState {
+    /// Modifiers of keys currently held down, by the user or by us.
+    pub depressed: Modifiers,
+    /// Modifiers of sticky keys, which apply to the next key press.
+    pub latched: Modifiers,
+    /// Modifiers that are on until their key is pressed again, like Caps Lock.
+    pub locked: Modifiers,
+}
+
+impl ModifierState {
+    /// The modifiers that apply to the next key press.
+    pub fn effective(&self) -> Modifiers {
+        self.depressed | self.latched | self.locked
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ModifierState, Modifiers, ShortcutModifiers};
+
+    #[test]
+    fn test_modifiers() {
+        let state = ModifierState {
+            depressed: Modifiers::SHIFT | Modifiers::CONTROL,
+            latched: Modifiers::default(),
+            locked: Modifiers::from_bits(0b10),
+        };
+        assert!(state
+            .effective()
+            .contains(Modifiers::LOCK | Modifiers::SHIFT));
+        assert!(!state.effective().contains(Modifiers::MOD1));
+        assert!(state.latched.is_empty());
+        assert_eq!(state.effective().bits(), 0b111);
+        assert_eq!(format!("{:?}", state.depressed), r#"{"Shift", "Control"}"#);
+    }
+
+    #[test]
+    fn test_shortcut_modifiers() {
+        let mut mods = ShortcutModifiers::CTRL | ShortcutModifiers::ALT;
+        assert_eq!(
+            mods.iter().collect::<Vec<_>>(),
+            [ShortcutModifiers::CTRL, ShortcutModifiers::ALT]
+        );
+        assert_eq!(ShortcutModifiers::ALT.xkb_names(), ["Alt", "Mod1"]);
+        assert!(mods.xkb_names().is_empty());
+        assert_eq!(
+            ShortcutModifiers::from_bits_truncate(0xff).iter().count(),
+            7
+        );
+        mods |= ShortcutModifiers::SHIFT;
+        assert_eq!(
+            mods & !ShortcutModifiers::CTRL,
+            ShortcutModifiers::ALT | ShortcutModifiers::SHIFT
+        );
+        assert!(ShortcutModifiers::empty().is_empty());
+    }
+
+    #[test]
+    fn test_modifier_conversions() {
+        let shortcut = ShortcutModifiers::CTRL | ShortcutModifiers::SUPER | ShortcutModifiers::META;
+        assert_eq!(
+            Modifiers::from(shortcut),
+            Modifiers::CONTROL | Modifiers::MOD4
+        );
+        let real = Modifiers::SHIFT | Modifiers::MOD1 | Modifiers::MOD2;
+        assert_eq!(
+            ShortcutModifiers::from(real),
+            ShortcutModifiers::SHIFT | ShortcutModifiers::ALT
+        );
+        assert_eq!(
+            Modifiers::from(ShortcutModifiers::from(Modifiers::MOD5)),
+            Modifiers::MOD5
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use x11rb::connection::Connection;
+use x11rb::protocol::randr::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::{self, ConnectionExt as _};
+
+use crate::{Error, Result};
+
+/// Monitors are described with `RRGetMonitors`, which is new in RandR 1.5.
+const MIN_VERSION: (u32, u32) = (1, 5);
+
+/// Pixels per millimeter at 96 DPI, the density of scale 1.
+const BASE_DENSITY: f64 = 96.0 / 25.4;
+
+/// A monitor showing part of the screen, see [`crate::InputSynth::randr_screen_layout`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Monitor {
+    /// The name of the output, like `"HDMI-1"`.
+    pub name: String,
+    /// Position of the top-left corner on the screen.
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    /// Pixel density relative to 96 DPI, from the physical size the monitor reports. 1 if it
+    /// doesn't report one.
+    pub scale: f64,
+    pub primary: bool,
+}
+
+impl Monitor {
+    /// Screen coordinates of the center, rounded down for odd sizes.
+    pub fn center(&self) -> (i16, i16) {
+        (
+            self.x.saturating_add((self.width / 2) as i16),
+            self.y.saturating_add((self.height / 2) as i16),
+        )
+    }
+
+    /// Screen coordinates of `(x, y)` relative to the monitor, or `None` if that's outside it.
+    pub fn to_screen(&self, x: i16, y: i16) -> Option<(i16, i16)> {
+        if x < 0 || y < 0 || x as u16 >= self.width || y as u16 >= self.height {
+            return None;
+        }
+        Some((self.x.checked_add(x)?, self.y.checked_add(y)?))
+    }
+}
+
+/// The active monitors on the screen of `root`.
+pub(crate) fn monitors(conn: &impl Connection, root: xproto::Window) -> Result<Vec<Monitor>> {
+    if conn
+        .extension_information(randr::X11_EXTENSION_NAME)?
+        .is_none()
+    {
+        return Err(Error::Unsupported("listing monitors without RandR"));
+    }
+    let version = conn
+        .randr_query_version(MIN_VERSION.0, MIN_VERSION.1)?
+        .reply()?;
+    if (version.major_version, version.minor_version) < MIN_VERSION {
+        return Err(Error::Unsupported("listing monitors before RandR 1.5"));
+    }
+    let reply = conn.randr_get_monitors(root, true)?.reply()?;
+    reply
+        .monitors
+        .iter()
+        .map(|info| {
+            let name = conn.get_atom_name(info.name)?.reply()?.name;
+            Ok(Monitor {
+                name: String::from_utf8_lossy(&name).into_owned(),
+                x: info.x,
+                y: info.y,
+                width: info.width,
+                height: info.height,
+                scale: scale(info.width, info.width_in_millimeters),
+                primary: info.primary,
+            })
+        })
+        .collect()
+}
+
+fn scale(pixels: u16, millimeters: u32) -> f64 {
+    if millimeters == 0 {
+        return 1.0;
+    }
+    f64::from(pixels) / f64::from(millimeters) / BASE_DENSITY
+}
+
+#[cfg(test)]
+mod test {
+    use super::{scale, Monitor};
+
+    #[test]
+    fn test_monitor() {
+        let monitor = Monitor {
+            name: "HDMI-1".into(),
+            x: 1920,
+            y: 0,
+            width: 3840,
+            height: 2160,
+            scale: scale(3840, 508),
+            primary: false,
+        };
+        assert_eq!(monitor.to_screen(0, 0), Some((1920, 0)));
+        assert_eq!(monitor.to_screen(3839, 2159), Some((5759, 2159)));
+        assert_eq!(monitor.to_screen(3840, 0), None);
+        assert_eq!(monitor.to_screen(-1, 0), None);
+        assert_eq!(monitor.center(), (3840, 1080));
+        assert!((monitor.scale - 2.0).abs() < 0.01);
+        assert_eq!(scale(1920, 0), 1.0);
+    }
+}
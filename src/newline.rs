@@ -0,0 +1,63 @@
+use crate::Key;
+
+/// Which key typing a line break presses, see [`crate::InputSynth::set_newline_policy`].
+///
+/// `"\r\n"` and a lone `'\r'` are line breaks too, so text with Windows or old Mac line endings
+/// types the same as with `'\n'`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NewlinePolicy {
+    /// The main Return key, what terminals and most text fields expect.
+    #[default]
+    Return,
+    /// The Enter key of the keypad, which some applications treat differently from Return.
+    KpEnter,
+    /// The `Linefeed` keysym, which few keymaps have a key for.
+    LinefeedKeysym,
+    /// Leave line breaks out.
+    Skip,
+}
+
+impl NewlinePolicy {
+    /// The key typed for a line break, or `None` if line breaks are skipped.
+    pub(crate) fn key(self) -> Option<Key> {
+        match self {
+            NewlinePolicy::Return => Some(Key::RETURN),
+            NewlinePolicy::KpEnter => Some(Key::KP_ENTER),
+            NewlinePolicy::LinefeedKeysym => Some(Key::new(0xff0a)),
+            NewlinePolicy::Skip => None,
+        }
+    }
+}
+
+/// The characters of `s`, with each `"\r\n"` and lone `'\r'` turned into a single `'\n'`.
+pub(crate) fn normalize_line_breaks(s: &str) -> impl Iterator<Item = char> + '_ {
+    let mut chars = s.chars().peekable();
+    std::iter::from_fn(move || {
+        let ch = chars.next()?;
+        if ch != '\r' {
+            return Some(ch);
+        }
+        chars.next_if_eq(&'\n');
+        Some('\n')
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{normalize_line_breaks, NewlinePolicy};
+
+    #[test]
+    fn test_newline_key() {
+        let keysym = |policy: NewlinePolicy| policy.key().map(|key| key.keysym());
+        assert_eq!(keysym(NewlinePolicy::default()), Some(0xff0d));
+        assert_eq!(keysym(NewlinePolicy::KpEnter), Some(0xff8d));
+        assert_eq!(keysym(NewlinePolicy::LinefeedKeysym), Some(0xff0a));
+        assert_eq!(keysym(NewlinePolicy::Skip), None);
+    }
+
+    #[test]
+    fn test_normalize_line_breaks() {
+        let normalized: String = normalize_line_breaks("a\r\nb\rc\n\r\n\td\n").collect();
+        assert_eq!(normalized, "a\nb\nc\n\n\td\n");
+    }
+}
@@ -0,0 +1,103 @@
+use std::time::{Duration, Instant};
+
+/// Limits on how fast synthesized events are pushed to the X server. The default places no
+/// limit at all.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Never send more than this many events per second.
+    pub max_events_per_second: Option<u32>,
+    /// Flush the connection once this many events are queued without being flushed.
+    pub max_pending: Option<u32>,
+}
+
+impl RateLimit {
+    pub fn is_unlimited(&self) -> bool {
+        self.max_events_per_second.is_none() && self.max_pending.is_none()
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct RateLimiter {
+    limit: RateLimit,
+    last_event: Option<Instant>,
+    pending: u32,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn limit(&self) -> RateLimit {
+        self.limit
+    }
+
+    /// Decide what has to happen before the next event is sent. Returns whether the connection
+    /// should be flushed and how long to sleep afterwards.
+    pub(crate) fn next_event(&mut self, now: Instant) -> (bool, Duration) {
+        if self.limit.is_unlimited() {
+            return (false, Duration::ZERO);
+        }
+        let mut flush = self
+            .limit
+            .max_pending
+            .is_some_and(|max| self.pending >= max.max(1));
+        let mut wait = Duration::ZERO;
+        if let (Some(eps), Some(last)) = (self.limit.max_events_per_second, self.last_event) {
+            let interval = Duration::from_secs(1) / eps.max(1);
+            let due = last + interval;
+            if due > now {
+                wait = due - now;
+                // No point in sleeping while the previous events are still sitting in our buffer
+                flush = true;
+            }
+        }
+        if flush {
+            self.pending = 0;
+        }
+        self.pending += 1;
+        self.last_event = Some(now + wait);
+        (flush, wait)
+    }
+
+    /// The connection was flushed by someone else.
+    pub(crate) fn flushed(&mut self) {
+        self.pending = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RateLimit, RateLimiter};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_rate_limiter() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new(RateLimit::default());
+        for _ in 0..10 {
+            assert_eq!(limiter.next_event(now), (false, Duration::ZERO));
+        }
+
+        let mut limiter = RateLimiter::new(RateLimit {
+            max_pending: Some(2),
+            ..Default::default()
+        });
+        let flushes: Vec<_> = (0..5).map(|_| limiter.next_event(now).0).collect();
+        assert_eq!(flushes, [false, false, true, false, true]);
+
+        let mut limiter = RateLimiter::new(RateLimit {
+            max_events_per_second: Some(100),
+            ..Default::default()
+        });
+        assert_eq!(limiter.next_event(now), (false, Duration::ZERO));
+        assert_eq!(limiter.next_event(now), (true, Duration::from_millis(10)));
+        assert_eq!(
+            limiter.next_event(now + Duration::from_millis(50)),
+            (false, Duration::ZERO)
+        );
+    }
+}
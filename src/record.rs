@@ -0,0 +1,595 @@
+//! Recording real input with the RECORD extension, to be replayed as a [`Script`].
+
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::errors::ConnectionError;
+use x11rb::protocol::record::{self, ConnectionExt as _};
+use x11rb::protocol::xproto;
+use x11rb::x11_utils::TryParse;
+
+use crate::{lock, Action, Error, InputSynth, KeyCombo, KeymapState, Result, Script, XConnection};
+
+/// Categories of `EnableContext` replies.
+const FROM_SERVER: u8 = 0;
+const START_OF_DATA: u8 = 4;
+
+/// How a [`Recorder`] times the actions it records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RecordTiming {
+    /// Wait between actions as long as the recorded user did.
+    #[default]
+    Recorded,
+    /// Wait the same duration between all actions. Zero leaves out the waits.
+    Fixed(Duration),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Input {
+    Key {
+        keycode: u32,
+        press: bool,
+    },
+    Button {
+        x: i16,
+        y: i16,
+        button: u8,
+        press: bool,
+    },
+    Motion {
+        x: i16,
+        y: i16,
+    },
+}
+
+/// An event captured from the server, with its server time in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Recorded {
+    time: u32,
+    input: Input,
+}
+
+/// Records the keyboard and pointer input of all clients on an X server, including that of
+/// real devices, and turns it into a [`Script`].
+///
+/// Pointer motion is coalesced, a key or button pressed and released with nothing in between
+/// becomes a single [`Action::Key`] or [`Action::Click`], and keycodes are turned back into the
+/// keysyms they produce without modifiers. Replaying a recording that pressed Shift and `a`
+/// therefore types `A`.
+pub struct Recorder {
+    /// Controls the recording. The recorded data comes in on another connection, which is
+    /// blocked while recording.
+    control: XConnection,
+    display: Option<String>,
+    context: record::Context,
+    keymap: KeymapState,
+    timing: RecordTiming,
+    stop_hotkey: Option<KeyCombo>,
+    recording: Option<JoinHandle<Result<Vec<Recorded>>>>,
+}
+
+/// Parse the events in the data of a `FromServer` reply.
+fn parse_events(mut data: &[u8], events: &mut Vec<Recorded>) -> Result<()> {
+    while let Some(&response_type) = data.first() {
+        let (recorded, remaining) = match response_type & 0x7f {
+            xproto::KEY_PRESS_EVENT | xproto::KEY_RELEASE_EVENT => {
+                let (event, remaining) =
+                    xproto::KeyPressEvent::try_parse(data).map_err(ConnectionError::from)?;
+                let input = Input::Key {
+                    keycode: event.detail.into(),
+                    press: event.response_type & 0x7f == xproto::KEY_PRESS_EVENT,
+                };
+                (Some((event.time, input)), remaining)
+            }
+            xproto::BUTTON_PRESS_EVENT | xproto::BUTTON_RELEASE_EVENT => {
+                let (event, remaining) =
+                    xproto::ButtonPressEvent::try_parse(data).map_err(ConnectionError::from)?;
+                let input = Input::Button {
+                    x: event.root_x,
+                    y: event.root_y,
+                    button: event.detail,
+                    press: event.response_type & 0x7f == xproto::BUTTON_PRESS_EVENT,
+                };
+                (Some((event.time, input)), remaining)
+            }
+            xproto::MOTION_NOTIFY_EVENT => {
+                let (event, remaining) =
+                    xproto::MotionNotifyEvent::try_parse(data).map_err(ConnectionError::from)?;
+                let input = Input::Motion {
+                    x: event.root_x,
+                    y: event.root_y,
+                };
+                (Some((event.time, input)), remaining)
+            }
+            // Events are 32 bytes.
+            _ => (None, data.get(32..).unwrap_or_default()),
+        };
+        if let Some((time, input)) = recorded {
+            events.push(Recorded { time, input });
+        }
+        data = remaining;
+    }
+    Ok(())
+}
+
+/// Receive recorded events on `data` until the recording is disabled. `started` is notified
+/// once the server started recording.
+fn record(
+    data: &XConnection,
+    context: record::Context,
+    started: mpsc::Sender<()>,
+) -> Result<Vec<Recorded>> {
+    let mut events = Vec::new();
+    for reply in data.record_enable_context(context)? {
+        let reply = reply?;
+        match reply.category {
+            START_OF_DATA => {
+                let _ = started.send(());
+            }
+            FROM_SERVER => parse_events(&reply.data, &mut events)?,
+            _ => (),
+        }
+    }
+    Ok(events)
+}
+
+/// Turn recorded events into a script, see [`Recorder`].
+fn to_script(
+    events: &[Recorded],
+    keymap: &KeymapState,
+    timing: RecordTiming,
+    stop_hotkey: Option<&KeyCombo>,
+) -> Script {
+    // Each action with the times of the first and last event it was made from.
+    let mut actions: Vec<(u32, u32, Action)> = Vec::new();
+    for &Recorded { time, input } in events {
+        let action = match input {
+            Input::Key { keycode, press } => {
+                let Some(keysym) = keymap.keycode_to_keysym(keycode, 0) else {
+                    continue;
+                };
+                let combo = KeyCombo::new(vec![keysym]);
+                if press {
+                    Action::KeyPress { combo }
+                } else {
+                    Action::KeyRelease { combo }
+                }
+            }
+            Input::Button {
+                x,
+                y,
+                button,
+                press: true,
+            } => Action::ButtonPress { x, y, button },
+            Input::Button {
+                x,
+                y,
+                button,
+                press: false,
+            } => Action::ButtonRelease { x, y, button },
+            Input::Motion { x, y } => Action::Move { x, y },
+        };
+        actions.push((time, time, action));
+    }
+
+    // The hotkey that stopped the recording is pressed last, possibly followed by some motion.
+    if let Some(hotkey) = stop_hotkey {
+        let mut i = actions.len();
+        while i > 0 {
+            i -= 1;
+            match &actions[i].2 {
+                Action::Move { .. } => (),
+                Action::KeyPress { combo } | Action::KeyRelease { combo }
+                    if combo.keysyms().iter().all(|k| hotkey.keysyms().contains(k)) =>
+                {
+                    actions.remove(i);
+                }
+                _ => break,
+            }
+        }
+    }
+
+    let mut merged: Vec<(u32, u32, Action)> = Vec::with_capacity(actions.len());
+    for (start, end, action) in actions {
+        let combined = match (merged.last().map(|(_, _, last)| last), &action) {
+            (Some(Action::Move { .. }), Action::Move { .. }) => Some(action.clone()),
+            (Some(Action::KeyPress { combo: pressed }), Action::KeyRelease { combo })
+                if pressed == combo =>
+            {
+                Some(Action::Key {
+                    combo: combo.clone(),
+                })
+            }
+            (
+                Some(&Action::ButtonPress { x, y, button }),
+                &Action::ButtonRelease {
+                    button: released, ..
+                },
+            ) if button == released => Some(Action::Click { x, y, button }),
+            _ => None,
+        };
+        match (combined, merged.last_mut()) {
+            (Some(combined), Some(last)) => {
+                if matches!(combined, Action::Move { .. }) {
+                    last.0 = start;
+                }
+                last.1 = end;
+                last.2 = combined;
+            }
+            _ => merged.push((start, end, action)),
+        }
+    }
+
+    let mut script = Script::new();
+    let mut previous_end = None;
+    for (start, end, action) in merged {
+        let wait = match timing {
+            RecordTiming::Recorded => previous_end
+                .map(|previous_end: u32| {
+                    Duration::from_millis(start.wrapping_sub(previous_end).into())
+                })
+                .unwrap_or_default(),
+            RecordTiming::Fixed(wait) if previous_end.is_some() => wait,
+            RecordTiming::Fixed(_) => Duration::ZERO,
+        };
+        if !wait.is_zero() {
+            script.push(Action::Wait { duration: wait });
+        }
+        script.push(action);
+        previous_end = Some(end);
+    }
+    script
+}
+
+impl Recorder {
+    /// Prepare to record input on the display `synth` is connected to, using its keymap to
+    /// translate keycodes. Fails with [`Error::ExtensionMissing`] if the server doesn't support
+    /// RECORD.
+    pub fn new(synth: &InputSynth) -> Result<Self> {
+        let keymap = lock(&synth.mapping)
+            .duplicate()
+            .ok_or(Error::KeymapCopyFailed)?;
+        let (control, _) = InputSynth::connect(synth.display.as_deref())?;
+        if control
+            .extension_information(record::X11_EXTENSION_NAME)?
+            .is_none()
+        {
+            return Err(Error::ExtensionMissing {
+                name: record::X11_EXTENSION_NAME,
+            });
+        }
+        let (major, minor) = record::X11_XML_VERSION;
+        control
+            .record_query_version(major as _, minor as _)?
+            .reply()?;
+
+        let context = control.generate_id()?;
+        let none = record::Range8 { first: 0, last: 0 };
+        let no_extension = record::ExtRange {
+            major: none,
+            minor: record::Range16 { first: 0, last: 0 },
+        };
+        let range = record::Range {
+            core_requests: none,
+            core_replies: none,
+            ext_requests: no_extension,
+            ext_replies: no_extension,
+            delivered_events: none,
+            device_events: record::Range8 {
+                first: xproto::KEY_PRESS_EVENT,
+                last: xproto::MOTION_NOTIFY_EVENT,
+            },
+            errors: none,
+            client_started: false,
+            client_died: false,
+        };
+        control
+            .record_create_context(context, 0, &[record::CS::ALL_CLIENTS.into()], &[range])?
+            .check()?;
+        Ok(Self {
+            control,
+            display: synth.display.clone(),
+            context,
+            keymap,
+            timing: RecordTiming::default(),
+            stop_hotkey: None,
+            recording: None,
+        })
+    }
+
+    /// Set how to time the recorded actions.
+    pub fn set_timing(&mut self, timing: RecordTiming) {
+        self.timing = timing;
+    }
+
+    /// Leave out the keys of `hotkey` at the end of recordings, for applications that stop
+    /// recording when a hotkey is pressed.
+    pub fn set_stop_hotkey(&mut self, hotkey: Option<KeyCombo>) {
+        self.stop_hotkey = hotkey;
+    }
+
+    /// Start recording. Input from this point on is recorded, until [`Self::stop`].
+    pub fn start(&mut self) -> Result<()> {
+        if self.recording.is_some() {
+            return Err(Error::Unsupported("starting a recording twice"));
+        }
+        let (data, _) = InputSynth::connect(self.display.as_deref())?;
+        let context = self.context;
+        let (started, on_start) = mpsc::channel();
+        let recording = std::thread::spawn(move || record(&data, context, started));
+        if on_start.recv().is_err() {
+            // The thread failed before the recording started.
+            return match recording.join() {
+                Ok(result) => result.map(|_| ()),
+                Err(panic) => std::panic::resume_unwind(panic),
+            };
+        }
+        self.recording = Some(recording);
+        Ok(())
+    }
+
+    /// Stop recording and return what was recorded. Returns an empty script if not recording.
+    pub fn stop(&mut self) -> Result<Script> {
+        let Some(recording) = self.recording.take() else {
+            return Ok(Script::new());
+        };
+        self.control.record_disable_context(self.context)?.check()?;
+        let events = match recording.join() {
+            Ok(events) => events?,
+            Err(panic) => std::panic::resume_unwind(panic),
+        };
+        Ok(to_script(
+            &events,
+            &self.keymap,
+            self.timing,
+            self.stop_hotkey.as_ref(),
+        ))
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if self.recording.is_some() {
+            let _ = self.control.record_disable_context(self.context);
+        }
+        let _ = self.control.record_free_context(self.context);
+        let _ = self.control.flush();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use xkbcommon::xkb;
+
+    use super::{to_script, Input, RecordTiming, Recorded};
+    use crate::{Action, KeyCombo, KeymapState};
+
+    #[test]
+    fn test_to_script() {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_names(&context, "", "", "us", "", None, 0).unwrap();
+        let keymap = KeymapState::new(&context, keymap);
+        // Keycodes in the evdev keymap.
+        const A: u32 = 38;
+        const F12: u32 = 96;
+        let key = |time, keycode, press| Recorded {
+            time,
+            input: Input::Key { keycode, press },
+        };
+        let events = [
+            Recorded {
+                time: 0,
+                input: Input::Motion { x: 1, y: 1 },
+            },
+            Recorded {
+                time: 10,
+                input: Input::Motion { x: 2, y: 2 },
+            },
+            key(20, A, true),
+            key(30, A, false),
+            Recorded {
+                time: 50,
+                input: Input::Button {
+                    x: 2,
+                    y: 2,
+                    button: 1,
+                    press: true,
+                },
+            },
+            key(60, F12, true),
+        ];
+        let key_a = KeyCombo::new(vec![0x61]);
+        let hotkey: KeyCombo = "F12".parse().unwrap();
+
+        let script = to_script(&events, &keymap, RecordTiming::Recorded, Some(&hotkey));
+        assert_eq!(
+            script.actions(),
+            [
+                Action::Move { x: 2, y: 2 },
+                Action::Wait {
+                    duration: Duration::from_millis(10)
+                },
+                Action::Key {
+                    combo: key_a.clone()
+                },
+                Action::Wait {
+                    duration: Duration::from_millis(20)
+                },
+                Action::ButtonPress {
+                    x: 2,
+                    y: 2,
+                    button: 1
+                },
+            ]
+        );
+
+        let script = to_script(&events, &keymap, RecordTiming::Fixed(Duration::ZERO), None);
+        assert_eq!(
+            script.actions(),
+            [
+                Action::Move { x: 2, y: 2 },
+                Action::Key { combo: key_a },
+                Action::ButtonPress {
+                    x: 2,
+                    y: 2,
+                    button: 1
+                },
+                Action::KeyPress { combo: hotkey },
+            ]
+        );
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_record_round_trip() {
+        use super::Recorder;
+        use crate::TestServer;
+
+        let server = TestServer::start().unwrap();
+        let synth = server.synth();
+        let mut recorder = Recorder::new(synth).unwrap();
+        recorder.set_timing(RecordTiming::Fixed(Duration::ZERO));
+        recorder.start().unwrap();
+        synth.click_at(30, 40, 1).unwrap();
+        synth.type_string("a").unwrap();
+        // Make sure the server handled our input before the recording is stopped.
+        synth.ping().unwrap();
+        let script = recorder.stop().unwrap();
+        assert_eq!(
+            script.actions(),
+            [
+                Action::Move { x: 30, y: 40 },
+                Action::Click {
+                    x: 30,
+                    y: 40,
+                    button: 1
+                },
+                Action::Key {
+                    combo: KeyCombo::new(vec![0x61])
+                },
+            ]
+        );
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_hold() {
+        use super::Recorder;
+        use crate::{Script, TestServer};
+
+        const HOLD: Duration = Duration::from_millis(100);
+        /// Server timestamps are in milliseconds.
+        const SLACK: Duration = Duration::from_millis(1);
+        // The time between the last press and the first release.
+        let held = |script: &Script| {
+            let first_release = script
+                .actions()
+                .iter()
+                .position(|action| {
+                    matches!(
+                        action,
+                        Action::KeyRelease { .. } | Action::ButtonRelease { .. }
+                    )
+                })
+                .unwrap();
+            script.actions()[..first_release]
+                .iter()
+                .rev()
+                .map_while(|action| match action {
+                    Action::Wait { duration } => Some(*duration),
+                    _ => None,
+                })
+                .sum::<Duration>()
+        };
+
+        let server = TestServer::start().unwrap();
+        let synth = server.synth();
+        let mut recorder = Recorder::new(synth).unwrap();
+        recorder.start().unwrap();
+        synth.hold_key(u32::from(b'A'), HOLD).unwrap();
+        synth.ping().unwrap();
+        let script = recorder.stop().unwrap();
+        assert!(held(&script) + SLACK >= HOLD, "{script:?}");
+        // Shift is held until after the key is released.
+        let keys: Vec<_> = script
+            .actions()
+            .iter()
+            .filter(|action| !matches!(action, Action::Wait { .. }))
+            .collect();
+        match keys.as_slice() {
+            [Action::KeyPress { combo: pressed }, _, _, Action::KeyRelease { combo: released }] => {
+                assert_eq!(pressed, released)
+            }
+            _ => panic!("{script:?}"),
+        }
+
+        recorder.start().unwrap();
+        synth.hold_button(1, 10, 10, HOLD).unwrap();
+        synth.ping().unwrap();
+        let script = recorder.stop().unwrap();
+        assert!(held(&script) + SLACK >= HOLD, "{script:?}");
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_concurrent_typing() {
+        use super::Recorder;
+        use crate::TestServer;
+
+        const THREADS: usize = 4;
+        const REPEATS: usize = 10;
+        const SHIFT_L: u32 = 0xffe1;
+        let key = |keysym: u8| Action::Key {
+            combo: KeyCombo::new(vec![keysym.into()]),
+        };
+        // Recorded keysyms are those without modifiers, so capitals show up as Shift around the
+        // lowercase key.
+        let shifted = |keysym: u8| {
+            let shift = KeyCombo::new(vec![SHIFT_L]);
+            [
+                Action::KeyPress {
+                    combo: shift.clone(),
+                },
+                key(keysym),
+                Action::KeyRelease { combo: shift },
+            ]
+        };
+        let hello_world: Vec<_> = shifted(b'h')
+            .into_iter()
+            .chain(b"ello ".iter().map(|&b| key(b)))
+            .chain(shifted(b'w'))
+            .chain(b"orld".iter().map(|&b| key(b)))
+            .collect();
+
+        let server = TestServer::start().unwrap();
+        let synth = server.synth();
+        let mut recorder = Recorder::new(synth).unwrap();
+        recorder.set_timing(RecordTiming::Fixed(Duration::ZERO));
+        recorder.start().unwrap();
+        std::thread::scope(|scope| {
+            for _ in 0..THREADS {
+                scope.spawn(|| {
+                    for _ in 0..REPEATS {
+                        synth.type_string("Hello World").unwrap();
+                    }
+                });
+            }
+        });
+        synth.ping().unwrap();
+        let script = recorder.stop().unwrap();
+        // Calls from different threads don't interleave, each string arrives in one piece.
+        let actions = script.actions();
+        assert_eq!(
+            actions.len(),
+            THREADS * REPEATS * hello_world.len(),
+            "{script:?}"
+        );
+        for block in actions.chunks(hello_world.len()) {
+            assert_eq!(block, hello_world, "{script:?}");
+        }
+    }
+}
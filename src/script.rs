@@ -0,0 +1,213 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use xkbcommon::xkb;
+
+use crate::{keysym_from_name, Error, Result};
+
+/// A combination of keys pressed together, like `ctrl+shift+t`. The keys are pressed in order
+/// and released in reverse order, see [`crate::InputSynth::key_combo`].
+///
+/// Parsed from key names joined with `+`. Names are XKB keysym names, like `Return` or `a`, and
+/// `ctrl`, `shift`, `alt` and `super` for the left modifier keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "String", into = "String"))]
+pub struct KeyCombo {
+    keysyms: Vec<u32>,
+}
+
+/// Keysym for a key name in a combination.
+fn keysym(name: &str) -> Option<u32> {
+    let name = match name.to_ascii_lowercase().as_str() {
+        "ctrl" | "control" => "Control_L",
+        "shift" => "Shift_L",
+        "alt" => "Alt_L",
+        "super" | "meta" => "Super_L",
+        _ => name,
+    };
+    keysym_from_name(name)
+}
+
+impl KeyCombo {
+    pub fn new(keysyms: Vec<u32>) -> Self {
+        Self { keysyms }
+    }
+
+    pub fn keysyms(&self) -> &[u32] {
+        &self.keysyms
+    }
+}
+
+impl FromStr for KeyCombo {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let keysyms = s
+            .split('+')
+            .map(|name| keysym(name).ok_or_else(|| Error::UnknownKeyName(name.to_owned())))
+            .collect::<Result<_>>()?;
+        Ok(Self { keysyms })
+    }
+}
+
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, &keysym) in self.keysyms.iter().enumerate() {
+            if i != 0 {
+                f.write_str("+")?;
+            }
+            f.write_str(&xkb::keysym_get_name(keysym))?;
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<String> for KeyCombo {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<KeyCombo> for String {
+    fn from(combo: KeyCombo) -> Self {
+        combo.to_string()
+    }
+}
+
+/// Durations in scripts are given in milliseconds.
+#[cfg(feature = "serde")]
+mod millis {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(
+        duration: &Duration,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_millis().try_into().unwrap_or(u64::MAX))
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+}
+
+/// One step of a [`Script`].
+///
+/// In data files, each action is a map with an `action` key naming the variant in snake case,
+/// plus the fields of the variant, e.g. `{"action": "click", "x": 10, "y": 20, "button": 1}`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "action", rename_all = "snake_case"))]
+pub enum Action {
+    /// Type `text`, see [`crate::InputSynth::type_string`].
+    Type { text: String },
+    /// Press a key combination, see [`crate::InputSynth::key_combo`].
+    Key { combo: KeyCombo },
+    /// Press the keys of `combo` in order, without releasing them.
+    KeyPress { combo: KeyCombo },
+    /// Release the keys of `combo` in reverse order.
+    KeyRelease { combo: KeyCombo },
+    /// Move the pointer to `(x, y)` and press `button` there, without releasing it.
+    ButtonPress { x: i16, y: i16, button: u8 },
+    /// Move the pointer to `(x, y)` and release `button` there.
+    ButtonRelease { x: i16, y: i16, button: u8 },
+    /// Move the pointer to `(x, y)` and click `button` there.
+    Click { x: i16, y: i16, button: u8 },
+    /// Move the pointer to `(x, y)`.
+    Move { x: i16, y: i16 },
+    /// Scroll at `(x, y)`, see [`crate::InputSynth::smooth_scroll`].
+    Scroll { x: i16, y: i16, dx: f64, dy: f64 },
+    /// Do nothing for `duration`, given as `ms` in data files.
+    Wait {
+        #[cfg_attr(feature = "serde", serde(rename = "ms", with = "millis"))]
+        duration: Duration,
+    },
+    /// An action this version doesn't know, from a script written for a newer one. Skipped when
+    /// running the script.
+    #[cfg_attr(feature = "serde", serde(other))]
+    Unsupported,
+}
+
+/// A list of actions to run one after the other, see [`crate::InputSynth::run_script`].
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Script {
+    actions: Vec<Action>,
+}
+
+impl From<Vec<Action>> for Script {
+    fn from(actions: Vec<Action>) -> Self {
+        Self { actions }
+    }
+}
+
+impl Script {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, action: Action) {
+        self.actions.push(action);
+    }
+
+    pub fn actions(&self) -> &[Action] {
+        &self.actions
+    }
+}
+
+/// The outcome of a successful [`crate::InputSynth::run_script`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScriptReport {
+    /// How many steps were run.
+    pub completed: usize,
+    /// Indices of the [`Action::Unsupported`] steps that were skipped.
+    pub skipped: Vec<usize>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::KeyCombo;
+    use crate::Error;
+
+    #[test]
+    fn test_key_combo() {
+        let combo: KeyCombo = "ctrl+Shift+t".parse().unwrap();
+        assert_eq!(combo.keysyms(), [0xffe3, 0xffe1, 0x74]);
+        assert_eq!(combo.to_string(), "Control_L+Shift_L+t");
+        assert_eq!(combo.to_string().parse::<KeyCombo>().unwrap(), combo);
+        assert!(matches!(
+            "ctrl+nosuchkey".parse::<KeyCombo>(),
+            Err(Error::UnknownKeyName(name)) if name == "nosuchkey"
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_unknown_action() {
+        use serde::de::value::{Error, MapDeserializer};
+        use serde::Deserialize;
+
+        use super::Action;
+
+        let action = |fields: Vec<(&'static str, &'static str)>| {
+            Action::deserialize(MapDeserializer::<_, Error>::new(fields.into_iter()))
+        };
+        assert_eq!(
+            action(vec![("action", "type"), ("text", "hi")]).unwrap(),
+            Action::Type { text: "hi".into() }
+        );
+        assert_eq!(
+            action(vec![("action", "teleport"), ("to", "mars")]).unwrap(),
+            Action::Unsupported
+        );
+    }
+}
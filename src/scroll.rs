@@ -0,0 +1,161 @@
+use x11rb::connection::RequestConnection;
+use x11rb::protocol::xinput::{self, ConnectionExt as _, DeviceClassData, DeviceType, ScrollType};
+
+use crate::xi1::{self, fp3232_to_f64};
+use crate::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ScrollAxis {
+    number: u16,
+    /// Valuator change corresponding to one step of a scroll wheel.
+    increment: f64,
+}
+
+/// A pointer device with XInput 2.1 scroll valuators, through which we can send smooth scroll
+/// events.
+#[derive(Debug)]
+pub(crate) struct ScrollDevice {
+    device_id: u8,
+    horizontal: Option<ScrollAxis>,
+    vertical: Option<ScrollAxis>,
+    /// The valuator numbers of both axes, and everything in between, have to be sent together.
+    /// These are the current values of those valuators, starting from valuator `first`.
+    first: u16,
+    values: Vec<f64>,
+}
+
+impl ScrollDevice {
+    pub(crate) fn find(conn: &impl RequestConnection) -> Result<Option<ScrollDevice>> {
+        let devices = conn
+            .xinput_xi_query_device(u16::from(xinput::Device::ALL))?
+            .reply()?;
+        for device in devices.infos {
+            if device.type_ != DeviceType::SLAVE_POINTER || device.deviceid > 0x7f {
+                continue;
+            }
+            let (mut horizontal, mut vertical) = (None, None);
+            for class in &device.classes {
+                if let DeviceClassData::Scroll(scroll) = &class.data {
+                    let axis = Some(ScrollAxis {
+                        number: scroll.number,
+                        increment: fp3232_to_f64(scroll.increment),
+                    });
+                    if scroll.scroll_type == ScrollType::HORIZONTAL {
+                        horizontal = axis;
+                    } else if scroll.scroll_type == ScrollType::VERTICAL {
+                        vertical = axis;
+                    }
+                }
+            }
+            let numbers: Vec<_> = horizontal
+                .iter()
+                .chain(&vertical)
+                .map(|a| a.number)
+                .collect();
+            let (Some(&first), Some(&last)) = (numbers.iter().min(), numbers.iter().max()) else {
+                continue;
+            };
+            let mut values = vec![0.0; (last - first + 1) as usize];
+            for class in &device.classes {
+                if let DeviceClassData::Valuator(v) = &class.data {
+                    if (first..=last).contains(&v.number) {
+                        values[(v.number - first) as usize] = fp3232_to_f64(v.value);
+                    }
+                }
+            }
+            return Ok(Some(ScrollDevice {
+                device_id: device.deviceid as u8,
+                horizontal,
+                vertical,
+                first,
+                values,
+            }));
+        }
+        Ok(None)
+    }
+
+    /// How many valuator units one wheel step scrolls vertically, if the device can.
+    pub(crate) fn vertical_resolution(&self) -> Option<f64> {
+        self.vertical.map(|axis| axis.increment.abs())
+    }
+
+    /// Build the events for scrolling by `dx` and `dy` wheel steps. Returns the part of the
+    /// scroll that the device can't express, because it lacks that axis.
+    pub(crate) fn scroll(
+        &mut self,
+        xi_first_event: u8,
+        dx: f64,
+        dy: f64,
+    ) -> (Vec<[u8; 32]>, (f64, f64)) {
+        let mut rest = (dx, dy);
+        if let Some(axis) = self.horizontal {
+            self.values[(axis.number - self.first) as usize] += dx * axis.increment;
+            rest.0 = 0.0;
+        }
+        if let Some(axis) = self.vertical {
+            self.values[(axis.number - self.first) as usize] += dy * axis.increment;
+            rest.1 = 0.0;
+        }
+        let values: Vec<_> = self.values.iter().map(|v| v.round() as i32).collect();
+        let events = xi1::motion_events(xi_first_event, self.device_id, self.first as u8, &values);
+        (events, rest)
+    }
+}
+
+/// The legacy scroll buttons, and how many clicks of them it takes to scroll by `dx` and `dy`
+/// wheel steps.
+pub(crate) fn scroll_buttons(dx: f64, dy: f64) -> [(u8, u32); 2] {
+    let horizontal = if dx < 0.0 { 6 } else { 7 };
+    let vertical = if dy < 0.0 { 4 } else { 5 };
+    [
+        (horizontal, dx.abs().round() as u32),
+        (vertical, dy.abs().round() as u32),
+    ]
+}
+
+/// The scroll positions, in wheel steps, to go through when scrolling by `dy` in up to `steps`
+/// steps. Positions are multiples of `1 / resolution`, the smallest scroll that can be sent, and
+/// no two steps go to the same one.
+pub(crate) fn scroll_path(dy: f64, steps: u32, resolution: f64) -> Vec<f64> {
+    let units = (dy.abs() * resolution).round();
+    let steps = f64::from(steps).min(units).max(1.0) as u32;
+    (1..=steps)
+        .map(|i| (dy * resolution * f64::from(i) / f64::from(steps)).round() / resolution)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{scroll_buttons, scroll_path, ScrollAxis, ScrollDevice};
+
+    #[test]
+    fn test_scroll_path() {
+        // Buttons can only scroll by whole steps.
+        assert_eq!(scroll_path(3.0, 10, 1.0), [1.0, 2.0, 3.0]);
+        assert_eq!(scroll_path(-2.0, 4, 2.0), [-0.5, -1.0, -1.5, -2.0]);
+        assert_eq!(scroll_path(1.0, 4, 120.0), [0.25, 0.5, 0.75, 1.0]);
+        assert_eq!(scroll_path(0.0, 4, 120.0), [0.0]);
+    }
+
+    #[test]
+    fn test_scroll() {
+        assert_eq!(scroll_buttons(-1.4, 2.6), [(6, 1), (5, 3)]);
+        assert_eq!(scroll_buttons(0.2, -1.0), [(7, 0), (4, 1)]);
+
+        let mut device = ScrollDevice {
+            device_id: 9,
+            horizontal: None,
+            vertical: Some(ScrollAxis {
+                number: 3,
+                increment: 15.0,
+            }),
+            first: 3,
+            values: vec![100.0],
+        };
+        let (events, rest) = device.scroll(64, 1.5, -0.5);
+        assert_eq!(rest, (1.5, 0.0));
+        assert_eq!(events.len(), 2);
+        assert_eq!(device.values, [92.5]);
+        assert_eq!(events[1][7], 3);
+    }
+}
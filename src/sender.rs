@@ -0,0 +1,22 @@
+use std::sync::mpsc;
+
+use crate::InputEvent;
+
+/// Queues events for the thread that owns an [`crate::InputSynth`], see
+/// [`crate::InputSynth::make_sender`]. Can be cloned and sent to other threads.
+#[derive(Debug, Clone)]
+pub struct InputSender {
+    sender: mpsc::Sender<InputEvent>,
+}
+
+impl InputSender {
+    pub(crate) fn new(sender: mpsc::Sender<InputEvent>) -> Self {
+        Self { sender }
+    }
+
+    /// Queue `event`. Fails, giving the event back, once the queue was closed by
+    /// [`crate::InputSynth::run_sender_loop`] returning.
+    pub fn send(&self, event: InputEvent) -> Result<(), mpsc::SendError<InputEvent>> {
+        self.sender.send(event)
+    }
+}
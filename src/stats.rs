@@ -0,0 +1,104 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::lock;
+
+/// Counts of the input synthesized so far, see [`crate::InputSynth::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct InputStats {
+    /// Key presses, including those of modifiers pressed to reach a keysym.
+    pub keys_pressed: u64,
+    /// Button presses, including the scroll wheel buttons.
+    pub mouse_clicks: u64,
+    /// How far the pointer was moved, in a straight line between positions we moved it to.
+    pub mouse_distance_pixels: f64,
+    /// Characters typed by the `type_*` methods and [`crate::InputSynth::ascii_char`].
+    pub chars_typed: u64,
+}
+
+/// The counters behind [`InputStats`], which can be updated and read from any thread.
+#[derive(Debug, Default)]
+pub(crate) struct Stats {
+    keys_pressed: AtomicU64,
+    mouse_clicks: AtomicU64,
+    /// Bits of an `f64`.
+    mouse_distance_pixels: AtomicU64,
+    chars_typed: AtomicU64,
+    /// Where we last moved the pointer, `None` before the first motion.
+    position: Mutex<Option<(i16, i16)>>,
+}
+
+impl Stats {
+    pub(crate) fn key_pressed(&self) {
+        self.keys_pressed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn mouse_clicked(&self) {
+        self.mouse_clicks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn char_typed(&self) {
+        self.chars_typed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn moved_to(&self, x: i16, y: i16) {
+        let Some((from_x, from_y)) = lock(&self.position).replace((x, y)) else {
+            return;
+        };
+        let distance = (f64::from(x) - f64::from(from_x)).hypot(f64::from(y) - f64::from(from_y));
+        let _ =
+            self.mouse_distance_pixels
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                    Some((f64::from_bits(bits) + distance).to_bits())
+                });
+    }
+
+    pub(crate) fn get(&self) -> InputStats {
+        InputStats {
+            keys_pressed: self.keys_pressed.load(Ordering::Relaxed),
+            mouse_clicks: self.mouse_clicks.load(Ordering::Relaxed),
+            mouse_distance_pixels: f64::from_bits(
+                self.mouse_distance_pixels.load(Ordering::Relaxed),
+            ),
+            chars_typed: self.chars_typed.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Zero the counters. The pointer position is kept, so the next motion is still measured.
+    pub(crate) fn reset(&self) {
+        self.keys_pressed.store(0, Ordering::Relaxed);
+        self.mouse_clicks.store(0, Ordering::Relaxed);
+        self.mouse_distance_pixels
+            .store(0f64.to_bits(), Ordering::Relaxed);
+        self.chars_typed.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{InputStats, Stats};
+
+    #[test]
+    fn test_stats() {
+        let stats = Stats::default();
+        stats.moved_to(0, 0);
+        stats.moved_to(3, 4);
+        stats.moved_to(3, 0);
+        stats.key_pressed();
+        stats.mouse_clicked();
+        stats.char_typed();
+        assert_eq!(
+            stats.get(),
+            InputStats {
+                keys_pressed: 1,
+                mouse_clicks: 1,
+                mouse_distance_pixels: 9.0,
+                chars_typed: 1,
+            }
+        );
+        stats.reset();
+        assert_eq!(stats.get(), InputStats::default());
+        stats.moved_to(3, 1);
+        assert_eq!(stats.get().mouse_distance_pixels, 1.0);
+    }
+}
@@ -0,0 +1,190 @@
+use x11rb::connection::RequestConnection;
+use x11rb::protocol::xinput::{self, ConnectionExt as _, DeviceClassData, DeviceType};
+use x11rb::protocol::xproto;
+
+use crate::xi1::{self, fp3232_to_f64};
+use crate::Result;
+
+/// Axis labels set by the X server for tablet devices, from xserver-properties.h.
+const AXIS_LABELS: [&[u8]; 5] = [
+    b"Abs X",
+    b"Abs Y",
+    b"Abs Pressure",
+    b"Abs Tilt X",
+    b"Abs Tilt Y",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Axis {
+    pub(crate) number: u16,
+    pub(crate) min: f64,
+    pub(crate) max: f64,
+}
+
+impl Axis {
+    /// Map `fraction`, in `[0, 1]`, onto the range of this axis.
+    pub(crate) fn map(&self, fraction: f64) -> i32 {
+        (self.min + fraction.clamp(0.0, 1.0) * (self.max - self.min)).round() as i32
+    }
+}
+
+/// A tablet stylus found on the server.
+#[derive(Debug)]
+pub(crate) struct Stylus {
+    device_id: u8,
+    /// X, Y, pressure, tilt X and tilt Y, in that order.
+    axes: [Axis; 5],
+    /// Current values of all valuators of the device, indexed by valuator number. Axes we don't
+    /// set are sent back unchanged.
+    values: Vec<i32>,
+    in_proximity: bool,
+}
+
+impl Stylus {
+    /// Look for a slave pointer that has all the axes of a pen.
+    pub(crate) fn find(conn: &impl RequestConnection) -> Result<Option<Stylus>> {
+        let mut labels = [0; 5];
+        for (atom, name) in labels.iter_mut().zip(AXIS_LABELS) {
+            *atom = xproto::ConnectionExt::intern_atom(conn, true, name)?
+                .reply()?
+                .atom;
+            if *atom == x11rb::NONE {
+                // The server has never seen a tablet.
+                return Ok(None);
+            }
+        }
+        let devices = conn
+            .xinput_xi_query_device(u16::from(xinput::Device::ALL))?
+            .reply()?;
+        for device in devices.infos {
+            if device.type_ != DeviceType::SLAVE_POINTER || device.deviceid > 0x7f {
+                continue;
+            }
+            let mut axes = [None; 5];
+            let mut values = Vec::new();
+            for class in &device.classes {
+                if let DeviceClassData::Valuator(v) = &class.data {
+                    let number = v.number as usize;
+                    if values.len() <= number {
+                        values.resize(number + 1, 0);
+                    }
+                    values[number] = fp3232_to_f64(v.value).round() as i32;
+                    if let Some(i) = labels.iter().position(|&l| l == v.label) {
+                        axes[i] = Some(Axis {
+                            number: v.number,
+                            min: fp3232_to_f64(v.min),
+                            max: fp3232_to_f64(v.max),
+                        });
+                    }
+                }
+            }
+            if let [Some(x), Some(y), Some(p), Some(tx), Some(ty)] = axes {
+                return Ok(Some(Stylus {
+                    device_id: device.deviceid as u8,
+                    axes: [x, y, p, tx, ty],
+                    values,
+                    in_proximity: false,
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Build the events for moving the stylus. Positions are fractions of the screen size,
+    /// pressure is in `[0, 1]` and tilts are in `[-1, 1]`.
+    pub(crate) fn motion(
+        &mut self,
+        xi_first_event: u8,
+        position: (f64, f64),
+        pressure: f64,
+        tilt: (f64, f64),
+    ) -> Vec<[u8; 32]> {
+        let fractions = [
+            position.0,
+            position.1,
+            pressure.clamp(0.0, 1.0),
+            (tilt.0.clamp(-1.0, 1.0) + 1.0) / 2.0,
+            (tilt.1.clamp(-1.0, 1.0) + 1.0) / 2.0,
+        ];
+        for (axis, fraction) in self.axes.iter().zip(fractions) {
+            self.values[axis.number as usize] = axis.map(fraction);
+        }
+        let mut events = Vec::new();
+        if !self.in_proximity {
+            self.in_proximity = true;
+            events.push(xi1::pointer_event(
+                xi_first_event + xi1::PROXIMITY_IN,
+                self.device_id,
+                false,
+            ));
+        }
+        events.extend(xi1::motion_events(
+            xi_first_event,
+            self.device_id,
+            0,
+            &self.values,
+        ));
+        events
+    }
+
+    /// Build the event for taking the stylus away from the tablet, if it isn't already.
+    pub(crate) fn leave(&mut self, xi_first_event: u8) -> Option<[u8; 32]> {
+        if !self.in_proximity {
+            return None;
+        }
+        self.in_proximity = false;
+        Some(xi1::pointer_event(
+            xi_first_event + xi1::PROXIMITY_OUT,
+            self.device_id,
+            false,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Axis, Stylus};
+
+    #[test]
+    fn test_stylus_events() {
+        let axis = |number, max| Axis {
+            number,
+            min: 0.0,
+            max,
+        };
+        let mut stylus = Stylus {
+            device_id: 12,
+            axes: [
+                axis(0, 1000.0),
+                axis(1, 1000.0),
+                axis(2, 2047.0),
+                axis(3, 127.0),
+                axis(4, 127.0),
+            ],
+            values: vec![0, 0, 0, 0, 0, 42],
+            in_proximity: false,
+        };
+        assert_eq!(
+            Axis {
+                number: 0,
+                min: -64.0,
+                max: 64.0
+            }
+            .map(0.5),
+            0
+        );
+
+        let events = stylus.motion(100, (0.5, 1.0), 2.0, (-3.0, 1.0));
+        // Proximity in, motion, one valuator event with all six valuators.
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0][0], 108);
+        assert_eq!(events[1][0], 105);
+        assert_eq!(events[1][31], 12 | 0x80);
+        assert_eq!(events[2][1], 12);
+        assert_eq!(stylus.values, [500, 1000, 2047, 0, 127, 42]);
+
+        assert_eq!(stylus.motion(100, (0.0, 0.0), 0.0, (0.0, 0.0)).len(), 2);
+        assert_eq!(stylus.leave(100).unwrap()[0], 109);
+        assert!(stylus.leave(100).is_none());
+    }
+}
@@ -0,0 +1,145 @@
+//! Helpers for testing against a private X server.
+
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+use x11rb::protocol::xproto::{ConnectionExt as _, ImageFormat};
+
+use crate::{Error, InputSynth, Result};
+
+/// How long to wait for Xvfb to start accepting connections.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An `Xvfb` server running for the duration of a test, with an [`InputSynth`] connected to it.
+/// The server is killed when this is dropped.
+pub struct TestServer {
+    synth: InputSynth,
+    display: String,
+    child: Child,
+}
+
+impl TestServer {
+    /// Start `Xvfb` on an unused display, with one 1280x1024 screen.
+    ///
+    /// Xvfb picks the display itself, so servers started by tests running in parallel never
+    /// compete for the same one.
+    pub fn start() -> Result<Self> {
+        // With `-displayfd`, Xvfb writes the display number it got once it accepts
+        // connections.
+        let mut child = Command::new("Xvfb")
+            .args([
+                "-displayfd",
+                "1",
+                "-screen",
+                "0",
+                "1280x1024x24",
+                "-nolisten",
+                "tcp",
+            ])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let result = Self::read_display(&mut child).and_then(|display| {
+            let synth = InputSynth::new_with_display(&display)?;
+            Ok((display, synth))
+        });
+        match result {
+            Ok((display, synth)) => Ok(Self {
+                synth,
+                display,
+                child,
+            }),
+            Err(e) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                Err(e)
+            }
+        }
+    }
+
+    /// Wait for Xvfb to announce its display on its standard output, e.g. `":99"`.
+    fn read_display(child: &mut Child) -> Result<String> {
+        let stdout = child.stdout.take().ok_or(Error::Unsupported(
+            "starting a test server without its output",
+        ))?;
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut line = String::new();
+            let _ = sender.send(BufReader::new(stdout).read_line(&mut line).map(|_| line));
+        });
+        let line = receiver
+            .recv_timeout(STARTUP_TIMEOUT)
+            .map_err(|_| Error::Timeout(STARTUP_TIMEOUT))??;
+        match line.trim().parse::<u32>() {
+            Ok(n) => Ok(format!(":{n}")),
+            // Xvfb closes its output when it exits without a display.
+            Err(_) => Err(Error::Io(std::io::Error::other(match child.wait() {
+                Ok(status) => format!("Xvfb exited with {status}"),
+                Err(e) => format!("Xvfb didn't start: {e}"),
+            }))),
+        }
+    }
+
+    /// The [`InputSynth`] connected to this server.
+    pub fn synth(&self) -> &InputSynth {
+        &self.synth
+    }
+
+    /// The display name of this server, e.g. `":99"`.
+    pub fn display(&self) -> &str {
+        &self.display
+    }
+
+    /// Capture the contents of the screen, as 32-bit pixels in the server's byte order.
+    pub fn screenshot(&self) -> Result<Vec<u8>> {
+        let (width, height) = self.synth.screen_size();
+        let image = self
+            .synth
+            .conn()
+            .get_image(
+                ImageFormat::Z_PIXMAP,
+                self.synth.root_window()?,
+                0,
+                0,
+                width,
+                height,
+                !0,
+            )?
+            .reply()?;
+        Ok(image.data)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TestServer;
+
+    #[test]
+    fn test_test_server() {
+        let server = TestServer::start().unwrap();
+        server.synth().move_cursor(10, 10).unwrap();
+        assert_eq!(server.screenshot().unwrap().len(), 1280 * 1024 * 4);
+    }
+
+    #[test]
+    fn test_parallel_servers() {
+        let servers: Vec<_> = (0..4)
+            .map(|_| std::thread::spawn(TestServer::start))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|server| server.join().unwrap().unwrap())
+            .collect();
+        let mut displays: Vec<_> = servers.iter().map(TestServer::display).collect();
+        displays.sort();
+        displays.dedup();
+        assert_eq!(displays.len(), servers.len());
+    }
+}
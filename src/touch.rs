@@ -0,0 +1,68 @@
+use crate::{Error, Result};
+
+/// What has to be sent to the server for a touch event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TouchAction {
+    /// Move the pointer, then press button 1.
+    Press,
+    /// Move the pointer.
+    Motion,
+    /// Release button 1.
+    Release,
+}
+
+/// Active touch points. Touches are delivered through the emulated pointer, like the server does
+/// for the first touch of a direct touch device, so only one touch can be active at a time.
+#[derive(Debug, Default)]
+pub(crate) struct Touches {
+    active: Option<u32>,
+}
+
+impl Touches {
+    pub(crate) fn begin(&mut self, id: u32) -> Result<TouchAction> {
+        match self.active {
+            Some(active) if active == id => Err(Error::InvalidTouch(id)),
+            Some(_) => Err(Error::Unsupported("more than one simultaneous touch")),
+            None => {
+                self.active = Some(id);
+                Ok(TouchAction::Press)
+            }
+        }
+    }
+
+    pub(crate) fn update(&self, id: u32) -> Result<TouchAction> {
+        if self.active == Some(id) {
+            Ok(TouchAction::Motion)
+        } else {
+            Err(Error::InvalidTouch(id))
+        }
+    }
+
+    pub(crate) fn end(&mut self, id: u32) -> Result<TouchAction> {
+        if self.active == Some(id) {
+            self.active = None;
+            Ok(TouchAction::Release)
+        } else {
+            Err(Error::InvalidTouch(id))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TouchAction, Touches};
+    use crate::Error;
+
+    #[test]
+    fn test_touch_sequence() {
+        let mut touches = Touches::default();
+        assert_eq!(touches.begin(1).unwrap(), TouchAction::Press);
+        assert!(matches!(touches.begin(1), Err(Error::InvalidTouch(1))));
+        assert!(matches!(touches.begin(2), Err(Error::Unsupported(_))));
+        assert_eq!(touches.update(1).unwrap(), TouchAction::Motion);
+        assert!(matches!(touches.update(2), Err(Error::InvalidTouch(2))));
+        assert_eq!(touches.end(1).unwrap(), TouchAction::Release);
+        assert!(matches!(touches.end(1), Err(Error::InvalidTouch(1))));
+        assert_eq!(touches.begin(2).unwrap(), TouchAction::Press);
+    }
+}
@@ -0,0 +1,659 @@
+//! Input injection through virtual kernel input devices, below the display server. Events
+//! created this way are indistinguishable from those of a real keyboard, mouse or touchscreen.
+
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{gesture, lock, Error, InputBackend, InputSynth, Key, KeymapState, Result};
+
+// From linux/uinput.h and linux/input-event-codes.h.
+const UI_DEV_CREATE: libc::c_ulong = 0x5501;
+const UI_DEV_DESTROY: libc::c_ulong = 0x5502;
+const UI_DEV_SETUP: libc::c_ulong = 0x405c5503;
+const UI_ABS_SETUP: libc::c_ulong = 0x401c5504;
+const UI_SET_EVBIT: libc::c_ulong = 0x40045564;
+const UI_SET_KEYBIT: libc::c_ulong = 0x40045565;
+const UI_SET_RELBIT: libc::c_ulong = 0x40045566;
+const UI_SET_ABSBIT: libc::c_ulong = 0x40045567;
+const UI_SET_PROPBIT: libc::c_ulong = 0x4004556e;
+/// `UI_GET_SYSNAME(64)`
+const UI_GET_SYSNAME_64: libc::c_ulong = 0x8040552c;
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_REL: u16 = 0x02;
+const EV_ABS: u16 = 0x03;
+const SYN_REPORT: u16 = 0;
+const REL_HWHEEL: u16 = 0x06;
+const REL_WHEEL: u16 = 0x08;
+const ABS_X: u16 = 0x00;
+const ABS_Y: u16 = 0x01;
+const ABS_MT_SLOT: u16 = 0x2f;
+const ABS_MT_POSITION_X: u16 = 0x35;
+const ABS_MT_POSITION_Y: u16 = 0x36;
+const ABS_MT_TRACKING_ID: u16 = 0x39;
+const INPUT_PROP_DIRECT: u16 = 0x01;
+const BUS_VIRTUAL: u16 = 0x06;
+/// Pointer buttons, for X buttons 1, 2, 3, 8 and 9.
+const BTN_LEFT: u16 = 0x110;
+const BTN_RIGHT: u16 = 0x111;
+const BTN_MIDDLE: u16 = 0x112;
+const BTN_SIDE: u16 = 0x113;
+const BTN_EXTRA: u16 = 0x114;
+const BTN_TOUCH: u16 = 0x14a;
+/// XKB keycodes are evdev keycodes plus 8.
+const EVDEV_OFFSET: u32 = 8;
+
+fn ioctl(file: &File, request: libc::c_ulong, arg: libc::c_ulong) -> Result<()> {
+    // SAFETY: all requests we make either take an integer argument, or a pointer to a struct of
+    // the size encoded in the request.
+    if unsafe { libc::ioctl(file.as_raw_fd(), request as _, arg) } < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+/// Open `/dev/uinput` for setting up a new device.
+fn open_uinput() -> Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .open("/dev/uinput")
+        .map_err(|e| match e.kind() {
+            ErrorKind::PermissionDenied => Error::UinputPermissionDenied,
+            _ => e.into(),
+        })
+}
+
+/// Enable the absolute axis `axis`, going from 0 to `max`.
+fn setup_abs(file: &File, axis: u16, max: i32) -> Result<()> {
+    ioctl(file, UI_SET_ABSBIT, axis.into())?;
+    let setup = libc::uinput_abs_setup {
+        code: axis,
+        absinfo: libc::input_absinfo {
+            value: 0,
+            minimum: 0,
+            maximum: max,
+            fuzz: 0,
+            flat: 0,
+            resolution: 0,
+        },
+    };
+    ioctl(file, UI_ABS_SETUP, &setup as *const _ as _)
+}
+
+/// Create the device set up on `file`, named `name`.
+fn create_device(file: &File, name: &[u8]) -> Result<()> {
+    let mut setup = libc::uinput_setup {
+        id: libc::input_id {
+            bustype: BUS_VIRTUAL,
+            vendor: 0,
+            product: 0,
+            version: 1,
+        },
+        name: [0; libc::UINPUT_MAX_NAME_SIZE],
+        ff_effects_max: 0,
+    };
+    for (dst, &src) in setup.name.iter_mut().zip(name) {
+        *dst = src as _;
+    }
+    ioctl(file, UI_DEV_SETUP, &setup as *const _ as _)?;
+    ioctl(file, UI_DEV_CREATE, 0)
+}
+
+/// The path of the event device the kernel created for `file`, like `/dev/input/event5`.
+fn event_node(file: &File) -> Result<String> {
+    let mut sysname = [0u8; 64];
+    ioctl(file, UI_GET_SYSNAME_64, sysname.as_mut_ptr() as _)?;
+    let sysname = std::ffi::CStr::from_bytes_until_nul(&sysname)
+        .ok()
+        .and_then(|name| name.to_str().ok())
+        .ok_or(Error::Unsupported("device names that aren't UTF-8"))?;
+    let event = std::fs::read_dir(format!("/sys/devices/virtual/input/{sysname}"))?
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .find(|name| name.starts_with("event"))
+        .ok_or(Error::Unsupported("uinput devices without an event device"))?;
+    Ok(format!("/dev/input/{event}"))
+}
+
+/// Wait for udev to create the device node of `file`, which it does in the background. Without
+/// udev, e.g. in a container, the node may never appear, which is no reason to fail.
+fn wait_for_node(file: &File) -> Result<()> {
+    const POLL_INTERVAL: Duration = Duration::from_millis(10);
+    const TIMEOUT: Duration = Duration::from_secs(1);
+    let node = event_node(file)?;
+    let start = Instant::now();
+    while !Path::new(&node).exists() && start.elapsed() < TIMEOUT {
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    Ok(())
+}
+
+/// Write `events` to `file`, followed by a `SYN_REPORT` that makes them take effect together.
+fn send(file: &File, events: &[(u16, u16, i32)]) -> Result<()> {
+    let mut bytes = Vec::new();
+    for &(type_, code, value) in events.iter().chain([&(EV_SYN, SYN_REPORT, 0)]) {
+        // SAFETY: `input_event` is plain old data. The kernel fills in the time.
+        let mut event: libc::input_event = unsafe { std::mem::zeroed() };
+        event.type_ = type_;
+        event.code = code;
+        event.value = value;
+        bytes.extend_from_slice(unsafe {
+            std::slice::from_raw_parts(
+                &event as *const _ as *const u8,
+                std::mem::size_of::<libc::input_event>(),
+            )
+        });
+    }
+    (&*file).write_all(&bytes)?;
+    Ok(())
+}
+
+/// Injects input through a virtual keyboard and mouse created with `/dev/uinput`. This works
+/// for clients that ignore XTEST, like those running on Xwayland or grabbing input devices
+/// directly, but needs write access to `/dev/uinput`.
+///
+/// Creating the device waits until its node in `/dev/input` appears, for up to a second. Input
+/// sent before the display server has opened the node is lost, and it may take the server a
+/// moment longer to do so after the node appears.
+///
+/// The device is removed again when this is dropped.
+pub struct UinputBackend {
+    file: File,
+    keymap: Mutex<KeymapState>,
+    /// Size of the screen, which the absolute pointer axes cover.
+    size: (u16, u16),
+}
+
+impl UinputBackend {
+    /// Create the virtual device, using the keymap and screen size of `synth`'s X server.
+    pub fn new(synth: &InputSynth) -> Result<Self> {
+        let keymap = lock(&synth.mapping)
+            .duplicate()
+            .ok_or(Error::KeymapCopyFailed)?;
+        Self::with_keymap(keymap, synth.screen_size())
+    }
+
+    fn with_keymap(keymap: KeymapState, size: (u16, u16)) -> Result<Self> {
+        let file = open_uinput()?;
+        for ev in [EV_KEY, EV_REL, EV_ABS] {
+            ioctl(&file, UI_SET_EVBIT, ev.into())?;
+        }
+        // Every key a keymap can refer to, and the buttons.
+        for key in 1..256 {
+            ioctl(&file, UI_SET_KEYBIT, key)?;
+        }
+        for button in [BTN_LEFT, BTN_RIGHT, BTN_MIDDLE, BTN_SIDE, BTN_EXTRA] {
+            ioctl(&file, UI_SET_KEYBIT, button.into())?;
+        }
+        for rel in [REL_WHEEL, REL_HWHEEL] {
+            ioctl(&file, UI_SET_RELBIT, rel.into())?;
+        }
+        for (axis, size) in [(ABS_X, size.0), (ABS_Y, size.1)] {
+            setup_abs(&file, axis, i32::from(size.max(1)) - 1)?;
+        }
+        create_device(&file, b"inputsynth virtual input")?;
+        let backend = Self {
+            file,
+            keymap: Mutex::new(keymap),
+            size,
+        };
+        wait_for_node(&backend.file)?;
+        Ok(backend)
+    }
+
+    fn send(&self, events: &[(u16, u16, i32)]) -> Result<()> {
+        send(&self.file, events)
+    }
+
+    fn send_key(&self, keycode: u32, press: bool) -> Result<()> {
+        self.send(&[(EV_KEY, (keycode - EVDEV_OFFSET) as u16, i32::from(press))])
+    }
+}
+
+impl InputBackend for UinputBackend {
+    fn move_cursor(&self, x: i16, y: i16) -> Result<()> {
+        // The axes go up to the last pixel, see `with_keymap`.
+        let x = i32::from(x).clamp(0, i32::from(self.size.0.max(1)) - 1);
+        let y = i32::from(y).clamp(0, i32::from(self.size.1.max(1)) - 1);
+        self.send(&[(EV_ABS, ABS_X, x), (EV_ABS, ABS_Y, y)])
+    }
+
+    fn click(&self, x: i16, y: i16, button: u8, press: bool) -> Result<()> {
+        self.move_cursor(x, y)?;
+        let code = match button {
+            1 => BTN_LEFT,
+            2 => BTN_MIDDLE,
+            3 => BTN_RIGHT,
+            4..=7 => {
+                // Scrolling happens on press, there is nothing to release.
+                if press {
+                    let (axis, value) = match button {
+                        4 => (REL_WHEEL, 1),
+                        5 => (REL_WHEEL, -1),
+                        6 => (REL_HWHEEL, -1),
+                        _ => (REL_HWHEEL, 1),
+                    };
+                    self.send(&[(EV_REL, axis, value)])?;
+                }
+                return Ok(());
+            }
+            8 => BTN_SIDE,
+            9 => BTN_EXTRA,
+            _ => return Err(Error::Unsupported("buttons above 9 through uinput")),
+        };
+        self.send(&[(EV_KEY, code, i32::from(press))])
+    }
+
+    fn key(&self, keysym: u32, press: bool) -> Result<()> {
+        let (mods, keycode) = lock(&self.keymap)
+            .find_key_sequence(Key::new(keysym))
+            .ok_or(Error::NoKeySequence { keysym })?;
+        if press {
+            for &m in &mods {
+                self.send_key(m, true)?;
+            }
+            self.send_key(keycode, true)
+        } else {
+            self.send_key(keycode, false)?;
+            for &m in mods.iter().rev() {
+                self.send_key(m, false)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+impl Drop for UinputBackend {
+    fn drop(&mut self) {
+        let _ = ioctl(&self.file, UI_DEV_DESTROY, 0);
+    }
+}
+
+/// How many fingers a [`UinputTouchscreen`] can have down at the same time.
+const TOUCH_SLOTS: usize = 10;
+
+/// What happens to a finger in one frame of touch events.
+#[derive(Debug, Clone, Copy)]
+enum Finger {
+    Down(f64, f64),
+    Move(f64, f64),
+    Up,
+}
+
+/// Fingers currently down on a [`UinputTouchscreen`].
+#[derive(Debug, Default)]
+struct Fingers {
+    /// The touch id and position of the finger in each multi-touch slot, `None` where no finger
+    /// is down.
+    slots: [Option<(u32, (i32, i32))>; TOUCH_SLOTS],
+    /// The tracking id of the next finger to go down. Each touch gets a new one.
+    next_tracking_id: u16,
+}
+
+/// A virtual touchscreen created with `/dev/uinput`, covering the screen. Unlike the touches of
+/// [`InputSynth::emulated_touch_begin`], which are turned into pointer events one at a time,
+/// these are real multi-touch: several fingers can be down at once, and clients get touch
+/// events, like `XI_TouchBegin`, that gesture recognizers work with.
+///
+/// Like [`UinputBackend`], this needs write access to `/dev/uinput`, and input sent before the
+/// display server has picked up the device is lost. The device is removed again when this is
+/// dropped, after lifting the fingers that are still down.
+pub struct UinputTouchscreen {
+    file: File,
+    size: (u16, u16),
+    fingers: Mutex<Fingers>,
+    frame_interval: Duration,
+}
+
+impl UinputTouchscreen {
+    /// Create the virtual touchscreen, covering the screen of `synth`'s X server.
+    pub fn new(synth: &InputSynth) -> Result<Self> {
+        Self::with_size(synth.screen_size())
+    }
+
+    fn with_size(size: (u16, u16)) -> Result<Self> {
+        let file = open_uinput()?;
+        for ev in [EV_KEY, EV_ABS] {
+            ioctl(&file, UI_SET_EVBIT, ev.into())?;
+        }
+        ioctl(&file, UI_SET_KEYBIT, BTN_TOUCH.into())?;
+        // A touchscreen, not a touchpad: positions are on the screen.
+        ioctl(&file, UI_SET_PROPBIT, INPUT_PROP_DIRECT.into())?;
+        let (max_x, max_y) = (i32::from(size.0.max(1)) - 1, i32::from(size.1.max(1)) - 1);
+        for (axis, max) in [
+            (ABS_X, max_x),
+            (ABS_Y, max_y),
+            (ABS_MT_SLOT, TOUCH_SLOTS as i32 - 1),
+            (ABS_MT_POSITION_X, max_x),
+            (ABS_MT_POSITION_Y, max_y),
+            (ABS_MT_TRACKING_ID, u16::MAX.into()),
+        ] {
+            setup_abs(&file, axis, max)?;
+        }
+        create_device(&file, b"inputsynth virtual touchscreen")?;
+        let touchscreen = Self {
+            file,
+            size,
+            fingers: Mutex::new(Fingers::default()),
+            frame_interval: Duration::from_millis(10),
+        };
+        wait_for_node(&touchscreen.file)?;
+        Ok(touchscreen)
+    }
+
+    /// How long [`Self::pinch`] and [`Self::rotate`] wait between moving the fingers, 10ms by
+    /// default. Shorter intervals make smoother gestures, at the cost of more events.
+    pub fn set_frame_interval(&mut self, interval: Duration) {
+        self.frame_interval = interval;
+    }
+
+    /// Put a finger down at `(x, y)`. `id` identifies the touch in later calls to
+    /// [`Self::touch_update`] and [`Self::touch_end`], and has to differ from the ids of the
+    /// other fingers that are down. Fails with [`Error::Unsupported`] if 10 fingers are down.
+    pub fn touch_begin(&self, id: u32, x: f64, y: f64) -> Result<()> {
+        self.send_frame(&[(id, Finger::Down(x, y))])
+    }
+
+    /// Move the finger of touch `id` to `(x, y)`.
+    pub fn touch_update(&self, id: u32, x: f64, y: f64) -> Result<()> {
+        self.send_frame(&[(id, Finger::Move(x, y))])
+    }
+
+    /// Lift the finger of touch `id`.
+    pub fn touch_end(&self, id: u32) -> Result<()> {
+        self.send_frame(&[(id, Finger::Up)])
+    }
+
+    /// Pinch with `fingers` fingers spread evenly around `center`, moving them from
+    /// `start_dist` to `end_dist` pixels apart over `duration`. Spreading the fingers usually
+    /// zooms in, bringing them together zooms out. The fingers move together, in frames
+    /// [`Self::set_frame_interval`] apart.
+    ///
+    /// The fingers are lifted again even if this fails halfway. Their touch ids are above
+    /// `0x8000_0000`, and mustn't be used by fingers already down.
+    pub fn pinch(
+        &self,
+        center: (i16, i16),
+        start_dist: u16,
+        end_dist: u16,
+        duration: Duration,
+        fingers: u8,
+    ) -> Result<()> {
+        if fingers < 2 {
+            return Err(Error::TooFewFingers(fingers));
+        }
+        let center = (center.0.into(), center.1.into());
+        let frames = gesture::pinch(center, start_dist, end_dist, fingers, self.frames(duration));
+        self.play_gesture(&frames, duration)
+    }
+
+    /// Turn two fingers `degrees` around `center` over `duration`, clockwise for positive
+    /// `degrees`, like [`Self::pinch`].
+    pub fn rotate(&self, center: (i16, i16), degrees: f64, duration: Duration) -> Result<()> {
+        let center = (center.0.into(), center.1.into());
+        let frames = gesture::rotate(center, degrees, self.frames(duration));
+        self.play_gesture(&frames, duration)
+    }
+
+    /// How many times the fingers move in a gesture lasting `duration`.
+    fn frames(&self, duration: Duration) -> usize {
+        let interval = self.frame_interval.as_nanos().max(1);
+        (duration.as_nanos() / interval).max(1) as usize
+    }
+
+    /// Put one finger down for each position of the first frame, move them through the rest
+    /// over `duration`, and lift them again. Fingers that went down are lifted even if the
+    /// gesture fails halfway, so no touch is left active.
+    fn play_gesture(&self, frames: &[Vec<(f64, f64)>], duration: Duration) -> Result<()> {
+        /// Touch ids of gestures, far from those callers of `touch_begin` are likely to use.
+        const FIRST_TOUCH_ID: u32 = 0x8000_0000;
+        let Some((first, rest)) = frames.split_first() else {
+            return Ok(());
+        };
+        let ids: Vec<u32> = (FIRST_TOUCH_ID..).take(first.len()).collect();
+        let down: Vec<_> = ids
+            .iter()
+            .zip(first)
+            .map(|(&id, &(x, y))| (id, Finger::Down(x, y)))
+            .collect();
+        // All or none of the fingers go down.
+        self.send_frame(&down)?;
+        let interval = duration / rest.len().max(1) as u32;
+        let result = rest.iter().try_for_each(|frame| {
+            std::thread::sleep(interval);
+            let moves: Vec<_> = ids
+                .iter()
+                .zip(frame)
+                .map(|(&id, &(x, y))| (id, Finger::Move(x, y)))
+                .collect();
+            self.send_frame(&moves)
+        });
+        let up: Vec<_> = ids.iter().map(|&id| (id, Finger::Up)).collect();
+        let lifted = self.send_frame(&up);
+        result.and(lifted)
+    }
+
+    /// Send what happens to each finger in `changes` as one frame, so clients see the fingers
+    /// move at the same time. Nothing is sent if one of the changes is invalid.
+    fn send_frame(&self, changes: &[(u32, Finger)]) -> Result<()> {
+        let mut fingers = lock(&self.fingers);
+        let mut slots = fingers.slots;
+        let mut next_tracking_id = fingers.next_tracking_id;
+        let was_touching = slots.iter().any(Option::is_some);
+        let mut events = Vec::new();
+        for &(id, change) in changes {
+            let active = slots
+                .iter()
+                .position(|slot| slot.is_some_and(|(slot_id, _)| slot_id == id));
+            let slot = match (change, active) {
+                (Finger::Down(..), None) => slots
+                    .iter()
+                    .position(Option::is_none)
+                    .ok_or(Error::Unsupported("more than 10 simultaneous touches"))?,
+                (Finger::Move(..) | Finger::Up, Some(slot)) => slot,
+                _ => return Err(Error::InvalidTouch(id)),
+            };
+            events.push((EV_ABS, ABS_MT_SLOT, slot as i32));
+            match change {
+                Finger::Down(x, y) | Finger::Move(x, y) => {
+                    if let Finger::Down(..) = change {
+                        events.push((EV_ABS, ABS_MT_TRACKING_ID, next_tracking_id.into()));
+                        next_tracking_id = next_tracking_id.wrapping_add(1);
+                    }
+                    let position = self.clamp(x, y);
+                    slots[slot] = Some((id, position));
+                    events.extend([
+                        (EV_ABS, ABS_MT_POSITION_X, position.0),
+                        (EV_ABS, ABS_MT_POSITION_Y, position.1),
+                    ]);
+                }
+                Finger::Up => {
+                    slots[slot] = None;
+                    events.push((EV_ABS, ABS_MT_TRACKING_ID, -1));
+                }
+            }
+        }
+        // Single-touch clients follow the first finger that is down. The kernel drops values
+        // that didn't change.
+        let first = slots.iter().flatten().next();
+        if let Some(&(_, (x, y))) = first {
+            events.extend([(EV_ABS, ABS_X, x), (EV_ABS, ABS_Y, y)]);
+        }
+        let touching = first.is_some();
+        if touching != was_touching {
+            events.push((EV_KEY, BTN_TOUCH, i32::from(touching)));
+        }
+        send(&self.file, &events)?;
+        fingers.slots = slots;
+        fingers.next_tracking_id = next_tracking_id;
+        Ok(())
+    }
+
+    /// `(x, y)` rounded to a pixel on the screen.
+    fn clamp(&self, x: f64, y: f64) -> (i32, i32) {
+        let clamp = |v: f64, size: u16| (v.round() as i32).clamp(0, i32::from(size.max(1)) - 1);
+        (clamp(x, self.size.0), clamp(y, self.size.1))
+    }
+}
+
+impl Drop for UinputTouchscreen {
+    fn drop(&mut self) {
+        let up: Vec<_> = lock(&self.fingers)
+            .slots
+            .iter()
+            .flatten()
+            .map(|&(id, _)| (id, Finger::Up))
+            .collect();
+        if !up.is_empty() {
+            let _ = self.send_frame(&up);
+        }
+        let _ = ioctl(&self.file, UI_DEV_DESTROY, 0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use xkbcommon::xkb;
+
+    use super::{UinputBackend, UinputTouchscreen, EV_ABS, EV_KEY, EV_SYN};
+    use crate::{Error, InputBackend, KeymapState};
+
+    const KEY_A: u16 = 30;
+
+    #[test]
+    fn test_uinput_key() {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_names(&context, "", "", "us", "", None, 0).unwrap();
+        let backend =
+            match UinputBackend::with_keymap(KeymapState::new(&context, keymap), (1024, 768)) {
+                Err(Error::UinputPermissionDenied) => return,
+                Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => return,
+                result => result.unwrap(),
+            };
+
+        // Read our own events back from the event device.
+        let mut device = std::fs::File::open(super::event_node(&backend.file).unwrap()).unwrap();
+
+        backend.key('a' as u32, true).unwrap();
+        let mut event: libc::input_event = unsafe { std::mem::zeroed() };
+        let buf = unsafe {
+            std::slice::from_raw_parts_mut(
+                &mut event as *mut _ as *mut u8,
+                std::mem::size_of::<libc::input_event>(),
+            )
+        };
+        device.read_exact(buf).unwrap();
+        assert_eq!((event.type_, event.code, event.value), (EV_KEY, KEY_A, 1));
+        backend.key('a' as u32, false).unwrap();
+    }
+
+    #[test]
+    fn test_uinput_clamp() {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_names(&context, "", "", "us", "", None, 0).unwrap();
+        let backend = match UinputBackend::with_keymap(KeymapState::new(&context, keymap), (4, 3)) {
+            Err(Error::UinputPermissionDenied) => return,
+            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => return,
+            result => result.unwrap(),
+        };
+        let mut device = std::fs::File::open(super::event_node(&backend.file).unwrap()).unwrap();
+        backend.move_cursor(10, 10).unwrap();
+        let mut events: [libc::input_event; 2] = unsafe { std::mem::zeroed() };
+        let buf = unsafe {
+            std::slice::from_raw_parts_mut(
+                events.as_mut_ptr() as *mut u8,
+                std::mem::size_of_val(&events),
+            )
+        };
+        device.read_exact(buf).unwrap();
+        let values: Vec<_> = events.iter().map(|e| (e.code, e.value)).collect();
+        assert_eq!(values, [(super::ABS_X, 3), (super::ABS_Y, 2)]);
+    }
+
+    /// Read the events of `device` up to the next `SYN_REPORT`.
+    fn read_frame(device: &mut std::fs::File) -> Vec<(u16, u16, i32)> {
+        let mut events = Vec::new();
+        loop {
+            let mut event: libc::input_event = unsafe { std::mem::zeroed() };
+            let buf = unsafe {
+                std::slice::from_raw_parts_mut(
+                    &mut event as *mut _ as *mut u8,
+                    std::mem::size_of::<libc::input_event>(),
+                )
+            };
+            device.read_exact(buf).unwrap();
+            if event.type_ == EV_SYN {
+                return events;
+            }
+            events.push((event.type_, event.code, event.value));
+        }
+    }
+
+    #[test]
+    fn test_uinput_touch() {
+        let touchscreen = match UinputTouchscreen::with_size((1024, 768)) {
+            Err(Error::UinputPermissionDenied) => return,
+            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => return,
+            result => result.unwrap(),
+        };
+        let mut device =
+            std::fs::File::open(super::event_node(&touchscreen.file).unwrap()).unwrap();
+
+        touchscreen.touch_begin(1, 10.0, 20.0).unwrap();
+        let events = read_frame(&mut device);
+        assert!(events.contains(&(EV_ABS, super::ABS_MT_TRACKING_ID, 0)));
+        assert!(events.contains(&(EV_ABS, super::ABS_MT_POSITION_X, 10)));
+        assert!(events.contains(&(EV_KEY, super::BTN_TOUCH, 1)));
+
+        // A second finger, down at the same time as the first.
+        touchscreen.touch_begin(2, 30.0, 40.0).unwrap();
+        let events = read_frame(&mut device);
+        assert!(events.contains(&(EV_ABS, super::ABS_MT_SLOT, 1)));
+        assert!(events.contains(&(EV_ABS, super::ABS_MT_TRACKING_ID, 1)));
+        assert!(matches!(
+            touchscreen.touch_begin(2, 0.0, 0.0),
+            Err(Error::InvalidTouch(2))
+        ));
+        assert!(matches!(
+            touchscreen.touch_update(3, 0.0, 0.0),
+            Err(Error::InvalidTouch(3))
+        ));
+
+        touchscreen.touch_end(1).unwrap();
+        touchscreen.touch_end(2).unwrap();
+        read_frame(&mut device);
+        let events = read_frame(&mut device);
+        assert!(events.contains(&(EV_ABS, super::ABS_MT_TRACKING_ID, -1)));
+        assert!(events.contains(&(EV_KEY, super::BTN_TOUCH, 0)));
+    }
+
+    #[test]
+    fn test_uinput_pinch() {
+        use std::time::Duration;
+        let touchscreen = match UinputTouchscreen::with_size((1024, 768)) {
+            Err(Error::UinputPermissionDenied) => return,
+            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => return,
+            result => result.unwrap(),
+        };
+        let duration = Duration::from_millis(50);
+        assert!(matches!(
+            touchscreen.pinch((100, 100), 50, 200, duration, 1),
+            Err(Error::TooFewFingers(1))
+        ));
+        touchscreen.pinch((100, 100), 50, 200, duration, 3).unwrap();
+        touchscreen.rotate((100, 100), 90.0, duration).unwrap();
+        // Every finger was lifted.
+        assert!(touchscreen
+            .fingers
+            .lock()
+            .unwrap()
+            .slots
+            .iter()
+            .all(Option::is_none));
+    }
+}
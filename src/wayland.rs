@@ -0,0 +1,357 @@
+//! Input injection on Wayland compositors, through the virtual keyboard and virtual pointer
+//! protocols. These are implemented by wlroots based compositors, among others.
+
+use std::io::{Seek, Write};
+use std::os::fd::AsFd;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::{wl_output, wl_pointer, wl_registry, wl_seat};
+use wayland_client::{delegate_noop, Connection, Dispatch, EventQueue, QueueHandle, WEnum};
+use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::{
+    zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1,
+    zwp_virtual_keyboard_v1::ZwpVirtualKeyboardV1,
+};
+use wayland_protocols_wlr::virtual_pointer::v1::client::{
+    zwlr_virtual_pointer_manager_v1::ZwlrVirtualPointerManagerV1,
+    zwlr_virtual_pointer_v1::ZwlrVirtualPointerV1,
+};
+use xkbcommon::xkb;
+
+use crate::{lock, Error, InputBackend, Key, KeymapState, Result};
+
+/// `WL_KEYBOARD_KEYMAP_FORMAT_XKB_V1`
+const KEYMAP_FORMAT_XKB_V1: u32 = 1;
+/// XKB keycodes are evdev keycodes plus 8.
+const EVDEV_OFFSET: u32 = 8;
+
+fn wayland_error(e: impl std::fmt::Display) -> Error {
+    Error::Wayland(e.to_string())
+}
+
+/// Position and size of an output in the compositor's coordinate space.
+#[derive(Debug, Default, Clone, Copy)]
+struct Output {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    scale: i32,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    outputs: Vec<Output>,
+}
+
+impl State {
+    /// The bounding box of all outputs, as `(x, y, width, height)`.
+    fn extent(&self) -> (i32, i32, u32, u32) {
+        let boxes = self.outputs.iter().map(|o| {
+            let scale = o.scale.max(1);
+            (o.x, o.y, o.x + o.width / scale, o.y + o.height / scale)
+        });
+        let (x0, y0, x1, y1) = boxes.fold(
+            (i32::MAX, i32::MAX, i32::MIN, i32::MIN),
+            |(x0, y0, x1, y1), (bx0, by0, bx1, by1)| {
+                (x0.min(bx0), y0.min(by0), x1.max(bx1), y1.max(by1))
+            },
+        );
+        if x0 >= x1 || y0 >= y1 {
+            return (0, 0, 1, 1);
+        }
+        (x0, y0, (x1 - x0) as u32, (y1 - y0) as u32)
+    }
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_output::WlOutput, usize> for State {
+    fn event(
+        state: &mut Self,
+        _: &wl_output::WlOutput,
+        event: wl_output::Event,
+        &index: &usize,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let Some(output) = state.outputs.get_mut(index) else {
+            return;
+        };
+        match event {
+            wl_output::Event::Geometry { x, y, .. } => {
+                output.x = x;
+                output.y = y;
+            }
+            wl_output::Event::Mode {
+                flags: WEnum::Value(flags),
+                width,
+                height,
+                ..
+            } if flags.contains(wl_output::Mode::Current) => {
+                output.width = width;
+                output.height = height;
+            }
+            wl_output::Event::Scale { factor } => output.scale = factor,
+            _ => (),
+        }
+    }
+}
+
+delegate_noop!(State: ignore wl_seat::WlSeat);
+delegate_noop!(State: ZwpVirtualKeyboardManagerV1);
+delegate_noop!(State: ZwpVirtualKeyboardV1);
+delegate_noop!(State: ZwlrVirtualPointerManagerV1);
+delegate_noop!(State: ZwlrVirtualPointerV1);
+
+struct Keyboard {
+    keymap: KeymapState,
+    /// Tracks modifiers, which we have to send to the compositor ourselves.
+    state: xkb::State,
+}
+
+// SAFETY: `state` is created from the keymap in `keymap` and shares its reference counts, see
+// the comment on `KeymapState`.
+unsafe impl Send for Keyboard {}
+
+/// Injects input into a Wayland compositor that supports the `zwp_virtual_keyboard_v1` and
+/// `zwlr_virtual_pointer_v1` protocols.
+///
+/// Coordinates are in the compositor's global space, where outputs are placed side by side.
+/// The virtual keyboard uses a keymap compiled from the XKB defaults, which is uploaded to the
+/// compositor.
+pub struct WaylandBackend {
+    connection: Connection,
+    // Kept alive for the objects created on it.
+    _queue: Mutex<EventQueue<State>>,
+    keyboard: ZwpVirtualKeyboardV1,
+    pointer: ZwlrVirtualPointerV1,
+    /// Origin and size of the area covered by all outputs.
+    extent: (i32, i32, u32, u32),
+    keyboard_state: Mutex<Keyboard>,
+    start: Instant,
+}
+
+impl WaylandBackend {
+    /// Connect to the compositor named by `$WAYLAND_DISPLAY`.
+    pub fn new() -> Result<Self> {
+        let connection = Connection::connect_to_env().map_err(wayland_error)?;
+        let (globals, mut queue) =
+            registry_queue_init::<State>(&connection).map_err(wayland_error)?;
+        let qh = queue.handle();
+        let seat: wl_seat::WlSeat = globals.bind(&qh, 1..=1, ()).map_err(wayland_error)?;
+        let keyboard_manager: ZwpVirtualKeyboardManagerV1 =
+            globals.bind(&qh, 1..=1, ()).map_err(wayland_error)?;
+        let pointer_manager: ZwlrVirtualPointerManagerV1 =
+            globals.bind(&qh, 1..=1, ()).map_err(wayland_error)?;
+
+        let mut state = State::default();
+        globals.contents().with_list(|list| {
+            for global in list.iter().filter(|g| g.interface == "wl_output") {
+                let _: wl_output::WlOutput = globals.registry().bind(
+                    global.name,
+                    global.version.min(2),
+                    &qh,
+                    state.outputs.len(),
+                );
+                state.outputs.push(Output::default());
+            }
+        });
+        queue.roundtrip(&mut state).map_err(wayland_error)?;
+
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_names(&context, "", "", "", "", None, 0).ok_or(
+            Error::Wayland("failed to compile the default keymap".into()),
+        )?;
+        let keyboard = keyboard_manager.create_virtual_keyboard(&seat, &qh, ());
+        let mut file = tempfile()?;
+        file.write_all(keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1).as_bytes())?;
+        file.write_all(b"\0")?;
+        let size = file.stream_position()?;
+        keyboard.keymap(KEYMAP_FORMAT_XKB_V1, file.as_fd(), size as u32);
+        let pointer = pointer_manager.create_virtual_pointer(Some(&seat), &qh, ());
+        connection.flush().map_err(wayland_error)?;
+
+        let state_xkb = xkb::State::new(&keymap);
+        Ok(Self {
+            connection,
+            _queue: Mutex::new(queue),
+            keyboard,
+            pointer,
+            extent: state.extent(),
+            keyboard_state: Mutex::new(Keyboard {
+                keymap: KeymapState::new(&context, keymap),
+                state: state_xkb,
+            }),
+            start: Instant::now(),
+        })
+    }
+
+    /// Event timestamp, in milliseconds.
+    fn time(&self) -> u32 {
+        self.start.elapsed().as_millis() as u32
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.connection.flush().map_err(wayland_error)
+    }
+
+    /// Send a key event, and the modifiers that results in.
+    fn send_key(&self, keyboard: &mut Keyboard, keycode: u32, press: bool) {
+        let (state, direction) = if press {
+            (1, xkb::KeyDirection::Down)
+        } else {
+            (0, xkb::KeyDirection::Up)
+        };
+        self.keyboard
+            .key(self.time(), keycode - EVDEV_OFFSET, state);
+        keyboard.state.update_key(keycode, direction);
+        self.keyboard.modifiers(
+            keyboard.state.serialize_mods(xkb::STATE_MODS_DEPRESSED),
+            keyboard.state.serialize_mods(xkb::STATE_MODS_LATCHED),
+            keyboard.state.serialize_mods(xkb::STATE_MODS_LOCKED),
+            keyboard.state.serialize_layout(xkb::STATE_LAYOUT_EFFECTIVE),
+        );
+    }
+}
+
+/// An anonymous file to pass the keymap through.
+fn tempfile() -> Result<std::fs::File> {
+    let dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let path = dir.join(format!(
+        "inputsynth-keymap-{}-{}",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    std::fs::remove_file(&path)?;
+    Ok(file)
+}
+
+impl InputBackend for WaylandBackend {
+    fn move_cursor(&self, x: i16, y: i16) -> Result<()> {
+        let (x0, y0, width, height) = self.extent;
+        let x = (i32::from(x) - x0).clamp(0, width as i32) as u32;
+        let y = (i32::from(y) - y0).clamp(0, height as i32) as u32;
+        self.pointer
+            .motion_absolute(self.time(), x, y, width, height);
+        self.pointer.frame();
+        self.flush()
+    }
+
+    fn click(&self, x: i16, y: i16, button: u8, press: bool) -> Result<()> {
+        /// Linux input event codes for X buttons 1 to 3, 8 and 9.
+        const BTN_LEFT: u32 = 0x110;
+        const BTN_RIGHT: u32 = 0x111;
+        const BTN_MIDDLE: u32 = 0x112;
+        const BTN_SIDE: u32 = 0x113;
+        const BTN_EXTRA: u32 = 0x114;
+        /// Scroll distance of one wheel step, as used by libinput.
+        const SCROLL_STEP: f64 = 15.0;
+        self.move_cursor(x, y)?;
+        let code = match button {
+            1 => BTN_LEFT,
+            2 => BTN_MIDDLE,
+            3 => BTN_RIGHT,
+            4..=7 => {
+                // Scrolling happens on press, there is nothing to release.
+                if press {
+                    let (axis, sign) = match button {
+                        4 => (wl_pointer::Axis::VerticalScroll, -1),
+                        5 => (wl_pointer::Axis::VerticalScroll, 1),
+                        6 => (wl_pointer::Axis::HorizontalScroll, -1),
+                        _ => (wl_pointer::Axis::HorizontalScroll, 1),
+                    };
+                    self.pointer.axis_source(wl_pointer::AxisSource::Wheel);
+                    self.pointer.axis_discrete(
+                        self.time(),
+                        axis,
+                        SCROLL_STEP * f64::from(sign),
+                        sign,
+                    );
+                    self.pointer.frame();
+                }
+                return self.flush();
+            }
+            8 => BTN_SIDE,
+            9 => BTN_EXTRA,
+            _ => return Err(Error::Unsupported("buttons above 9 on Wayland")),
+        };
+        let state = if press {
+            wl_pointer::ButtonState::Pressed
+        } else {
+            wl_pointer::ButtonState::Released
+        };
+        self.pointer.button(self.time(), code, state);
+        self.pointer.frame();
+        self.flush()
+    }
+
+    fn key(&self, keysym: u32, press: bool) -> Result<()> {
+        let mut keyboard = lock(&self.keyboard_state);
+        let (mods, keycode) = keyboard
+            .keymap
+            .find_key_sequence(Key::new(keysym))
+            .ok_or(Error::NoKeySequence { keysym })?;
+        if press {
+            for &m in &mods {
+                self.send_key(&mut keyboard, m, true);
+            }
+            self.send_key(&mut keyboard, keycode, true);
+        } else {
+            self.send_key(&mut keyboard, keycode, false);
+            for &m in mods.iter().rev() {
+                self.send_key(&mut keyboard, m, false);
+            }
+        }
+        self.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Output, State};
+
+    #[test]
+    fn test_output_extent() {
+        assert_eq!(State::default().extent(), (0, 0, 1, 1));
+        let state = State {
+            outputs: vec![
+                Output {
+                    x: 0,
+                    y: 0,
+                    width: 3840,
+                    height: 2160,
+                    scale: 2,
+                },
+                Output {
+                    x: 1920,
+                    y: 0,
+                    width: 1280,
+                    height: 1024,
+                    scale: 1,
+                },
+            ],
+        };
+        assert_eq!(state.extent(), (0, 0, 3200, 1080));
+    }
+}
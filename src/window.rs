@@ -0,0 +1,136 @@
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    self, AtomEnum, ClientMessageEvent, ConfigureWindowAux, ConnectionExt as _, EventMask,
+    InputFocus, KeyButMask, KeyPressEvent, StackMode,
+};
+
+use crate::clipboard::intern;
+use crate::Result;
+
+/// The `WM_NAME` of `window`, if it has one.
+pub(crate) fn window_name(
+    conn: &impl Connection,
+    window: xproto::Window,
+) -> Result<Option<String>> {
+    /// Longer names are cut off, they only go into error messages.
+    const MAX_LENGTH: u32 = 256;
+    let reply = conn
+        .get_property(
+            false,
+            window,
+            AtomEnum::WM_NAME,
+            AtomEnum::ANY,
+            0,
+            MAX_LENGTH / 4,
+        )?
+        .reply()?;
+    if reply.format != 8 || reply.value.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(String::from_utf8_lossy(&reply.value).into_owned()))
+}
+
+/// Whether `window` is `ancestor` or one of its descendants.
+pub(crate) fn is_within(
+    conn: &impl Connection,
+    mut window: xproto::Window,
+    ancestor: xproto::Window,
+) -> Result<bool> {
+    // Focus can also be PointerRoot or None, which aren't windows.
+    while window != ancestor && window > 1 {
+        let tree = conn.query_tree(window)?.reply()?;
+        if window == tree.root {
+            return Ok(false);
+        }
+        window = tree.parent;
+    }
+    Ok(window == ancestor)
+}
+
+/// Ask the window manager to activate `window`, or focus and raise it ourselves if the window
+/// manager doesn't support `_NET_ACTIVE_WINDOW`.
+pub(crate) fn activate(
+    conn: &impl Connection,
+    root: xproto::Window,
+    window: xproto::Window,
+) -> Result<()> {
+    let net_supported = intern(conn, b"_NET_SUPPORTED")?;
+    let net_active_window = intern(conn, b"_NET_ACTIVE_WINDOW")?;
+    let supported = conn
+        .get_property(false, root, net_supported, AtomEnum::ATOM, 0, u32::MAX)?
+        .reply()?;
+    let ewmh = supported
+        .value32()
+        .is_some_and(|mut atoms| atoms.any(|atom| atom == net_active_window));
+    if ewmh {
+        // Source indication 2 is a pager, which window managers trust to mean the user asked.
+        let event = ClientMessageEvent::new(
+            32,
+            window,
+            net_active_window,
+            [2, x11rb::CURRENT_TIME, 0, 0, 0],
+        );
+        conn.send_event(
+            false,
+            root,
+            EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+            event,
+        )?;
+    } else {
+        conn.set_input_focus(InputFocus::PARENT, window, x11rb::CURRENT_TIME)?;
+        conn.configure_window(
+            window,
+            &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE),
+        )?;
+    }
+    conn.flush()?;
+    Ok(())
+}
+
+/// Send a key or button event straight to `window` with `SendEvent`, as if the pointer was at
+/// `position` in it, or where it actually is if `None`. `response_type` is one of the key and
+/// button press and release event codes, and `detail` the keycode or button.
+pub(crate) fn send_input_event(
+    conn: &impl Connection,
+    window: xproto::Window,
+    response_type: u8,
+    detail: u8,
+    state: u16,
+    position: Option<(i16, i16)>,
+) -> Result<()> {
+    let pointer = conn.query_pointer(window)?.reply()?;
+    let (event_x, event_y, root_x, root_y) = match position {
+        Some((x, y)) => {
+            let translated = conn
+                .translate_coordinates(window, pointer.root, x, y)?
+                .reply()?;
+            (x, y, translated.dst_x, translated.dst_y)
+        }
+        None => (pointer.win_x, pointer.win_y, pointer.root_x, pointer.root_y),
+    };
+    // All core key and button events share the layout of KeyPress.
+    let event = KeyPressEvent {
+        response_type,
+        detail,
+        sequence: 0,
+        time: x11rb::CURRENT_TIME,
+        root: pointer.root,
+        event: window,
+        child: x11rb::NONE,
+        root_x,
+        root_y,
+        event_x,
+        event_y,
+        state: KeyButMask::from(state),
+        same_screen: pointer.same_screen,
+    };
+    let mask = match response_type {
+        xproto::KEY_PRESS_EVENT => EventMask::KEY_PRESS,
+        xproto::KEY_RELEASE_EVENT => EventMask::KEY_RELEASE,
+        xproto::BUTTON_PRESS_EVENT => EventMask::BUTTON_PRESS,
+        _ => EventMask::BUTTON_RELEASE,
+    };
+    conn.send_event(false, window, mask, event)?;
+    conn.flush()?;
+    Ok(())
+}
@@ -0,0 +1,125 @@
+//! Injection of XInput 1 device events through XTEST, which is the only way for a client to
+//! send events carrying valuators other than the pointer position.
+
+use std::io::IoSlice;
+
+use x11rb::connection::RequestConnection;
+use x11rb::protocol::xinput::Fp3232;
+use x11rb::protocol::xtest;
+
+use crate::{Error, Result};
+
+/// XInput 1 event type offsets, from XI.h.
+const DEVICE_VALUATOR: u8 = 0;
+pub(crate) const DEVICE_KEY_PRESS: u8 = 1;
+pub(crate) const DEVICE_KEY_RELEASE: u8 = 2;
+pub(crate) const DEVICE_BUTTON_PRESS: u8 = 3;
+pub(crate) const DEVICE_BUTTON_RELEASE: u8 = 4;
+pub(crate) const DEVICE_MOTION_NOTIFY: u8 = 5;
+pub(crate) const PROXIMITY_IN: u8 = 8;
+pub(crate) const PROXIMITY_OUT: u8 = 9;
+/// Set on the device id of an XInput 1 event that is followed by valuator events.
+const MORE_EVENTS: u8 = 0x80;
+/// XTEST minor opcode of FakeInput.
+const XTEST_FAKE_INPUT: u8 = 2;
+
+pub(crate) fn fp3232_to_f64(v: Fp3232) -> f64 {
+    v.integral as f64 + v.frac as f64 / (1u64 << 32) as f64
+}
+
+/// A deviceKeyButtonPointer event, everything but the type and device is filled in by the
+/// server.
+pub(crate) fn pointer_event(type_: u8, device_id: u8, more_events: bool) -> [u8; 32] {
+    let mut event = [0; 32];
+    event[0] = type_;
+    event[31] = device_id | if more_events { MORE_EVENTS } else { 0 };
+    event
+}
+
+/// A key or button event of `device_id`, for the key or button `detail`.
+pub(crate) fn device_event(type_: u8, device_id: u8, detail: u8) -> [u8; 32] {
+    let mut event = pointer_event(type_, device_id, false);
+    event[1] = detail;
+    event
+}
+
+fn valuator_event(
+    xi_first_event: u8,
+    device_id: u8,
+    first: u8,
+    values: &[i32],
+    more: bool,
+) -> [u8; 32] {
+    let mut event = [0; 32];
+    event[0] = xi_first_event + DEVICE_VALUATOR;
+    event[1] = device_id | if more { MORE_EVENTS } else { 0 };
+    event[6] = values.len() as u8;
+    event[7] = first;
+    for (i, v) in values.iter().enumerate() {
+        event[8 + i * 4..12 + i * 4].copy_from_slice(&v.to_ne_bytes());
+    }
+    event
+}
+
+/// A motion event for `device_id`, setting valuators starting from `first_valuator` to `values`.
+pub(crate) fn motion_events(
+    xi_first_event: u8,
+    device_id: u8,
+    first_valuator: u8,
+    values: &[i32],
+) -> Vec<[u8; 32]> {
+    let mut events = vec![pointer_event(
+        xi_first_event + DEVICE_MOTION_NOTIFY,
+        device_id,
+        true,
+    )];
+    // Each valuator event carries at most 6 valuators.
+    for (i, chunk) in values.chunks(6).enumerate() {
+        let more = (i + 1) * 6 < values.len();
+        let first = first_valuator + i as u8 * 6;
+        events.push(valuator_event(
+            xi_first_event,
+            device_id,
+            first,
+            chunk,
+            more,
+        ));
+    }
+    events
+}
+
+/// Send a FakeInput request carrying several events. x11rb's `xtest_fake_input` can only carry
+/// one event, but XInput device events need to be followed by their valuators.
+pub(crate) fn fake_input(conn: &impl RequestConnection, events: &[[u8; 32]]) -> Result<()> {
+    let xtest = conn
+        .extension_information(xtest::X11_EXTENSION_NAME)?
+        .ok_or(Error::Unsupported("XTEST"))?;
+    let length = (1 + events.len() * 8) as u16;
+    let mut header = [xtest.major_opcode, XTEST_FAKE_INPUT, 0, 0];
+    header[2..].copy_from_slice(&length.to_ne_bytes());
+    let mut bufs = vec![IoSlice::new(&header)];
+    bufs.extend(events.iter().map(|e| IoSlice::new(e)));
+    conn.send_request_without_reply(&bufs, Vec::new())?
+        .check()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn test_motion_events() {
+        let events = super::motion_events(100, 3, 2, &[1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(events.len(), 3);
+        assert_eq!((events[0][0], events[0][31]), (105, 3 | 0x80));
+        assert_eq!((events[1][0], events[1][1]), (100, 3 | 0x80));
+        assert_eq!((events[1][6], events[1][7]), (6, 2));
+        assert_eq!((events[2][1], events[2][6], events[2][7]), (3, 1, 8));
+        assert_eq!(events[2][8..12], 7i32.to_ne_bytes());
+    }
+
+    #[test]
+    fn test_device_event() {
+        let event = super::device_event(100 + super::DEVICE_KEY_PRESS, 7, 38);
+        assert_eq!((event[0], event[1], event[31]), (101, 38, 7));
+    }
+}
@@ -0,0 +1,147 @@
+//! Safe wrappers for libxkbcommon functions that xkbcommon-rs doesn't bind.
+
+#[cfg(feature = "xcb-ffi")]
+use xkbcommon::xkb::x11::ffi::xkb_x11_keymap_new_from_device;
+use xkbcommon::xkb::{
+    ffi::{xkb_keymap, xkb_mod_mask_t},
+    Context, Keycode, Keymap, KeymapCompileFlags, LayoutIndex, LevelIndex, ModMask,
+};
+
+extern "C" {
+    fn xkb_keymap_key_get_mods_for_level(
+        keymap: *mut xkb_keymap,
+        key: Keycode,
+        layout: LayoutIndex,
+        level: LevelIndex,
+        masks_out: *mut xkb_mod_mask_t,
+        masks_size: usize,
+    ) -> usize;
+}
+
+/// `None` if libxkbcommon failed to fetch or compile the keymap.
+#[cfg(feature = "xcb-ffi")]
+#[must_use]
+pub(super) fn keymap_new_from_device(
+    context: &Context,
+    connection: &x11rb::xcb_ffi::XCBConnection,
+    device_id: i32,
+    flags: KeymapCompileFlags,
+) -> Option<Keymap> {
+    let keymap = unsafe {
+        xkb_x11_keymap_new_from_device(
+            context.get_raw_ptr(),
+            connection.get_raw_xcb_connection() as *mut _,
+            device_id,
+            flags,
+        )
+    };
+    (!keymap.is_null()).then(|| unsafe { Keymap::from_raw_ptr(keymap) })
+}
+
+/// libxkbcommon can only fetch keymaps through libxcb, so the keymap has to be built from the
+/// core mapping instead.
+#[cfg(not(feature = "xcb-ffi"))]
+pub(super) fn keymap_new_from_device(
+    _: &Context,
+    _: &super::XConnection,
+    _: i32,
+    _: KeymapCompileFlags,
+) -> Option<Keymap> {
+    None
+}
+
+/// The modifier masks that select `level` of `keycode` in `layout`, in the order the key type
+/// lists them. Empty if the key has no such level.
+pub(super) fn mods_for_level(
+    keymap: &Keymap,
+    keycode: Keycode,
+    layout: LayoutIndex,
+    level: LevelIndex,
+) -> Vec<ModMask> {
+    let raw = keymap.get_raw_ptr();
+    assert!(!raw.is_null());
+    // Most levels are reached by a mask or two. libxkbcommon fills in as many masks as fit, so
+    // grow the buffer until some room is left.
+    let mut masks = vec![0; 4];
+    loop {
+        // SAFETY: `raw` is a valid keymap for the lifetime of `keymap`, and `masks` has room
+        // for the number of masks we pass.
+        let count = unsafe {
+            xkb_keymap_key_get_mods_for_level(
+                raw,
+                keycode,
+                layout,
+                level,
+                masks.as_mut_ptr(),
+                masks.len(),
+            )
+        };
+        if count < masks.len() {
+            masks.truncate(count);
+            return masks;
+        }
+        masks.resize(masks.len() * 2, 0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use xkbcommon::xkb;
+
+    use super::mods_for_level;
+
+    /// Level 2 of `<A>` is reached through six different masks.
+    const KEYMAP: &str = r#"
+xkb_keymap {
+    xkb_keycodes { <A> = 38; };
+    xkb_types {
+        type "MANY" {
+            modifiers = Shift + Lock + Control;
+            map[Shift] = Level2;
+            map[Lock] = Level2;
+            map[Control] = Level2;
+            map[Shift + Lock] = Level2;
+            map[Shift + Control] = Level2;
+            map[Lock + Control] = Level2;
+        };
+    };
+    xkb_compatibility { };
+    xkb_symbols {
+        key <A> { type = "MANY", [ a, A ] };
+    };
+};
+"#;
+
+    #[test]
+    fn test_mods_for_level() {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_string(
+            &context,
+            KEYMAP.to_owned(),
+            xkb::KEYMAP_FORMAT_TEXT_V1,
+            xkb::COMPILE_NO_FLAGS,
+        )
+        .unwrap();
+        let mask = |name| 1 << keymap.mod_get_index(name);
+        let (shift, lock, control) = (
+            mask(xkb::MOD_NAME_SHIFT),
+            mask(xkb::MOD_NAME_CAPS),
+            mask(xkb::MOD_NAME_CTRL),
+        );
+        assert!(mods_for_level(&keymap, 38, 0, 0).contains(&0));
+        let mut level2 = mods_for_level(&keymap, 38, 0, 1);
+        level2.sort_unstable();
+        let mut expected = [
+            shift,
+            lock,
+            control,
+            shift | lock,
+            shift | control,
+            lock | control,
+        ];
+        expected.sort_unstable();
+        assert_eq!(level2, expected);
+        assert!(mods_for_level(&keymap, 38, 0, 2).is_empty());
+        assert!(mods_for_level(&keymap, 9, 0, 0).is_empty());
+    }
+}